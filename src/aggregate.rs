@@ -0,0 +1,196 @@
+use libc::c_ulonglong;
+use ffi::core;
+use block::BasicBlock;
+use builder::{Builder, CheckFailure};
+use context::{Context, GetContext};
+use module::Module;
+use target::TargetData;
+use types::{ArrayType, PointerType, StructType, Type};
+use util::Sub;
+use value::{Linkage, Value};
+
+/// A pointer to a struct, bound to the struct type it points to.
+///
+/// This computes field addresses with the right indices and types, so fields can be
+/// loaded and stored without writing GEPs by hand.
+#[derive(Copy, Clone)]
+pub struct StructRef<'a> {
+    ty: &'a StructType,
+    ptr: &'a Value
+}
+impl<'a> StructRef<'a> {
+    /// Bind `ptr`, which must be a pointer to `ty`, to the struct type `ty`.
+    pub fn new(ty: &'a StructType, ptr: &'a Value) -> StructRef<'a> {
+        match PointerType::from_super(ptr.get_type()) {
+            Some(ptr_ty) if ptr_ty.get_element() == &**ty => (),
+            _ => panic!("expected pointer to {:?}, got {:?}", ty, ptr.get_type())
+        }
+        StructRef {
+            ty: ty,
+            ptr: ptr
+        }
+    }
+    /// Returns the struct type this is bound to.
+    pub fn get_type(&self) -> &'a StructType {
+        self.ty
+    }
+    /// Returns the pointer this is bound to.
+    pub fn get_pointer(&self) -> &'a Value {
+        self.ptr
+    }
+    /// Build an instruction that computes the address of the field at `index`.
+    pub fn field_ptr(&self, builder: &'a Builder, index: usize) -> &'a Value {
+        builder.build_struct_gep(self.ptr, index)
+    }
+    /// Build an instruction that loads the field at `index`.
+    pub fn load_field(&self, builder: &'a Builder, index: usize) -> &'a Value {
+        builder.build_load(self.field_ptr(builder, index))
+    }
+    /// Build an instruction that stores `value` in the field at `index`.
+    pub fn store_field(&self, builder: &'a Builder, index: usize, value: &'a Value) -> &'a Value {
+        let field_ptr = self.field_ptr(builder, index);
+        let field_ty = self.ty.get_elements()[index];
+        if value.get_type() != field_ty {
+            panic!("expected {:?} for field {} of {:?}, got {:?}", field_ty, index, self.ty, value.get_type())
+        }
+        builder.build_store(value, field_ptr)
+    }
+}
+
+/// The layout of a tagged union, also known as a discriminated union or sum type.
+///
+/// This is laid out as a struct of the integer tag followed by a payload that is as big as
+/// the biggest variant and as aligned as the most aligned variant.
+pub struct TaggedUnion<'a> {
+    ty: &'a StructType,
+    tag: &'a Type,
+    variants: Vec<&'a Type>
+}
+impl<'a> TaggedUnion<'a> {
+    /// Compute the layout of a tagged union with the integer tag type `tag` and the payload
+    /// types `variants` on the target given.
+    ///
+    /// Variants without a payload should use the void type.
+    pub fn new(context: &'a Context, target: &TargetData, tag: &'a Type, variants: &[&'a Type]) -> TaggedUnion<'a> {
+        assert!(tag.is_integer(), "expected integer tag, got {:?}", tag);
+        let sized: Vec<_> = variants.iter().cloned().filter(|ty| !ty.is_void()).collect();
+        let size = sized.iter().map(|ty| ty.get_size(target)).max().unwrap_or(0);
+        let byte = Type::get::<u8>(context);
+        let align_ty = sized.iter().cloned().max_by_key(|ty| target.alignment_of(ty)).unwrap_or(byte);
+        let padding = size.saturating_sub(align_ty.get_size(target));
+        let fields: [&Type; 3] = [tag, align_ty, ArrayType::new(byte, padding)];
+        TaggedUnion {
+            ty: StructType::new(context, &fields, false),
+            tag: tag,
+            variants: variants.to_vec()
+        }
+    }
+    /// Returns the struct type that values of this union have.
+    pub fn get_type(&self) -> &'a StructType {
+        self.ty
+    }
+    /// Returns the payload type of the variant at `variant`.
+    pub fn get_variant(&self, variant: usize) -> &'a Type {
+        self.variants[variant]
+    }
+    /// Returns the constant tag that marks a value as the variant at `variant`.
+    pub fn tag_value(&self, variant: usize) -> &'a Value {
+        assert!(variant < self.variants.len(), "no such variant {}", variant);
+        unsafe { core::LLVMConstInt(self.tag.into(), variant as c_ulonglong, 0) }.into()
+    }
+    /// Build instructions that allocate a union on the stack and initialize it as the
+    /// variant at `variant` with the payload given, then return a pointer to it.
+    pub fn build_new(&self, builder: &'a Builder, variant: usize, payload: Option<&'a Value>) -> &'a Value {
+        let ptr = builder.build_alloca(self.ty);
+        self.build_set_tag(builder, ptr, variant);
+        if let Some(payload) = payload {
+            builder.build_store(payload, self.build_payload_ptr(builder, ptr, variant));
+        }
+        ptr
+    }
+    /// Build an instruction that loads the tag of the union `ptr` points to.
+    pub fn build_tag(&self, builder: &'a Builder, ptr: &'a Value) -> &'a Value {
+        StructRef::new(self.ty, ptr).load_field(builder, 0)
+    }
+    /// Build an instruction that marks the union `ptr` points to as the variant at `variant`.
+    pub fn build_set_tag(&self, builder: &'a Builder, ptr: &'a Value, variant: usize) -> &'a Value {
+        StructRef::new(self.ty, ptr).store_field(builder, 0, self.tag_value(variant))
+    }
+    /// Build instructions that reinterpret the payload of the union `ptr` points to as
+    /// the payload of the variant at `variant`, and return a pointer to it.
+    pub fn build_payload_ptr(&self, builder: &'a Builder, ptr: &'a Value, variant: usize) -> &'a Value {
+        let payload = StructRef::new(self.ty, ptr).field_ptr(builder, 1);
+        builder.build_bit_cast(payload, PointerType::new(self.variants[variant]))
+    }
+    /// Build an instruction that jumps to the block of the case matching the tag of the union
+    /// `ptr` points to, or `default` if none of them match it.
+    pub fn build_switch(&self, builder: &'a Builder, ptr: &'a Value, default: &'a BasicBlock, cases: &[(usize, &'a BasicBlock)]) -> &'a Value {
+        let tag = self.build_tag(builder, ptr);
+        let cases: Vec<_> = cases.iter().map(|&(variant, block)| (self.tag_value(variant), block)).collect();
+        builder.build_switch(tag, default, &cases)
+    }
+}
+
+/// The `{ptr, len}` fat pointer representation of a slice, as used by Rust and Go.
+#[derive(Copy, Clone)]
+pub struct Slice<'a> {
+    ty: &'a StructType,
+    elem: &'a Type,
+    len: &'a Type
+}
+impl<'a> Slice<'a> {
+    /// Make the representation of a slice of `elem`, with the integer length type `len`.
+    pub fn new(context: &'a Context, elem: &'a Type, len: &'a Type) -> Slice<'a> {
+        assert!(len.is_integer(), "expected integer length, got {:?}", len);
+        Slice {
+            ty: StructType::new(context, &[PointerType::new(elem), len], false),
+            elem: elem,
+            len: len
+        }
+    }
+    /// Returns the struct type that values of this slice have.
+    pub fn get_type(&self) -> &'a StructType {
+        self.ty
+    }
+    /// Returns the element type of this slice.
+    pub fn get_element(&self) -> &'a Type {
+        self.elem
+    }
+    /// Add the text given to `module` as a private constant global, and return a constant
+    /// slice of it.
+    ///
+    /// This requires the element type of this slice to be `i8`.
+    pub fn const_str(&self, module: &'a Module, text: &str) -> &'a Value {
+        let context = module.get_context();
+        assert_eq!(self.elem, Type::get::<u8>(context));
+        let global = module.add_global_variable("str", Value::new_string(context, text, true));
+        global.set_linkage(Linkage::Private);
+        global.set_constant(true);
+        let zero: &Value = unsafe { core::LLVMConstInt(self.len.into(), 0, 0) }.into();
+        let ptr = Value::new_inbounds_gep(global, &[zero, zero]);
+        let len = unsafe { core::LLVMConstInt(self.len.into(), text.len() as c_ulonglong, 0) }.into();
+        Value::new_struct(context, &[ptr, len], false)
+    }
+    /// Build an instruction that extracts the pointer from `slice`.
+    pub fn build_ptr(&self, builder: &'a Builder, slice: &'a Value) -> &'a Value {
+        builder.build_extract_value(slice, &[0])
+    }
+    /// Build an instruction that extracts the length from `slice`.
+    pub fn build_len(&self, builder: &'a Builder, slice: &'a Value) -> &'a Value {
+        builder.build_extract_value(slice, &[1])
+    }
+    /// Build instructions that make a slice from the pointer `ptr` and length `len`.
+    pub fn build_new(&self, builder: &'a Builder, ptr: &'a Value, len: &'a Value) -> &'a Value {
+        let slice = builder.build_insert_value(Value::new_undef(self.ty), ptr, &[0]);
+        builder.build_insert_value(slice, len, &[1])
+    }
+    /// Build instructions that check `index` is in bounds of `slice`, jumping to `fail` if it isn't,
+    /// then return a pointer to the element at `index`.
+    ///
+    /// This leaves the builder positioned in a new block that runs when the check passes.
+    pub fn build_index(&self, builder: &'a Builder, slice: &'a Value, index: &'a Value, fail: &'a BasicBlock) -> &'a Value {
+        let ptr = self.build_ptr(builder, slice);
+        let len = self.build_len(builder, slice);
+        builder.build_checked_index(ptr, index, len, CheckFailure::Jump(fail))
+    }
+}
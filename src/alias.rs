@@ -0,0 +1,157 @@
+use libc::c_uint;
+use ffi::{core, target, LLVMOpcode};
+use ffi::prelude::LLVMValueRef;
+use cbox::CBox;
+use module::Module;
+use target::TargetData;
+use types::{StructType, Type};
+use util::Sub;
+use value::{Arg, Attribute, CallSite, Instruction, Value};
+
+/// How two memory locations may overlap.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AliasResult {
+    /// The locations never overlap.
+    NoAlias,
+    /// The locations might overlap, or the analysis couldn't tell.
+    MayAlias,
+    /// The locations overlap, but don't start at the same address or aren't the same size.
+    PartialAlias,
+    /// The locations start at the same address and are the same size.
+    MustAlias
+}
+
+/// Answers whether two pointers in a module can refer to the same memory.
+///
+/// The C API doesn't expose LLVM's alias analyses, so this follows the simplest rules of its
+/// basic alias analysis. Pointers are traced through bitcasts and GEPs to the object they
+/// point into, distinct allocas, globals and `noalias` arguments never alias, and constant
+/// offsets into the same object are compared. Type-based alias analysis isn't done, so
+/// anything else may alias.
+pub struct AliasAnalysis {
+    target: CBox<TargetData>
+}
+impl AliasAnalysis {
+    /// Make an alias analysis that uses the data layout of `module` to compute offsets.
+    pub fn new(module: &Module) -> AliasAnalysis {
+        AliasAnalysis {
+            target: TargetData::new(module.get_data_layout())
+        }
+    }
+    /// Returns how the `size_a` bytes at `ptr_a` may overlap with the `size_b` bytes at `ptr_b`.
+    pub fn alias(&self, ptr_a: &Value, size_a: u64, ptr_b: &Value, size_b: u64) -> AliasResult {
+        let (base_a, offset_a) = self.decompose(ptr_a.into());
+        let (base_b, offset_b) = self.decompose(ptr_b.into());
+        if base_a == base_b {
+            match (offset_a, offset_b) {
+                (Some(a), Some(b)) if a == b && size_a == size_b => AliasResult::MustAlias,
+                (Some(a), Some(b)) if a + size_a as i64 <= b || b + size_b as i64 <= a => AliasResult::NoAlias,
+                (Some(_), Some(_)) => AliasResult::PartialAlias,
+                _ => AliasResult::MayAlias
+            }
+        } else if is_identified(base_a) && is_identified(base_b) {
+            AliasResult::NoAlias
+        } else {
+            AliasResult::MayAlias
+        }
+    }
+    /// Returns true if an instruction after `from` and before `to` may write to the `size`
+    /// bytes at `ptr`, so a load of them at `to` can't reuse the value they had at `from`.
+    ///
+    /// Stores are checked with `alias`, calls may write to any memory unless the function
+    /// called is `readnone` or `readonly`, and atomic instructions and fences always clobber.
+    /// Only the block of `from` is searched, so this is also true if `to` isn't after `from`
+    /// in the same block.
+    pub fn is_clobbered_between(&self, ptr: &Value, size: u64, from: &Instruction, to: &Instruction) -> bool {
+        let mut next = from.get_next();
+        while let Some(inst) = next {
+            if inst == to {
+                return false;
+            }
+            if self.may_write(inst, ptr, size) {
+                return true;
+            }
+            next = inst.get_next();
+        }
+        true
+    }
+    /// Returns true if `inst` may write to the `size` bytes at `ptr`.
+    fn may_write(&self, inst: &Instruction, ptr: &Value, size: u64) -> bool {
+        let inst: LLVMValueRef = inst.into();
+        unsafe {
+            match core::LLVMGetInstructionOpcode(inst) {
+                LLVMOpcode::LLVMStore => {
+                    let stored: &Type = core::LLVMTypeOf(core::LLVMGetOperand(inst, 0)).into();
+                    let dest: &Value = core::LLVMGetOperand(inst, 1).into();
+                    self.alias(dest, self.target.size_of(stored), ptr, size) != AliasResult::NoAlias
+                },
+                LLVMOpcode::LLVMCall | LLVMOpcode::LLVMInvoke => {
+                    let call: &Value = inst.into();
+                    match CallSite::from_super(call).unwrap().get_called_function() {
+                        Some(func) => !func.has_attribute(Attribute::ReadNone) && !func.has_attribute(Attribute::ReadOnly),
+                        None => true
+                    }
+                },
+                LLVMOpcode::LLVMAtomicRMW | LLVMOpcode::LLVMAtomicCmpXchg | LLVMOpcode::LLVMFence | LLVMOpcode::LLVMVAArg => true,
+                _ => false
+            }
+        }
+    }
+    /// Returns the object `ptr` points into and the offset into it in bytes, or `None` for the
+    /// offset if it isn't constant.
+    fn decompose(&self, mut ptr: LLVMValueRef) -> (LLVMValueRef, Option<i64>) {
+        let mut offset = Some(0);
+        loop {
+            let opcode = unsafe {
+                if !core::LLVMIsAInstruction(ptr).is_null() {
+                    core::LLVMGetInstructionOpcode(ptr)
+                } else if !core::LLVMIsAConstantExpr(ptr).is_null() {
+                    core::LLVMGetConstOpcode(ptr)
+                } else {
+                    return (ptr, offset)
+                }
+            };
+            match opcode {
+                LLVMOpcode::LLVMBitCast => (),
+                LLVMOpcode::LLVMGetElementPtr => offset = match (offset, self.gep_offset(ptr)) {
+                    (Some(offset), Some(gep_offset)) => Some(offset + gep_offset),
+                    _ => None
+                },
+                _ => return (ptr, offset)
+            }
+            ptr = unsafe { core::LLVMGetOperand(ptr, 0) };
+        }
+    }
+    /// Returns the offset in bytes that the GEP `gep` adds to its pointer, or `None` if any
+    /// of its indices aren't constant.
+    fn gep_offset(&self, gep: LLVMValueRef) -> Option<i64> {
+        unsafe {
+            let mut ty: &Type = core::LLVMTypeOf(core::LLVMGetOperand(gep, 0)).into();
+            let mut offset = 0;
+            for operand in 1..core::LLVMGetNumOperands(gep) as c_uint {
+                let index = core::LLVMGetOperand(gep, operand);
+                if core::LLVMIsAConstantInt(index).is_null() {
+                    return None;
+                }
+                let index = core::LLVMConstIntGetSExtValue(index) as i64;
+                if let Some(struct_ty) = StructType::from_super(ty) {
+                    offset += self.target.offset_of(struct_ty, index as usize) as i64;
+                    ty = struct_ty.get_elements()[index as usize];
+                } else {
+                    ty = core::LLVMGetElementType(ty.into()).into();
+                    offset += index * target::LLVMABISizeOfType((&*self.target).into(), ty.into()) as i64;
+                }
+            }
+            Some(offset)
+        }
+    }
+}
+
+/// Returns true if `base` is an object that no other object can alias, like an alloca.
+fn is_identified(base: LLVMValueRef) -> bool {
+    let identified = unsafe {
+        !core::LLVMIsAAllocaInst(base).is_null() || !core::LLVMIsAGlobalVariable(base).is_null() || !core::LLVMIsAFunction(base).is_null()
+    };
+    let base: &Value = base.into();
+    identified || Arg::from_super(base).map(|arg| arg.has_attribute(Attribute::NoAlias)).unwrap_or(false)
+}
@@ -0,0 +1,243 @@
+//! A driver that compiles whole programs ahead of time, generating code on several threads.
+
+use libc::size_t;
+use ffi::{bit_reader, core};
+use cbox::{CBox, CSemiBox};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{error, fmt, mem, thread};
+use builder::NULL_NAME;
+use context::{Context, GetContext};
+use module::Module;
+use target::{RelocMode, TargetError, TargetFeatures, TargetMachine};
+
+/// What `Compiler::compile` produces from the modules it is given.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Artifact {
+    /// A single relocatable object file, linked with `ld.lld -r`.
+    Object,
+    /// A static library of the object file of each partition, made with `llvm-ar`.
+    Archive,
+    /// An executable, linked with `ld.lld` and the link arguments given.
+    Executable
+}
+
+/// Compiles a program made of several modules into an object file, static library or
+/// executable, optimizing it as a whole and generating its code on several threads.
+///
+/// The modules are linked into one and optimized, then split with `Module::split` into a
+/// partition for each thread. LLVM contexts can't be shared between threads, so each partition
+/// is passed to its thread as bitcode and compiled in a context of its own. The object files
+/// are written next to the output and removed once they have been linked.
+///
+/// The backend of the target must have been initialized already, and the linker and archiver
+/// must be installed.
+pub struct Compiler<'a> {
+    modules: Vec<&'a Module>,
+    triple: String,
+    cpu: String,
+    features: String,
+    opt_level: usize,
+    reloc: RelocMode,
+    threads: usize,
+    linker: String,
+    archiver: String,
+    link_args: Vec<String>
+}
+impl<'a> Compiler<'a> {
+    /// Make a compiler for `triple` with no modules, which optimizes at level 2 and generates
+    /// code on four threads.
+    pub fn new(triple: &str) -> Compiler<'a> {
+        Compiler {
+            modules: Vec::new(),
+            triple: triple.to_owned(),
+            cpu: String::new(),
+            features: String::new(),
+            opt_level: 2,
+            reloc: RelocMode::Default,
+            threads: 4,
+            linker: "ld.lld".to_owned(),
+            archiver: "llvm-ar".to_owned(),
+            link_args: Vec::new()
+        }
+    }
+    /// Add a module to the program, which must be in the same context as the other modules.
+    pub fn add_module(mut self, module: &'a Module) -> Compiler<'a> {
+        self.modules.push(module);
+        self
+    }
+    /// Generate code for the CPU given instead of the generic CPU of the target.
+    pub fn cpu(mut self, cpu: &str) -> Compiler<'a> {
+        self.cpu = cpu.to_owned();
+        self
+    }
+    /// Generate code with the target features given.
+    pub fn features(mut self, features: &TargetFeatures) -> Compiler<'a> {
+        self.features = features.to_string();
+        self
+    }
+    /// Optimize the program and its code at the level given, from 0 to 3.
+    pub fn opt_level(mut self, opt_level: usize) -> Compiler<'a> {
+        self.opt_level = opt_level;
+        self
+    }
+    /// Generate code for the relocation model given.
+    pub fn reloc(mut self, reloc: RelocMode) -> Compiler<'a> {
+        self.reloc = reloc;
+        self
+    }
+    /// Generate code on the number of threads given, which is also the number of object files.
+    pub fn threads(mut self, threads: usize) -> Compiler<'a> {
+        assert!(threads > 0, "expected at least one thread");
+        self.threads = threads;
+        self
+    }
+    /// Link with the program given instead of `ld.lld`, which must take the same arguments.
+    pub fn linker(mut self, linker: &str) -> Compiler<'a> {
+        self.linker = linker.to_owned();
+        self
+    }
+    /// Pass an argument to the linker after the object files when linking an executable, such
+    /// as a library to link with or the path of the C runtime's startup file.
+    pub fn link_arg(mut self, arg: &str) -> Compiler<'a> {
+        self.link_args.push(arg.to_owned());
+        self
+    }
+    /// Compile the modules into the artifact given at `output`.
+    pub fn compile(&self, output: &Path, artifact: Artifact) -> Result<(), CompileError> {
+        assert!(!self.modules.is_empty(), "expected at least one module to compile");
+        let objects = try!(self.build_objects(output));
+        let result = self.link(output, artifact, &objects);
+        for object in &objects {
+            let _ = fs::remove_file(object);
+        }
+        result
+    }
+    /// Link, optimize and split the modules, then write an object file for each partition.
+    fn build_objects(&self, output: &Path) -> Result<Vec<PathBuf>, CompileError> {
+        let machine = try!(TargetMachine::new_with_reloc(&self.triple, &self.cpu, &self.features, self.opt_level, self.reloc).map_err(CompileError::Target));
+        let program = Module::new("program", self.modules[0].get_context());
+        program.set_target(&self.triple);
+        program.set_data_layout(&machine.get_data_layout().as_str());
+        // linking moves the function bodies out of the source module, so link copies
+        for &module in &self.modules {
+            try!(program.link_destroy(module.clone()).map_err(|err| CompileError::Link(err.to_string())));
+        }
+        program.optimize(self.opt_level, 0);
+        let workers: Vec<_> = program.split(self.threads).into_iter().enumerate().map(|(index, part)| {
+            let bitcode = part.to_bitcode().as_bytes().to_vec();
+            let path = output.with_extension(format!("{}.o", index));
+            let (triple, cpu, features) = (self.triple.clone(), self.cpu.clone(), self.features.clone());
+            let (opt_level, reloc) = (self.opt_level, self.reloc);
+            thread::spawn(move || -> Result<PathBuf, CompileError> {
+                let context = Context::new();
+                let module = try!(parse_bitcode(&context, &bitcode).map_err(CompileError::Codegen));
+                let machine = try!(TargetMachine::new_with_reloc(&triple, &cpu, &features, opt_level, reloc).map_err(CompileError::Target));
+                let object = try!(machine.emit_object(&module).map_err(|err| CompileError::Codegen(err.to_string())));
+                let mut file = try!(File::create(&path).map_err(CompileError::Io));
+                try!(file.write_all(object.as_bytes()).map_err(CompileError::Io));
+                Ok(path)
+            })
+        }).collect();
+        let mut objects = Vec::new();
+        let mut error = None;
+        for worker in workers {
+            match worker.join() {
+                Ok(Ok(object)) => objects.push(object),
+                Ok(Err(err)) => error = error.or(Some(err)),
+                Err(_) => error = error.or(Some(CompileError::Codegen("a code generation thread panicked".to_owned())))
+            }
+        }
+        match error {
+            Some(err) => {
+                for object in &objects {
+                    let _ = fs::remove_file(object);
+                }
+                Err(err)
+            },
+            None => Ok(objects)
+        }
+    }
+    /// Link or archive the object files into the artifact at `output`.
+    fn link(&self, output: &Path, artifact: Artifact, objects: &[PathBuf]) -> Result<(), CompileError> {
+        let mut command = match artifact {
+            Artifact::Object => {
+                let mut command = Command::new(&self.linker);
+                command.arg("-r").arg("-o").arg(output).args(objects);
+                command
+            },
+            Artifact::Archive => {
+                let _ = fs::remove_file(output);
+                let mut command = Command::new(&self.archiver);
+                command.arg("rcs").arg(output).args(objects);
+                command
+            },
+            Artifact::Executable => {
+                let mut command = Command::new(&self.linker);
+                command.arg("-o").arg(output).args(objects).args(&self.link_args);
+                command
+            }
+        };
+        let result = try!(command.output().map_err(CompileError::Io));
+        if result.status.success() {
+            Ok(())
+        } else {
+            Err(CompileError::Tool(format!("{:?}", command), String::from_utf8_lossy(&result.stderr).into_owned()))
+        }
+    }
+}
+
+/// Parse the bitcode `bytes` into a module in `context`.
+fn parse_bitcode<'a>(context: &'a Context, bytes: &[u8]) -> Result<CSemiBox<'a, Module>, String> {
+    unsafe {
+        let buf = core::LLVMCreateMemoryBufferWithMemoryRangeCopy(bytes.as_ptr() as *const _, bytes.len() as size_t, NULL_NAME.as_ptr());
+        let mut out = mem::uninitialized();
+        let mut err = mem::uninitialized();
+        let failed = bit_reader::LLVMParseBitcodeInContext(context.into(), buf, &mut out, &mut err) == 1;
+        core::LLVMDisposeMemoryBuffer(buf);
+        if failed {
+            Err(CBox::<str>::new(err).to_string())
+        } else {
+            Ok(CSemiBox::new(out))
+        }
+    }
+}
+
+/// An error returned when a program can't be compiled.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The modules couldn't be linked together, with the linker's message.
+    Link(String),
+    /// The target machine couldn't be created.
+    Target(TargetError),
+    /// A partition couldn't be compiled, with LLVM's message.
+    Codegen(String),
+    /// An object file couldn't be written, or the linker or archiver couldn't be run.
+    Io(io::Error),
+    /// The linker or archiver failed, with the command that was run and what it printed.
+    Tool(String, String)
+}
+impl fmt::Display for CompileError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Link(ref message) => write!(fmt, "failed to link modules: {}", message),
+            CompileError::Target(ref err) => write!(fmt, "{}", err),
+            CompileError::Codegen(ref message) => write!(fmt, "failed to generate code: {}", message),
+            CompileError::Io(ref err) => write!(fmt, "{}", err),
+            CompileError::Tool(ref command, ref message) => write!(fmt, "{} failed: {}", command, message)
+        }
+    }
+}
+impl error::Error for CompileError {
+    fn description(&self) -> &str {
+        match *self {
+            CompileError::Link(_) => "failed to link modules",
+            CompileError::Target(_) => "failed to create target machine",
+            CompileError::Codegen(_) => "failed to generate code",
+            CompileError::Io(_) => "I/O error",
+            CompileError::Tool(_, _) => "linker failed"
+        }
+    }
+}
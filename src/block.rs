@@ -1,10 +1,13 @@
-use ffi::core;
-use ffi::prelude::LLVMBasicBlockRef;
+use ffi::{core, LLVMIntPredicate, LLVMOpcode};
+use ffi::prelude::{LLVMBasicBlockRef, LLVMValueRef};
 use std::iter::{Iterator, DoubleEndedIterator, IntoIterator};
 use std::marker::PhantomData;
 use std::mem;
 use std::ops::Deref;
-use value::{Function, Value};
+use builder::{Builder, NULL_NAME};
+use context::GetContext;
+use types::Type;
+use value::{Function, IntPredicate, PhiNode, Value};
 use util::{self, Sub};
 
 /// A container of instructions that execute sequentially.
@@ -31,6 +34,14 @@ impl BasicBlock {
     pub fn get_parent(&self) -> Option<&Function> {
         unsafe { util::ptr_to_null(core::LLVMGetBasicBlockParent(self.into())) }
     }
+    /// Returns the constant `blockaddress` of this block, which is an `i8*` that can only be
+    /// used as the destination of an `indirectbr` in the same function.
+    ///
+    /// This block must be in a function, and must not be its entry block.
+    pub fn get_address(&self) -> &Value {
+        let function = self.get_parent().expect("block is not in a function");
+        unsafe { core::LLVMBlockAddress(function.into(), self.into()).into() }
+    }
     /// Return the terminator instruction for this basic block.
     pub fn get_terminator(&self) -> Option<&Value> {
         unsafe { util::ptr_to_null(core::LLVMGetBasicBlockTerminator(self.into())) }
@@ -43,6 +54,30 @@ impl BasicBlock {
     pub fn get_last(&self) -> Option<&Value> {
         unsafe { util::ptr_to_null(core::LLVMGetLastInstruction(self.into())) }
     }
+    /// Returns the blocks the terminator of this block can jump to, in the order LLVM numbers
+    /// them, or nothing if this block doesn't have a terminator yet.
+    ///
+    /// For a conditional branch this is the block for true before the block for false.
+    pub fn get_successors(&self) -> Vec<&BasicBlock> {
+        let mut successors = Vec::new();
+        unsafe {
+            let terminator = core::LLVMGetBasicBlockTerminator(self.into());
+            if terminator.is_null() {
+                return successors;
+            }
+            for index in 0..core::LLVMGetNumOperands(terminator) {
+                let operand = core::LLVMGetOperand(terminator, index as u32);
+                if core::LLVMValueIsBasicBlock(operand) != 0 {
+                    successors.push(core::LLVMValueAsBasicBlock(operand).into());
+                }
+            }
+            // branches keep their destinations in reverse
+            if !core::LLVMIsABranchInst(terminator).is_null() {
+                successors.reverse();
+            }
+        }
+        successors
+    }
     /// Move this basic block after the `other` basic block in its function.
     pub fn move_after(&self, other: &BasicBlock) {
         unsafe { core::LLVMMoveBasicBlockAfter(self.into(), other.into()) }
@@ -62,6 +97,103 @@ impl BasicBlock {
     pub unsafe fn delete(&self) {
         core::LLVMDeleteBasicBlock(self.into())
     }
+    /// If this block is the header of a simple counted loop, returns how many times the loop
+    /// goes around, which is how many times its exit test passes.
+    ///
+    /// The loop must count with a phi in this block that starts at a constant and changes by
+    /// a constant step each time around, and this block must end with a conditional branch on
+    /// a comparison of the phi or its next value with a constant. This returns `None` for any
+    /// other loop, and for loops whose counter would overflow before they exit.
+    pub fn get_trip_count(&self) -> Option<u64> {
+        unsafe { trip_count(self.into()) }
+    }
+}
+
+/// A basic block that takes parameters, like the blocks of MLIR or Cranelift, which are
+/// implemented as phi nodes at the start of the block.
+///
+/// Instead of adding incoming values to phis by hand, every branch to the block passes its
+/// arguments, and the block they came from is worked out from the builder.
+///
+/// ```rust
+/// use llvm::*;
+/// let ctx = Context::new();
+/// let module = Module::new("count", &ctx);
+/// let func = module.add_function("count", Type::get::<fn(u32) -> u32>(&ctx));
+/// let builder = Builder::new(&ctx);
+/// builder.position_at_end(func.append("entry"));
+/// let head = ParamBlock::new(func, "head", &[Type::get::<u32>(&ctx)]);
+/// let exit = ParamBlock::new(func, "exit", &[Type::get::<u32>(&ctx)]);
+/// head.build_jump(&builder, &[0u32.compile(&ctx)]);
+/// builder.position_at_end(head.get_block());
+/// let i = head.get_param(0);
+/// let next = builder.build_add(i, 1u32.compile(&ctx));
+/// let done = builder.build_icmp(next, &func[0], IntPredicate::Equal);
+/// ParamBlock::build_cond_jump(&builder, done, (&exit, &[next]), (&head, &[next]));
+/// builder.position_at_end(exit.get_block());
+/// builder.build_ret(exit.get_param(0));
+/// module.verify().unwrap();
+/// ```
+pub struct ParamBlock<'a> {
+    block: &'a BasicBlock,
+    params: Vec<&'a Value>
+}
+impl<'a> ParamBlock<'a> {
+    /// Append a block called `name` to `function` with a parameter for each type in `params`.
+    pub fn new(function: &'a Function, name: &str, params: &[&'a Type]) -> ParamBlock<'a> {
+        let block = function.append(name);
+        let builder = Builder::new(function.get_context());
+        builder.position_at_end(block);
+        let params = params.iter().map(|&ty| unsafe {
+            core::LLVMBuildPhi((&*builder).into(), ty.into(), NULL_NAME.as_ptr()).into()
+        }).collect();
+        ParamBlock {
+            block: block,
+            params: params
+        }
+    }
+    /// Returns the block these parameters belong to.
+    pub fn get_block(&self) -> &'a BasicBlock {
+        self.block
+    }
+    /// Returns the parameter at `index`, which is the value passed by whichever branch ran.
+    pub fn get_param(&self, index: usize) -> &'a Value {
+        self.params[index]
+    }
+    /// Returns the parameters of this block.
+    pub fn get_params(&self) -> &[&'a Value] {
+        &self.params
+    }
+    /// Build a branch to this block from the block `builder` is positioned in, passing `args`.
+    pub fn build_jump(&self, builder: &'a Builder, args: &[&'a Value]) -> &'a Value {
+        self.add_incoming(builder, args);
+        builder.build_br(self.block)
+    }
+    /// Build a branch to `then` if `cond` is true and `otherwise` if it isn't, passing each
+    /// block the arguments paired with it.
+    pub fn build_cond_jump(builder: &'a Builder, cond: &'a Value, then: (&ParamBlock<'a>, &[&'a Value]), otherwise: (&ParamBlock<'a>, &[&'a Value])) -> &'a Value {
+        then.0.add_incoming(builder, then.1);
+        otherwise.0.add_incoming(builder, otherwise.1);
+        builder.build_cond_br(cond, then.0.block, Some(otherwise.0.block))
+    }
+    /// Add `args` to the phis of this block as the values that come from the block `builder`
+    /// is positioned in.
+    fn add_incoming(&self, builder: &'a Builder, args: &[&'a Value]) {
+        if args.len() != self.params.len() {
+            panic!("expected {} arguments to {}, got {}", self.params.len(), self.get_name(), args.len())
+        }
+        let from: &BasicBlock = unsafe { core::LLVMGetInsertBlock(builder.into()) }.into();
+        for (&param, &arg) in self.params.iter().zip(args) {
+            if param.get_type() != arg.get_type() {
+                panic!("expected {:?} for argument of {}, got {:?}", param.get_type(), self.get_name(), arg.get_type())
+            }
+            let phi: &PhiNode = LLVMValueRef::from(param).into();
+            phi.add_incoming(arg, from);
+        }
+    }
+    fn get_name(&self) -> &str {
+        self.block.get_name().unwrap_or("<unnamed>")
+    }
 }
 
 /// Iterates through all the blocks contained in a function.
@@ -111,4 +243,174 @@ impl<'a> DoubleEndedIterator for BlockIter<'a> {
             }
         }
     }
-}
\ No newline at end of file
+}
+/// Returns the number of times the loop with the header `header` goes around, if it is a simple
+/// counted loop.
+unsafe fn trip_count(header: LLVMBasicBlockRef) -> Option<u64> {
+    let branch = core::LLVMGetBasicBlockTerminator(header);
+    if branch.is_null() || core::LLVMIsABranchInst(branch).is_null() || core::LLVMGetNumOperands(branch) != 3 {
+        return None;
+    }
+    let cond = core::LLVMGetOperand(branch, 0);
+    if core::LLVMIsAICmpInst(cond).is_null() {
+        return None;
+    }
+    let tested = core::LLVMGetOperand(cond, 0);
+    let bound = match const_int(core::LLVMGetOperand(cond, 1)) {
+        Some(bound) => bound,
+        None => return None
+    };
+    // the counter is either tested itself, or tested after being stepped
+    let phi = if is_phi_in(tested, header) {
+        tested
+    } else if !core::LLVMIsABinaryOperator(tested).is_null() && is_phi_in(core::LLVMGetOperand(tested, 0), header) {
+        core::LLVMGetOperand(tested, 0)
+    } else {
+        return None;
+    };
+    if core::LLVMCountIncoming(phi) != 2 {
+        return None;
+    }
+    let mut start = None;
+    let mut next = None;
+    for index in 0..2 {
+        let value = core::LLVMGetIncomingValue(phi, index);
+        match (const_int(value), step_of(value, phi)) {
+            (Some(value), _) => start = Some(value),
+            (None, Some(step)) => next = Some((value, step, core::LLVMGetIncomingBlock(phi, index))),
+            (None, None) => return None
+        }
+    }
+    let (start, (next, step, latch)) = match (start, next) {
+        (Some(start), Some(next)) => (start, next),
+        _ => return None
+    };
+    if tested != phi && tested != next {
+        return None;
+    }
+    let width = core::LLVMGetIntTypeWidth(core::LLVMTypeOf(phi));
+    if width > 64 {
+        return None;
+    }
+    let mask = if width == 64 { !0 } else { (1 << width) - 1 };
+    let first = if tested == next { start.wrapping_add(step) & mask } else { start };
+    let stays_if_true = reaches(core::LLVMValueAsBasicBlock(core::LLVMGetOperand(branch, 2)), latch, header);
+    let stays_if_false = reaches(core::LLVMValueAsBasicBlock(core::LLVMGetOperand(branch, 1)), latch, header);
+    let predicate = match (stays_if_true, stays_if_false) {
+        (true, false) => core::LLVMGetICmpPredicate(cond),
+        (false, true) => IntPredicate::from(core::LLVMGetICmpPredicate(cond)).inverse().into(),
+        _ => return None
+    };
+    // flipping the sign bit of signed values lets them be compared as unsigned values
+    let flip = match predicate {
+        LLVMIntPredicate::LLVMIntSGT | LLVMIntPredicate::LLVMIntSGE | LLVMIntPredicate::LLVMIntSLT | LLVMIntPredicate::LLVMIntSLE => 1 << (width - 1),
+        _ => 0
+    };
+    let (first, bound) = ((first ^ flip) & mask, (bound ^ flip) & mask);
+    let stays = |value: u64| match predicate {
+        LLVMIntPredicate::LLVMIntEQ => value == bound,
+        LLVMIntPredicate::LLVMIntNE => value != bound,
+        LLVMIntPredicate::LLVMIntUGT | LLVMIntPredicate::LLVMIntSGT => value > bound,
+        LLVMIntPredicate::LLVMIntUGE | LLVMIntPredicate::LLVMIntSGE => value >= bound,
+        LLVMIntPredicate::LLVMIntULT | LLVMIntPredicate::LLVMIntSLT => value < bound,
+        LLVMIntPredicate::LLVMIntULE | LLVMIntPredicate::LLVMIntSLE => value <= bound
+    };
+    if !stays(first) {
+        return Some(0);
+    }
+    let step = step & mask;
+    if step == 0 {
+        return None;
+    }
+    // the counter counts down if its step is negative as a signed value
+    let up = step >> (width - 1) & 1 == 0;
+    let size = if up { step } else { step.wrapping_neg() & mask };
+    match predicate {
+        LLVMIntPredicate::LLVMIntEQ => Some(1),
+        LLVMIntPredicate::LLVMIntNE => {
+            // the counter may wrap around, but it has to land on the bound exactly
+            let distance = (if up { bound.wrapping_sub(first) } else { first.wrapping_sub(bound) }) & mask;
+            if distance % size == 0 {
+                Some(distance / size)
+            } else {
+                None
+            }
+        },
+        LLVMIntPredicate::LLVMIntULT | LLVMIntPredicate::LLVMIntSLT | LLVMIntPredicate::LLVMIntULE | LLVMIntPredicate::LLVMIntSLE if up => {
+            let last = if stays(bound) { bound } else { bound - 1 };
+            let count = (last - first) / size;
+            if size > mask - (first + count * size) {
+                None
+            } else {
+                Some(count + 1)
+            }
+        },
+        LLVMIntPredicate::LLVMIntUGT | LLVMIntPredicate::LLVMIntSGT | LLVMIntPredicate::LLVMIntUGE | LLVMIntPredicate::LLVMIntSGE if !up => {
+            let last = if stays(bound) { bound } else { bound + 1 };
+            let count = (first - last) / size;
+            if size > first - count * size {
+                None
+            } else {
+                Some(count + 1)
+            }
+        },
+        // the counter moves away from the bound, so it only exits by overflowing
+        _ => None
+    }
+}
+
+/// Returns the value of `value` if it is a constant integer of up to 64 bits.
+unsafe fn const_int(value: LLVMValueRef) -> Option<u64> {
+    if core::LLVMIsAConstantInt(value).is_null() || core::LLVMGetIntTypeWidth(core::LLVMTypeOf(value)) > 64 {
+        None
+    } else {
+        Some(core::LLVMConstIntGetZExtValue(value))
+    }
+}
+
+/// Returns true if `value` is a phi in `block`.
+unsafe fn is_phi_in(value: LLVMValueRef, block: LLVMBasicBlockRef) -> bool {
+    !core::LLVMIsAPHINode(value).is_null() && core::LLVMGetInstructionParent(value) == block
+}
+
+/// Returns the constant amount that `value` adds to `phi`, if it is `phi` plus or minus a constant.
+unsafe fn step_of(value: LLVMValueRef, phi: LLVMValueRef) -> Option<u64> {
+    if core::LLVMIsABinaryOperator(value).is_null() || core::LLVMGetOperand(value, 0) != phi {
+        return None;
+    }
+    let step = match const_int(core::LLVMGetOperand(value, 1)) {
+        Some(step) => step,
+        None => return None
+    };
+    match core::LLVMGetInstructionOpcode(value) {
+        LLVMOpcode::LLVMAdd => Some(step),
+        LLVMOpcode::LLVMSub => Some(step.wrapping_neg()),
+        _ => None
+    }
+}
+
+/// Returns true if `latch` can be reached from `from` without going through `header`.
+unsafe fn reaches(from: LLVMBasicBlockRef, latch: LLVMBasicBlockRef, header: LLVMBasicBlockRef) -> bool {
+    let mut stack = vec![from];
+    let mut visited = Vec::new();
+    while let Some(block) = stack.pop() {
+        if block == latch {
+            return true;
+        }
+        if block == header || visited.contains(&block) {
+            continue;
+        }
+        visited.push(block);
+        let terminator = core::LLVMGetBasicBlockTerminator(block);
+        if terminator.is_null() {
+            continue;
+        }
+        for index in 0..core::LLVMGetNumOperands(terminator) {
+            let operand = core::LLVMGetOperand(terminator, index as u32);
+            if core::LLVMValueIsBasicBlock(operand) != 0 {
+                stack.push(core::LLVMValueAsBasicBlock(operand));
+            }
+        }
+    }
+    false
+}
@@ -4,7 +4,7 @@ use ffi::prelude::LLVMMemoryBufferRef;
 use cbox::{CBox, DisposeRef};
 use std::ops::Deref;
 use std::marker::PhantomData;
-use std::mem;
+use std::{mem, slice};
 use util;
 
 
@@ -23,6 +23,15 @@ impl MemoryBuffer {
         })
     }
 }
+impl MemoryBuffer {
+    /// Returns the contents of this buffer as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let ptr = core::LLVMGetBufferStart(self.into()) as *const u8;
+            slice::from_raw_parts(ptr, core::LLVMGetBufferSize(self.into()) as usize)
+        }
+    }
+}
 impl Deref for MemoryBuffer {
     type Target = str;
     fn deref(&self) -> &str {
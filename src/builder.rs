@@ -1,46 +1,73 @@
-use libc::{c_char, c_uint};
+use libc::{c_char, c_int, c_uint, c_ulonglong};
 use ffi::prelude::{LLVMBuilderRef, LLVMValueRef};
-use ffi::{core, LLVMBuilder, LLVMRealPredicate, LLVMIntPredicate};
+use ffi::{core, LLVMAtomicOrdering, LLVMAtomicRMWBinOp, LLVMBuilder};
 use cbox::CSemiBox;
 use std::marker::PhantomData;
-use std::mem;
+use std::cmp::Ordering;
+use std::{error, fmt, mem, ptr};
+use std::ops::Deref;
 use block::BasicBlock;
-use context::Context;
-use types::Type;
-use value::{Function, Value, Predicate};
+use compile::Compile;
+use context::{Context, GetContext, MetadataKind};
+use types::{FunctionType, IntegerType, PointerType, StructType, Type, TypeKind, VectorType};
+use util::{self, Sub};
+use value::{Function, IndirectBr, Instruction, IntPredicate, LandingPad, Linkage, PhiNode, RealPredicate, Switch, UnnamedAddr, Value};
 
-static NULL_NAME:[c_char; 1] = [0];
+pub static NULL_NAME:[c_char; 1] = [0];
 
 /// This provides a uniform API for creating instructions and inserting them into a basic block.
+///
+/// Only the instructions LLVM 3.8 has can be built. In particular there is no `freeze`, which
+/// LLVM 10 added to stop `undef` and `poison` from spreading, and 3.8 has no instruction that
+/// can stand in for it.
 pub struct Builder(PhantomData<[u8]>);
 native_ref!(&Builder = LLVMBuilderRef);
 dispose!{Builder, LLVMBuilder, core::LLVMDisposeBuilder}
 macro_rules! bin_op(
     ($name:ident, $func:ident) => (
         pub fn $name(&self, left: &Value, right: &Value) -> &Value {
-            unsafe { core::$func(self.into(), left.into(), right.into(), NULL_NAME.as_ptr()) }.into()
+            self.check_inserted(unsafe { core::$func(self.into(), left.into(), right.into(), NULL_NAME.as_ptr()) })
         }
     );
     ($name:ident, $ifunc:ident, $ffunc:ident) => (
         pub fn $name(&self, left: &Value, right: &Value) -> &Value {
             let ty = left.get_type();
-            unsafe {
+            self.check_inserted(unsafe {
                 (if ty.is_integer() {
                     core::$ifunc
                 } else {
                     core::$ffunc
-                })(self.into(), left.into(), right.into(), NULL_NAME.as_ptr()).into()
-            }
+                })(self.into(), left.into(), right.into(), NULL_NAME.as_ptr())
+            })
         }
     );
 );
 macro_rules! un_op(
     ($name:ident, $func:ident) => (
         pub fn $name(&self, value: &Value) -> &Value {
-            unsafe { core::$func(self.into(), value.into(), NULL_NAME.as_ptr() as *const c_char) }.into()
+            self.check_inserted(unsafe { core::$func(self.into(), value.into(), NULL_NAME.as_ptr() as *const c_char) })
         }
     );
 );
+macro_rules! overflow_op(
+    ($name:ident, $op:expr) => (
+        /// Build a call to the `with.overflow` intrinsic for the operation, and return the
+        /// result, which wraps on overflow, and an `i1` that is true if it overflowed.
+        pub fn $name(&self, left: &Value, right: &Value) -> (&Value, &Value) {
+            self.build_with_overflow($op, left, right)
+        }
+    );
+);
+/// What generated code should do when a runtime check fails.
+#[derive(Copy, Clone)]
+pub enum CheckFailure<'a> {
+    /// Abort the program with the `llvm.trap` intrinsic.
+    Trap,
+    /// Call the function given with the operands that failed the check, which must not return.
+    Call(&'a Function),
+    /// Jump to the block given.
+    Jump(&'a BasicBlock)
+}
 impl Builder {
     /// Create a new builder in the context given.
     pub fn new(context: &Context) -> CSemiBox<Builder> {
@@ -50,47 +77,172 @@ impl Builder {
     pub fn position_at_end(&self, block: &BasicBlock) {
         unsafe { core::LLVMPositionBuilderAtEnd(self.into(), block.into()) }
     }
+    /// Position the builder just before `inst`, so instructions are built in front of it.
+    pub fn position_before(&self, inst: &Instruction) {
+        unsafe { core::LLVMPositionBuilderBefore(self.into(), inst.into()) }
+    }
+    /// Position the builder at the start of `block`, after its phis and landing pad, so
+    /// instructions are built in front of the rest of the block.
+    ///
+    /// This is where code hoisted into a block or spilled on entry to it belongs.
+    pub fn position_at_start(&self, block: &BasicBlock) {
+        unsafe {
+            let mut first = core::LLVMGetFirstInstruction(block.into());
+            while !first.is_null() && (!core::LLVMIsAPHINode(first).is_null() || !core::LLVMIsALandingPadInst(first).is_null()) {
+                first = core::LLVMGetNextInstruction(first);
+            }
+            if first.is_null() {
+                core::LLVMPositionBuilderAtEnd(self.into(), block.into())
+            } else {
+                core::LLVMPositionBuilderBefore(self.into(), first)
+            }
+        }
+    }
+    /// Returns the block this builder is positioned in, or `None` if it hasn't been positioned.
+    pub fn get_insert_block(&self) -> Option<&BasicBlock> {
+        unsafe { util::ptr_to_null(core::LLVMGetInsertBlock(self.into())) }
+    }
+    /// Returns the function containing the block this builder is positioned in, or `None` if
+    /// it hasn't been positioned in a block of a function.
+    pub fn current_function(&self) -> Option<&Function> {
+        self.get_insert_block().and_then(|block| block.get_parent())
+    }
+    /// Set the debug location that every instruction built from now on is given as its `!dbg`
+    /// attachment, which debuggers and profilers map it back to source code with.
+    ///
+    /// The LLVM 3.8 C API can't create debug info, so `loc` must be a location taken from an
+    /// instruction with `Instruction::get_debug_location`, such as one in parsed IR.
+    pub fn set_current_debug_location(&self, loc: &Value) {
+        unsafe { core::LLVMSetCurrentDebugLocation(self.into(), loc.into()) }
+    }
+    /// Returns the debug location given to the instructions this builds, or `None` if they
+    /// aren't given one.
+    pub fn get_current_debug_location(&self) -> Option<&Value> {
+        unsafe {
+            let loc = core::LLVMGetCurrentDebugLocation(self.into());
+            // without a location this is the metadata wrapping nothing, which is unique to the
+            // context, so compare it with a new builder's
+            let builder = core::LLVMCreateBuilderInContext(core::LLVMGetTypeContext(core::LLVMTypeOf(loc)));
+            let none = core::LLVMGetCurrentDebugLocation(builder);
+            core::LLVMDisposeBuilder(builder);
+            if loc == none {
+                None
+            } else {
+                Some(loc.into())
+            }
+        }
+    }
+    /// Stop giving a debug location to the instructions this builds.
+    pub fn clear_debug_location(&self) {
+        unsafe { core::LLVMSetCurrentDebugLocation(self.into(), ptr::null_mut()) }
+    }
+    /// Build an instruction that returns `value` from the current function, or void if it is
+    /// `None`, after checking it matches the return type of the function.
+    ///
+    /// This panics with the name of the function if it doesn't, instead of leaving the mistake
+    /// for the verifier to find.
+    pub fn ret(&self, value: Option<&Value>) -> &Value {
+        let function = self.get_function();
+        let expected = function.get_signature().get_return();
+        match value {
+            Some(value) if value.get_type() == expected => self.build_ret(value),
+            None if expected.is_void() => self.build_ret_void(),
+            _ => panic!("expected {} to return {}, got {}", function.get_name().unwrap_or("<unnamed>"), expected,
+                value.map(|value| value.get_type().to_string()).unwrap_or("void".to_owned()))
+        }
+    }
+    /// Terminate every block of `function` that doesn't end with a terminator as `policy` says.
+    ///
+    /// This should be called once the whole body of `function` has been built, so blocks that
+    /// control can't reach the end of, like those after a call to a function that never returns,
+    /// don't each need their own `unreachable`. If `policy` is `Unterminated::Fail`, this returns
+    /// an error naming the first block without a terminator instead.
+    pub fn finalize(&self, function: &Function, policy: Unterminated) -> Result<(), UnterminatedBlock> {
+        let builder = Builder::new(function.get_context());
+        let mut block = unsafe { core::LLVMGetFirstBasicBlock(function.into()) };
+        while !block.is_null() {
+            unsafe {
+                if core::LLVMGetBasicBlockTerminator(block).is_null() {
+                    match policy {
+                        Unterminated::Unreachable => {
+                            core::LLVMPositionBuilderAtEnd((&*builder).into(), block);
+                            builder.build_unreachable();
+                        },
+                        Unterminated::Fail => {
+                            let block: &BasicBlock = block.into();
+                            return Err(UnterminatedBlock {
+                                function: function.get_name().unwrap_or("<unnamed>").to_owned(),
+                                block: block.get_name().unwrap_or("<unnamed>").to_owned()
+                            })
+                        }
+                    }
+                }
+                block = core::LLVMGetNextBasicBlock(block);
+            }
+        }
+        Ok(())
+    }
     /// Build an instruction that returns from the function with void.
     pub fn build_ret_void(&self) -> &Value {
-        unsafe { core::LLVMBuildRetVoid(self.into()) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildRetVoid(self.into()) })
     }
     /// Build an instruction that returns from the function with `value`.
     pub fn build_ret(&self, value: &Value) -> &Value {
-        unsafe { core::LLVMBuildRet(self.into(), value.into()) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildRet(self.into(), value.into()) })
     }
     /// Build an instruction that allocates an array with the element type `elem` and the size `size`.
     ///
     /// The size of this array will be the size of `elem` times `size`.
     pub fn build_array_alloca(&self, elem: &Type, size: &Value) -> &Value {
-        unsafe { core::LLVMBuildArrayAlloca(self.into(), elem.into(), size.into(), NULL_NAME.as_ptr() as *const c_char) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildArrayAlloca(self.into(), elem.into(), size.into(), NULL_NAME.as_ptr() as *const c_char) })
     }
     /// Build an instruction that allocates a pointer to fit the size of `ty` then returns this pointer.
     pub fn build_alloca(&self, ty: &Type) -> &Value {
-        unsafe { core::LLVMBuildAlloca(self.into(), ty.into(), NULL_NAME.as_ptr() as *const c_char) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildAlloca(self.into(), ty.into(), NULL_NAME.as_ptr() as *const c_char) })
     }
     /// Build an instruction that frees the `val`, which _MUST_ be a pointer that was returned
     /// from `build_alloca`.
     pub fn build_free(&self, val: &Value) -> &Value {
-        unsafe { core::LLVMBuildFree(self.into(), val.into()) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildFree(self.into(), val.into()) })
     }
     /// Build an instruction that store the value `val` in the pointer `ptr`.
     pub fn build_store(&self, val: &Value, ptr: &Value) -> &Value {
-        unsafe { core::LLVMBuildStore(self.into(), val.into(), ptr.into()) }.into()
+        self.check_inserted(unsafe { core::LLVMBuildStore(self.into(), val.into(), ptr.into()) })
+    }
+    /// Build an instruction that marks the current position as unreachable.
+    pub fn build_unreachable(&self) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildUnreachable(self.into()) })
     }
     /// Build an instruction that branches to the block `dest`.
     pub fn build_br(&self, dest: &BasicBlock) -> &Value {
-        unsafe { core::LLVMBuildBr(self.into(), dest.into()).into() }
+        self.check_inserted(unsafe { core::LLVMBuildBr(self.into(), dest.into()) })
     }
     /// Build an instruction that branches to `if_block` if `cond` evaluates to true, and `else_block` otherwise.
     pub fn build_cond_br(&self, cond: &Value, if_block: &BasicBlock, else_block: Option<&BasicBlock>) -> &Value {
-        unsafe { core::LLVMBuildCondBr(self.into(), cond.into(), if_block.into(), mem::transmute(else_block)).into() }
+        self.check_inserted(unsafe { core::LLVMBuildCondBr(self.into(), cond.into(), if_block.into(), mem::transmute(else_block)) })
+    }
+    /// Build a phi node of type `ty` that yields the value paired with whichever block ran
+    /// before the current one.
+    ///
+    /// More incoming values can be added with `PhiNode::add_incoming`.
+    pub fn build_phi(&self, ty: &Type, incoming: &[(&Value, &BasicBlock)]) -> &PhiNode {
+        let phi = unsafe { core::LLVMBuildPhi(self.into(), ty.into(), NULL_NAME.as_ptr()) };
+        self.check_inserted(phi);
+        let phi: &PhiNode = phi.into();
+        phi.add_incomings(incoming);
+        phi
     }
     /// Build an instruction that calls the function `func` with the arguments `args`.
     ///
-    /// This will return the return value of the function.
-    pub fn build_call(&self, func: &Function, args: &[&Value]) -> &Value {
+    /// `func` can also be a function pointer or `InlineAsm`. This will return the return
+    /// value of the function.
+    ///
+    /// Operand bundles, such as `"deopt"` or `"gc-live"`, can't be attached to the call, since
+    /// the C API only gained functions to build and read them in LLVM 18.
+    pub fn build_call(&self, func: &Value, args: &[&Value]) -> &Value {
         unsafe {
             let call = core::LLVMBuildCall(self.into(), func.into(), args.as_ptr() as *mut LLVMValueRef, args.len() as c_uint, NULL_NAME.as_ptr());
+            self.check_inserted(call);
             core::LLVMSetTailCall(call, 0);
             call.into()
         }
@@ -98,57 +250,726 @@ impl Builder {
     /// Build an instruction that calls the function `func` with the arguments `args`.
     ///
     /// This will return the return value of the function.
-    pub fn build_tail_call(&self, func: &Function, args: &[&Value]) -> &Value {
+    pub fn build_tail_call(&self, func: &Value, args: &[&Value]) -> &Value {
         unsafe {
             let call = core::LLVMBuildCall(self.into(), func.into(), args.as_ptr() as *mut LLVMValueRef, args.len() as c_uint, NULL_NAME.as_ptr());
+            self.check_inserted(call);
             core::LLVMSetTailCall(call, 1);
             call.into()
         }
     }
+    /// Build an instruction that calls the function `func` with the arguments `args`, then
+    /// continues at `then` if it returns normally or `catch` if it unwinds.
+    ///
+    /// `catch` must start with a landing pad. This will return the return value of the function,
+    /// which is only available in `then`. Like `build_call`, this can't attach operand bundles.
+    pub fn build_invoke(&self, func: &Value, args: &[&Value], then: &BasicBlock, catch: &BasicBlock) -> &Value {
+        self.check_inserted(unsafe {
+            core::LLVMBuildInvoke(self.into(), func.into(), args.as_ptr() as *mut LLVMValueRef, args.len() as c_uint, then.into(), catch.into(), NULL_NAME.as_ptr())
+        })
+    }
+    /// Build a landing pad that yields the exception as a value of type `ty`, which is
+    /// `{i8*, i32}` for the Itanium ABI, and make `personality` the personality function of the
+    /// current function.
+    ///
+    /// `num_clauses` is how many clauses to reserve room for, which may be too few or too many.
+    pub fn build_landing_pad(&self, ty: &Type, personality: &Function, num_clauses: usize) -> &LandingPad {
+        unsafe {
+            let pad = core::LLVMBuildLandingPad(self.into(), ty.into(), personality.into(), num_clauses as c_uint, NULL_NAME.as_ptr());
+            self.check_inserted(pad);
+            pad.into()
+        }
+    }
+    /// Build an instruction that resumes unwinding with the exception `exn`, which a landing
+    /// pad yielded.
+    pub fn build_resume(&self, exn: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildResume(self.into(), exn.into()) })
+    }
+    /// Build a fence, which orders the memory accesses around it as `ordering` says without
+    /// accessing memory itself.
+    ///
+    /// If `single_thread` is true, this only synchronizes with signal handlers running on the
+    /// same thread, like `atomic_signal_fence` in C11. The ordering must be at least `Acquire`.
+    pub fn build_fence(&self, ordering: AtomicOrdering, single_thread: bool) -> &Value {
+        match ordering {
+            AtomicOrdering::Unordered | AtomicOrdering::Monotonic => panic!("fences can't be {:?}", ordering),
+            _ => ()
+        }
+        self.check_inserted(unsafe { core::LLVMBuildFence(self.into(), ordering.into(), single_thread as c_int, NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that atomically applies `op` to the integer `ptr` points to and
+    /// `value`, stores the result there, and returns the old value.
+    ///
+    /// This always builds an `atomicrmw` instruction, so the target must support atomics of
+    /// that width; use `with_atomics` to lower wider ones.
+    pub fn build_atomic_rmw(&self, op: AtomicOp, ptr: &Value, value: &Value, ordering: AtomicOrdering) -> &Value {
+        if ordering == AtomicOrdering::Unordered {
+            panic!("atomicrmw can't be unordered")
+        }
+        self.check_inserted(unsafe { core::LLVMBuildAtomicRMW(self.into(), op.into(), ptr.into(), value.into(), ordering.into(), 0) })
+    }
+    /// Build instructions that atomically replace the integer `ptr` points to with `new` if it
+    /// equals `cmp`, then return a `{T, i1}` struct of the old value and whether it was replaced.
+    ///
+    /// The `success` ordering applies if the value was replaced and the `failure` ordering if it
+    /// wasn't, which can't be a release ordering or stronger than `success`. Neither can be
+    /// unordered.
+    ///
+    /// The C API of the LLVM this binds can't build `cmpxchg` instructions, so this calls
+    /// `__atomic_compare_exchange_N` from libatomic, which must be available to the engine.
+    /// libatomic has no weak variant, so `weak` is ignored and the exchange never fails
+    /// spuriously, which a weak exchange also allows.
+    pub fn build_cmpxchg(&self, ptr: &Value, cmp: &Value, new: &Value, success: AtomicOrdering, failure: AtomicOrdering, weak: bool) -> &Value {
+        let _ = weak;
+        match failure {
+            AtomicOrdering::Unordered | AtomicOrdering::Release | AtomicOrdering::AcquireRelease => panic!("{:?} is not a valid failure ordering", failure),
+            _ => ()
+        }
+        if success == AtomicOrdering::Unordered {
+            panic!("cmpxchg can't be unordered")
+        }
+        if failure.strength() > success.strength() {
+            panic!("failure ordering {:?} is stronger than success ordering {:?}", failure, success)
+        }
+        let ty = cmp.get_type();
+        match PointerType::from_super(ptr.get_type()) {
+            Some(ptr_ty) if ptr_ty.get_element() == ty && new.get_type() == ty => (),
+            _ => panic!("expected pointer to {:?} and {:?} to exchange, got {:?} and {:?}", ty, ty, ptr.get_type(), new.get_type())
+        }
+        let ctx = ty.get_context();
+        let expected = self.build_entry_alloca(ty);
+        self.build_store(cmp, expected);
+        let (bool_ty, order_ty) = (Type::get::<bool>(ctx), Type::get::<u32>(ctx));
+        let func = self.get_intrinsic(&libcall_name("compare_exchange", ty), bool_ty, &[ptr.get_type(), expected.get_type(), ty, order_ty, order_ty]);
+        let exchanged = self.build_call(func, &[ptr, expected, new, success.to_c_abi().compile(ctx), failure.to_c_abi().compile(ctx)]);
+        let result = Value::new_undef(StructType::new(ctx, &[ty, bool_ty], false));
+        let result = self.build_insert_value(result, self.build_load(expected), &[0]);
+        self.build_insert_value(result, exchanged, &[1])
+    }
     /// Build an instruction that yields to `true_val` if `cond` is equal to `1`, and `false_val` otherwise.
+    ///
+    /// If `cond` is a vector of `i1`, the values must be vectors of the same length, and each
+    /// element is picked from `true_val` or `false_val` by the element of `cond` at its index.
     pub fn build_select(&self, cond: &Value, true_val: &Value, false_val: &Value) -> &Value {
-        unsafe { core::LLVMBuildSelect(self.into(), cond.into(), true_val.into(), false_val.into(), NULL_NAME.as_ptr()).into() }
+        let ty = true_val.get_type();
+        if false_val.get_type() != ty {
+            panic!("expected {:?} to select between, got {:?}", ty, false_val.get_type())
+        }
+        let bool_ty = Type::get::<bool>(ty.get_context());
+        let valid = match (VectorType::from_super(cond.get_type()), VectorType::from_super(ty)) {
+            (Some(cond_ty), Some(ty)) => cond_ty.get_element() == bool_ty && cond_ty.get_size() == ty.get_size(),
+            (Some(_), None) => false,
+            (None, _) => cond.get_type() == bool_ty
+        };
+        if !valid {
+            panic!("expected i1 condition to select between {:?}, got {:?}", ty, cond.get_type())
+        }
+        self.check_inserted(unsafe { core::LLVMBuildSelect(self.into(), cond.into(), true_val.into(), false_val.into(), NULL_NAME.as_ptr()) })
     }
     /// Build an instruction that casts a value into a certain type.
     pub fn build_bit_cast(&self, value: &Value, dest: &Type) -> &Value {
-        unsafe { core::LLVMBuildBitCast(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()).into() }
+        self.check_inserted(unsafe { core::LLVMBuildBitCast(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
     }
     /// Build an instruction to bitcast in integer into a pointer.
     pub fn build_int_to_ptr(&self, value: &Value, dest: &Type) -> &Value {
-        unsafe { core::LLVMBuildIntToPtr(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()).into() }
+        self.check_inserted(unsafe { core::LLVMBuildIntToPtr(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction to cast a pointer into an integer.
+    pub fn build_ptr_to_int(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildPtrToInt(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
     }
     /// Build an instruction that zero extends its operand to the type `dest`.
     pub fn build_zext(&self, value: &Value, dest: &Type) -> &Value {
-        unsafe { core::LLVMBuildZExtOrBitCast(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()).into() }
+        self.check_inserted(unsafe { core::LLVMBuildZExtOrBitCast(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
     }
     /// Build an instruction that truncates the high-order bits of value to fit into a certain type.
     pub fn build_trunc(&self, value: &Value, dest: &Type) -> &Value {
-        unsafe { core::LLVMBuildTrunc(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()).into() }
+        self.check_inserted(unsafe { core::LLVMBuildTrunc(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that sign extends its operand to the type `dest`.
+    pub fn build_sext(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildSExt(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that rounds a floating-point value to the smaller floating-point type `dest`.
+    pub fn build_fp_trunc(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildFPTrunc(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
     }
-    /// Build an instruction that inserts a value into an aggregate data value.
-    pub fn build_insert_value(&self, agg: &Value, elem: &Value, index: usize) -> &Value {
-        unsafe { core::LLVMBuildInsertValue(self.into(), agg.into(), elem.into(), index as c_uint, NULL_NAME.as_ptr()).into() }
+    /// Build an instruction that extends a floating-point value to the bigger floating-point type `dest`.
+    pub fn build_fp_ext(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildFPExt(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
     }
-    /// Build an instruction that extracts a value from an aggregate data value.
-    pub fn build_extract_value(&self, agg: &Value, index: usize) -> &Value {
-        unsafe { core::LLVMBuildExtractValue(self.into(), agg.into(), index as c_uint, NULL_NAME.as_ptr()).into() }
+    /// Build an instruction that converts a floating-point value to the unsigned integer type `dest`,
+    /// rounding towards zero.
+    pub fn build_fp_to_ui(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildFPToUI(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that converts a floating-point value to the signed integer type `dest`,
+    /// rounding towards zero.
+    pub fn build_fp_to_si(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildFPToSI(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that converts an unsigned integer to the floating-point type `dest`.
+    pub fn build_ui_to_fp(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildUIToFP(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that converts a signed integer to the floating-point type `dest`.
+    pub fn build_si_to_fp(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildSIToFP(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that casts a pointer to the pointer type `dest` in another address space.
+    pub fn build_addr_space_cast(&self, value: &Value, dest: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildAddrSpaceCast(self.into(), value.into(), dest.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build the instruction that converts `value` to the type `dest`, picked from the kinds of
+    /// the two types, or return `value` if it already has that type.
+    ///
+    /// Integers are sign extended and converted to and from floating-point numbers as signed
+    /// if `signed` is true, and as unsigned otherwise. Vectors are converted element by element,
+    /// and any other pair of types is bitcast.
+    pub fn build_cast<'a>(&'a self, value: &'a Value, dest: &'a Type, signed: bool) -> &'a Value {
+        let src = value.get_type();
+        if src == dest {
+            return value;
+        }
+        let (src_elem, dest_elem) = (scalar_type(src), scalar_type(dest));
+        match (src_elem.kind(), dest_elem.kind()) {
+            (TypeKind::Integer, TypeKind::Integer) => {
+                let src_width = IntegerType::from_super(src_elem).unwrap().get_width();
+                let dest_width = IntegerType::from_super(dest_elem).unwrap().get_width();
+                match src_width.cmp(&dest_width) {
+                    Ordering::Greater => self.build_trunc(value, dest),
+                    Ordering::Less if signed => self.build_sext(value, dest),
+                    Ordering::Less => self.build_zext(value, dest),
+                    Ordering::Equal => self.build_bit_cast(value, dest)
+                }
+            },
+            (TypeKind::Integer, TypeKind::Pointer) => self.build_int_to_ptr(value, dest),
+            (TypeKind::Pointer, TypeKind::Integer) => self.build_ptr_to_int(value, dest),
+            (TypeKind::Pointer, TypeKind::Pointer) => {
+                let (src_space, dest_space) = unsafe { (core::LLVMGetPointerAddressSpace(src_elem.into()), core::LLVMGetPointerAddressSpace(dest_elem.into())) };
+                if src_space != dest_space {
+                    self.build_addr_space_cast(value, dest)
+                } else {
+                    self.build_bit_cast(value, dest)
+                }
+            },
+            (TypeKind::Integer, _) if dest_elem.is_float() => if signed {
+                self.build_si_to_fp(value, dest)
+            } else {
+                self.build_ui_to_fp(value, dest)
+            },
+            (_, TypeKind::Integer) if src_elem.is_float() => if signed {
+                self.build_fp_to_si(value, dest)
+            } else {
+                self.build_fp_to_ui(value, dest)
+            },
+            _ if src_elem.is_float() && dest_elem.is_float() => match float_width(src_elem).cmp(&float_width(dest_elem)) {
+                Ordering::Greater => self.build_fp_trunc(value, dest),
+                Ordering::Less => self.build_fp_ext(value, dest),
+                Ordering::Equal => self.build_bit_cast(value, dest)
+            },
+            _ => self.build_bit_cast(value, dest)
+        }
+    }
+    /// Build instructions that insert `elem` into the aggregate `agg` at the path `indices`,
+    /// and return the new aggregate.
+    ///
+    /// Every index after the first goes one level deeper into nested aggregates, so `&[1, 0]`
+    /// replaces the first element of the second field of `agg`.
+    pub fn build_insert_value(&self, agg: &Value, elem: &Value, indices: &[usize]) -> &Value {
+        let (&index, rest) = indices.split_first().expect("expected at least one index");
+        let elem: LLVMValueRef = if rest.is_empty() {
+            elem.into()
+        } else {
+            let inner = self.build_extract_value(agg, &[index]);
+            self.build_insert_value(inner, elem, rest).into()
+        };
+        self.check_inserted(unsafe { core::LLVMBuildInsertValue(self.into(), agg.into(), elem, index as c_uint, NULL_NAME.as_ptr()) })
+    }
+    /// Build instructions that extract the value at the path `indices` from the aggregate `agg`.
+    ///
+    /// Every index after the first goes one level deeper into nested aggregates, so `&[1, 0]`
+    /// extracts the first element of the second field of `agg`.
+    pub fn build_extract_value(&self, agg: &Value, indices: &[usize]) -> &Value {
+        assert!(!indices.is_empty(), "expected at least one index");
+        let mut value: LLVMValueRef = agg.into();
+        for &index in indices {
+            value = unsafe { core::LLVMBuildExtractValue(self.into(), value, index as c_uint, NULL_NAME.as_ptr()) };
+            self.check_inserted(value);
+        }
+        value.into()
+    }
+    /// Build an instruction that extracts the element at `index` from the vector `vector`.
+    pub fn build_extract_element(&self, vector: &Value, index: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildExtractElement(self.into(), vector.into(), index.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that replaces the element at `index` of the vector `vector` with
+    /// `elem`, and returns the new vector.
+    pub fn build_insert_element(&self, vector: &Value, elem: &Value, index: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildInsertElement(self.into(), vector.into(), elem.into(), index.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that makes a vector of the elements of `left` and `right` picked by
+    /// `mask`.
+    ///
+    /// The indices in the mask count the elements of `left` followed by the elements of `right`,
+    /// so with two vectors of four elements, `&[0, 4, 1, 5]` interleaves their first halves.
+    pub fn build_shuffle_vector(&self, left: &Value, right: &Value, mask: &[u32]) -> &Value {
+        let len = match VectorType::from_super(left.get_type()) {
+            Some(ty) => ty.get_size(),
+            None => panic!("expected vector, got {:?}", left.get_type())
+        };
+        if let Some(index) = mask.iter().find(|&&index| index as usize >= len * 2) {
+            panic!("mask index {} is out of range for two vectors of {} elements", index, len)
+        }
+        let ctx = left.get_context();
+        let mask: Vec<&Value> = mask.iter().map(|index| index.compile(ctx)).collect();
+        let mask = Value::new_vector(&mask);
+        self.check_inserted(unsafe { core::LLVMBuildShuffleVector(self.into(), left.into(), right.into(), mask.into(), NULL_NAME.as_ptr()) })
     }
     /// Build an instruction that computes the address of a subelement of an aggregate data structure.
     ///
-    /// Basically type-safe pointer arithmetic.
+    /// Basically type-safe pointer arithmetic. This is the same as `build_inbounds_gep`.
     pub fn build_gep(&self, pointer: &Value, indices: &[&Value]) -> &Value {
-        unsafe { core::LLVMBuildInBoundsGEP(self.into(), pointer.into(), indices.as_ptr() as *mut LLVMValueRef, indices.len() as c_uint, NULL_NAME.as_ptr()).into() }
+        self.build_inbounds_gep(pointer, indices)
+    }
+    /// Build an instruction like `build_gep` that is also marked as staying in bounds of the
+    /// object `pointer` points into, which lets the optimizer assume the address doesn't wrap.
+    pub fn build_inbounds_gep(&self, pointer: &Value, indices: &[&Value]) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildInBoundsGEP(self.into(), pointer.into(), indices.as_ptr() as *mut LLVMValueRef, indices.len() as c_uint, NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction like `build_gep` that isn't marked as staying in bounds, for addresses
+    /// that may point outside the object `pointer` points into.
+    pub fn build_plain_gep(&self, pointer: &Value, indices: &[&Value]) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildGEP(self.into(), pointer.into(), indices.as_ptr() as *mut LLVMValueRef, indices.len() as c_uint, NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that computes the address of the field at `index` of the struct
+    /// `pointer` points to.
+    pub fn build_struct_gep(&self, pointer: &Value, index: usize) -> &Value {
+        let ty = PointerType::from_super(pointer.get_type()).and_then(|ty| StructType::from_super(ty.get_element()));
+        let num_fields = match ty {
+            Some(ty) => ty.get_elements().len(),
+            None => panic!("expected pointer to struct, got {:?}", pointer.get_type())
+        };
+        if index >= num_fields {
+            panic!("no such field {} on {:?}, which has {} fields", index, pointer.get_type(), num_fields)
+        }
+        self.check_inserted(unsafe { core::LLVMBuildStructGEP(self.into(), pointer.into(), index as c_uint, NULL_NAME.as_ptr()) })
     }
     /// Build an instruction that runs whichever block matches the value, or `default` if none of them matched it.
-    pub fn build_switch(&self, value: &Value, default: &BasicBlock, cases: &[(&Value, &BasicBlock)]) -> &Value {
+    pub fn build_switch(&self, value: &Value, default: &BasicBlock, cases: &[(&Value, &BasicBlock)]) -> &Switch {
+        let switch = self.build_empty_switch(value, default, cases.len());
+        for &(case, block) in cases {
+            switch.add_case(case, block);
+        }
+        switch
+    }
+    /// Build a switch on `value` that jumps to `default` until cases are added to it with
+    /// `Switch::add_case`, which is handy when the cases are discovered while building them.
+    ///
+    /// `expected_cases` is how many cases to reserve room for, which may be too few or too many.
+    pub fn build_empty_switch(&self, value: &Value, default: &BasicBlock, expected_cases: usize) -> &Switch {
+        let switch = unsafe { core::LLVMBuildSwitch(self.into(), value.into(), default.into(), expected_cases as c_uint) };
+        self.check_inserted(switch);
+        switch.into()
+    }
+    /// Build instructions that check `index` is less than `len`, handling failure as `on_fail`
+    /// says, then return a pointer to the element at `index` of `ptr`.
+    ///
+    /// If `on_fail` is `CheckFailure::Call`, the function is called with `index` and `len`.
+    /// This leaves the builder positioned in a new block that runs when the check passes.
+    pub fn build_checked_index(&self, ptr: &Value, index: &Value, len: &Value, on_fail: CheckFailure) -> &Value {
+        let in_bounds = self.build_icmp(index, len, IntPredicate::UnsignedLessThan);
+        self.build_check(in_bounds, on_fail, &[index, len]);
+        self.build_inbounds_gep(ptr, &[index])
+    }
+    /// Build an instruction that yields true if `ptr` is null.
+    pub fn build_is_null(&self, ptr: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildIsNull(self.into(), ptr.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that yields true if `ptr` isn't null.
+    pub fn build_is_not_null(&self, ptr: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildIsNotNull(self.into(), ptr.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build instructions that yield the number of elements between the pointers `left` and
+    /// `right`, which must have the same type, as a signed integer the size of a pointer.
+    ///
+    /// This is `left - right` divided by the size of the element type, like pointer subtraction
+    /// in C, so the pointers should point into the same array.
+    pub fn build_ptr_diff(&self, left: &Value, right: &Value) -> &Value {
+        assert_eq!(left.get_type(), right.get_type());
+        self.check_inserted(unsafe { core::LLVMBuildPtrDiff(self.into(), left.into(), right.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build a call to `llvm.assume`, which tells the optimizer that `cond` is always true.
+    ///
+    /// If `cond` is ever false, the behaviour is undefined.
+    pub fn build_assume(&self, cond: &Value) -> &Value {
+        let ctx = self.get_function().get_context();
+        let assume = self.get_intrinsic("llvm.assume", Type::get::<()>(ctx), &[Type::get::<bool>(ctx)]);
+        self.build_call(assume, &[cond])
+    }
+    /// Build an instruction that reads the next extra argument of type `ty` from the argument
+    /// list `list` points to, and moves the list on to the argument after it.
+    pub fn build_va_arg(&self, list: &Value, ty: &Type) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildVAArg(self.into(), list.into(), ty.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build a call to `llvm.va_start` that makes `list` point to the first extra argument of
+    /// the variadic function being built.
+    ///
+    /// `list` must point to memory that is as big and as aligned as the `va_list` type of the
+    /// target, like 24 bytes aligned to 8 on x86-64.
+    pub fn build_va_start(&self, list: &Value) -> &Value {
+        self.build_va_intrinsic("llvm.va_start", &[list])
+    }
+    /// Build a call to `llvm.va_end`, which must be called on every argument list started by
+    /// `build_va_start` or copied by `build_va_copy` before the function returns.
+    pub fn build_va_end(&self, list: &Value) -> &Value {
+        self.build_va_intrinsic("llvm.va_end", &[list])
+    }
+    /// Build a call to `llvm.va_copy` that copies the position in the argument list `src`
+    /// points to into the one `dest` points to.
+    pub fn build_va_copy(&self, dest: &Value, src: &Value) -> &Value {
+        self.build_va_intrinsic("llvm.va_copy", &[dest, src])
+    }
+    /// Build instructions that tell the optimizer `ptr` is a multiple of `align`, which must be
+    /// a power of two, so it can use aligned loads and stores through it.
+    pub fn build_assume_aligned(&self, ptr: &Value, align: usize) -> &Value {
+        assert!(align.is_power_of_two(), "expected power of two alignment, got {}", align);
+        let ctx = self.get_function().get_context();
+        let addr = self.build_ptr_to_int(ptr, Type::get::<usize>(ctx));
+        let low_bits = self.build_and(addr, (align - 1).compile(ctx));
+        self.build_assume(self.build_icmp(low_bits, 0usize.compile(ctx), IntPredicate::Equal))
+    }
+    /// Build instructions that tell the optimizer `ptr` is never null.
+    pub fn build_assume_nonnull(&self, ptr: &Value) -> &Value {
+        self.build_assume(self.build_is_not_null(ptr))
+    }
+    /// Build instructions that jump to `fail` if `ptr` is null, then return the branch.
+    ///
+    /// The branch is marked with `!make.implicit` metadata, which lets the backend fold the
+    /// check into a faulting load or store when null pointers are rare. This leaves the builder
+    /// positioned in a new block that runs when `ptr` isn't null.
+    pub fn build_null_check(&self, ptr: &Value, fail: &BasicBlock) -> &Value {
+        let function = self.get_function();
+        let ok = function.append("not_null");
         unsafe {
-            let switch = core::LLVMBuildSwitch(self.into(), value.into(), default.into(), cases.len() as c_uint);
-            for case in cases {
-                core::LLVMAddCase(switch, case.0.into(), case.1.into());
+            let ctx = function.get_context();
+            let is_null = core::LLVMBuildIsNull(self.into(), ptr.into(), NULL_NAME.as_ptr());
+            self.check_inserted(is_null);
+            let branch = core::LLVMBuildCondBr(self.into(), is_null, fail.into(), ok.into());
+            let kind = ctx.get_metadata_kind(MetadataKind::MakeImplicit);
+            core::LLVMSetMetadata(branch, kind as c_uint, core::LLVMMDNodeInContext(ctx.into(), ptr::null_mut(), 0));
+            self.position_at_end(ok);
+            branch.into()
+        }
+    }
+    /// Build a call that marks the `size` bytes at `ptr` as unaddressable for AddressSanitizer,
+    /// so any access to them is reported.
+    ///
+    /// This lets custom allocators that hand out memory from bigger chunks cooperate with ASan.
+    /// It calls `__asan_poison_memory_region` from the ASan runtime, so the code must run in a
+    /// process with the runtime loaded. Accesses are only checked in functions instrumented by ASan,
+    /// which need the `sanitize_address` attribute that isn't exposed by the C API, so it must
+    /// come from bitcode produced by a frontend.
+    pub fn build_asan_poison(&self, ptr: &Value, size: &Value) -> &Value {
+        self.build_asan_call("__asan_poison_memory_region", ptr, size)
+    }
+    /// Build a call that marks the `size` bytes at `ptr` as addressable again for AddressSanitizer.
+    ///
+    /// This calls `__asan_unpoison_memory_region` from the ASan runtime.
+    pub fn build_asan_unpoison(&self, ptr: &Value, size: &Value) -> &Value {
+        self.build_asan_call("__asan_unpoison_memory_region", ptr, size)
+    }
+    /// Build a call to `llvm.memcpy` that copies `size` bytes from `src` to `dst`, which must
+    /// not overlap, and are known to be aligned to `dst_align` and `src_align` bytes.
+    ///
+    /// The pointers are cast to `i8*` in their own address spaces, and `size` can be any
+    /// integer type. LLVM 3.8 only takes one alignment for both pointers, so this
+    /// gives it the smaller of the two.
+    pub fn build_memcpy(&self, dst: &Value, dst_align: usize, src: &Value, src_align: usize, size: &Value) -> &Value {
+        self.build_mem_transfer("llvm.memcpy", dst, src, size, dst_align.min(src_align))
+    }
+    /// Build a call to `llvm.memmove` that copies `size` bytes from `src` to `dst` like
+    /// `build_memcpy`, but which may overlap.
+    pub fn build_memmove(&self, dst: &Value, dst_align: usize, src: &Value, src_align: usize, size: &Value) -> &Value {
+        self.build_mem_transfer("llvm.memmove", dst, src, size, dst_align.min(src_align))
+    }
+    /// Build a call to `llvm.memset` that sets `size` bytes at `dst`, which is known to be
+    /// aligned to `dst_align` bytes, to the `i8` value `value`.
+    pub fn build_memset(&self, dst: &Value, dst_align: usize, value: &Value, size: &Value) -> &Value {
+        let ctx = self.get_function().get_context();
+        let byte_ptr = self.get_byte_ptr(dst);
+        let (bool_ty, int_ty) = (Type::get::<bool>(ctx), Type::get::<u32>(ctx));
+        let func = self.get_overloaded_intrinsic("llvm.memset", &[byte_ptr, size.get_type()], Type::get::<()>(ctx), &[byte_ptr, Type::get::<u8>(ctx), size.get_type(), int_ty, bool_ty]);
+        let dst = self.build_bit_cast(dst, byte_ptr);
+        self.build_call(func, &[dst, value, size, (dst_align as u32).compile(ctx), false.compile(ctx)])
+    }
+    /// Add a private constant global holding `text` followed by a null byte to the module this
+    /// builder is positioned in, and return an `i8*` to its first byte, like Clang's
+    /// `CreateGlobalStringPtr`.
+    ///
+    /// The global is `unnamed_addr`, so the linker can merge it with identical strings. Unlike
+    /// `LLVMBuildGlobalStringPtr`, `text` can contain null bytes.
+    pub fn build_global_string_ptr(&self, text: &str) -> &Value {
+        let module = self.get_function().get_parent();
+        let ctx = module.get_context();
+        let global = module.add_global_variable(".str", Value::new_string(ctx, text, false));
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(UnnamedAddr::Global);
+        global.set_alignment(1);
+        let zero = 0u32.compile(ctx);
+        Value::new_inbounds_gep(global, &[zero, zero])
+    }
+    /// Build a call to the `llvm.memcpy` or `llvm.memmove` intrinsic `name`.
+    fn build_mem_transfer(&self, name: &str, dst: &Value, src: &Value, size: &Value, align: usize) -> &Value {
+        let ctx = self.get_function().get_context();
+        let (dst_ptr, src_ptr) = (self.get_byte_ptr(dst), self.get_byte_ptr(src));
+        let (bool_ty, int_ty) = (Type::get::<bool>(ctx), Type::get::<u32>(ctx));
+        let func = self.get_overloaded_intrinsic(name, &[dst_ptr, src_ptr, size.get_type()], Type::get::<()>(ctx), &[dst_ptr, src_ptr, size.get_type(), int_ty, bool_ty]);
+        let (dst, src) = (self.build_bit_cast(dst, dst_ptr), self.build_bit_cast(src, src_ptr));
+        self.build_call(func, &[dst, src, size, (align as u32).compile(ctx), false.compile(ctx)])
+    }
+    /// Build a call to the ASan runtime function `name`, which takes a pointer and a size.
+    fn build_asan_call(&self, name: &str, ptr: &Value, size: &Value) -> &Value {
+        let ctx = self.get_function().get_context();
+        let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+        let func = self.get_intrinsic(name, Type::get::<()>(ctx), &[byte_ptr, Type::get::<usize>(ctx)]);
+        let ptr = self.build_bit_cast(ptr, byte_ptr);
+        self.build_call(func, &[ptr, size])
+    }
+    /// Build instructions that branch on `cond`, continuing in a new block when it is true and
+    /// handling failure as `on_fail` says when it is false.
+    fn build_check(&self, cond: &Value, on_fail: CheckFailure, operands: &[&Value]) {
+        let current = unsafe { core::LLVMGetInsertBlock(self.into()) };
+        let function = self.get_function();
+        let ok = function.append("check_ok");
+        let fail = match on_fail {
+            CheckFailure::Jump(block) => block,
+            CheckFailure::Trap => {
+                let fail = function.append("check_failed");
+                self.position_at_end(fail);
+                let trap = self.get_intrinsic("llvm.trap", Type::get::<()>(function.get_context()), &[]);
+                self.build_call(trap, &[]);
+                self.build_unreachable();
+                fail
+            },
+            CheckFailure::Call(handler) => {
+                let fail = function.append("check_failed");
+                self.position_at_end(fail);
+                self.build_call(handler, operands);
+                self.build_unreachable();
+                fail
             }
-            switch.into()
+        };
+        unsafe { core::LLVMPositionBuilderAtEnd(self.into(), current) }
+        self.build_cond_br(cond, ok, Some(fail));
+        self.position_at_end(ok);
+    }
+    /// Panic if the instruction `inst` that was just built came after a terminator, since it
+    /// would never run and the verifier would reject the function.
+    ///
+    /// This has to check after the instruction is built because the C API can't tell where
+    /// in the block the builder is positioned, so the instruction is erased before panicking,
+    /// which leaves the block valid if the panic is caught. Operations on constants are folded into
+    /// constants rather than built, so those are returned as they are.
+    fn check_inserted(&self, inst: LLVMValueRef) -> &Value {
+        unsafe {
+            if core::LLVMIsAInstruction(inst).is_null() {
+                return inst.into();
+            }
+            let prev = core::LLVMGetPreviousInstruction(inst);
+            if !prev.is_null() && !core::LLVMIsATerminatorInst(prev).is_null() {
+                let block: &BasicBlock = core::LLVMGetInstructionParent(inst).into();
+                let function = block.get_parent().and_then(|function| function.get_name()).unwrap_or("<unnamed>");
+                let message = format!("cannot build instructions after the terminator of block {} in {}", block.get_name().unwrap_or("<unnamed>"), function);
+                // nothing uses the instruction yet, so erasing it leaves the block as it was
+                core::LLVMInstructionEraseFromParent(inst);
+                panic!("{}", message)
+            }
+            inst.into()
         }
     }
+    /// Build an instruction at the start of the current function's entry block that allocates
+    /// a variable of type `ty`, so it isn't allocated again every time a loop runs.
+    fn build_entry_alloca(&self, ty: &Type) -> &Value {
+        let builder = Builder::new(ty.get_context());
+        builder.position_at_start(self.get_function().get_entry().unwrap());
+        unsafe { core::LLVMBuildAlloca((&*builder).into(), ty.into(), NULL_NAME.as_ptr()).into() }
+    }
+    /// Returns the function containing the block this builder is positioned in.
+    fn get_function(&self) -> &Function {
+        self.current_function().expect("builder is not positioned in a function")
+    }
+    /// Build a call to the `va_list` intrinsic `name` with the argument lists given.
+    fn build_va_intrinsic(&self, name: &str, lists: &[&Value]) -> &Value {
+        let ctx = self.get_function().get_context();
+        let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+        let params = vec![&**byte_ptr; lists.len()];
+        let intrinsic = self.get_intrinsic(name, Type::get::<()>(ctx), &params);
+        let args: Vec<&Value> = lists.iter().map(|&list| self.build_bit_cast(list, byte_ptr)).collect();
+        self.build_call(intrinsic, &args)
+    }
+    /// Returns the intrinsic with the name and signature given from the module this builder is
+    /// positioned in, declaring it if it hasn't been declared yet.
+    fn get_intrinsic<'a>(&'a self, name: &str, ret: &'a Type, args: &[&'a Type]) -> &'a Function {
+        let module = self.get_function().get_parent();
+        module.get_function(name).unwrap_or_else(||
+            &*module.add_function(name, FunctionType::new(ret, args))
+        )
+    }
+    /// Returns the overloaded intrinsic `name` for the types `overloads` with the signature given
+    /// from the module this builder is positioned in, declaring it if it hasn't been declared yet.
+    ///
+    /// This panics if LLVM doesn't define the intrinsic with that signature.
+    fn get_overloaded_intrinsic<'a>(&'a self, name: &str, overloads: &[&Type], ret: &'a Type, args: &[&'a Type]) -> &'a Function {
+        let module = self.get_function().get_parent();
+        match module.get_overloaded_intrinsic(name, overloads, FunctionType::new(ret, args)) {
+            Ok(func) => func,
+            Err(err) => panic!("{}", &*err)
+        }
+    }
+    /// Returns `i8*` in the address space of the pointer `ptr`.
+    fn get_byte_ptr(&self, ptr: &Value) -> &Type {
+        let ctx = self.get_function().get_context();
+        let space = match PointerType::from_super(ptr.get_type()) {
+            Some(ty) => unsafe { core::LLVMGetPointerAddressSpace(ty.into()) },
+            None => panic!("expected a pointer, got {:?}", ptr)
+        };
+        unsafe { core::LLVMPointerType(Type::get::<u8>(ctx).into(), space) }.into()
+    }
+    /// Make a wrapper around this builder whose arithmetic operations check for overflow,
+    /// handling it as `on_overflow` says.
+    ///
+    /// The operands are treated as signed integers if `signed` is true, and unsigned integers otherwise.
+    pub fn with_overflow_checks<'a>(&'a self, signed: bool, on_overflow: CheckFailure<'a>) -> CheckedBuilder<'a> {
+        CheckedBuilder {
+            builder: self,
+            signed: signed,
+            on_overflow: on_overflow
+        }
+    }
+    /// Make a wrapper around this builder whose atomic operations are lowered as `options` says.
+    pub fn with_atomics<'a>(&'a self, options: AtomicOptions<'a>) -> AtomicBuilder<'a> {
+        AtomicBuilder {
+            builder: self,
+            options: options
+        }
+    }
+    /// Build instructions that run `then` if `cond` is true and `otherwise` if it isn't, then
+    /// return a phi of the values they return.
+    ///
+    /// Each closure is called with this builder positioned in a new block for its branch, and
+    /// can create blocks of its own as long as it leaves the builder in a block without a
+    /// terminator. If the branches return void values, no phi is built and the value returned by
+    /// `then` is returned. This leaves the builder positioned in the block after both branches.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let ctx = Context::new();
+    /// let module = Module::new("max", &ctx);
+    /// let func = module.add_function("max", Type::get::<fn(i32, i32) -> i32>(&ctx));
+    /// let builder = Builder::new(&ctx);
+    /// builder.position_at_end(func.append("entry"));
+    /// let (a, b) = (&func[0], &func[1]);
+    /// let max = builder.if_then_else(builder.build_icmp(a, b, IntPredicate::SignedGreaterThan), |_| a, |_| b);
+    /// builder.build_ret(max);
+    /// module.verify().unwrap();
+    /// ```
+    pub fn if_then_else<'a, T, E>(&'a self, cond: &'a Value, then: T, otherwise: E) -> &'a Value
+        where T: FnOnce(&'a Builder) -> &'a Value, E: FnOnce(&'a Builder) -> &'a Value {
+        let function = self.get_function();
+        let (then_block, else_block, merge) = (function.append("then"), function.append("else"), function.append("merge"));
+        self.build_cond_br(cond, then_block, Some(else_block));
+        self.position_at_end(then_block);
+        let then_value = then(self);
+        let then_end = self.get_insert_block().unwrap();
+        self.build_br(merge);
+        self.position_at_end(else_block);
+        let else_value = otherwise(self);
+        let else_end = self.get_insert_block().unwrap();
+        self.build_br(merge);
+        self.position_at_end(merge);
+        let ty = then_value.get_type();
+        if ty.is_void() {
+            return then_value
+        }
+        if else_value.get_type() != ty {
+            panic!("branches of if_then_else return {:?} and {:?}", ty, else_value.get_type())
+        }
+        self.build_phi(ty, &[(then_value, then_end), (else_value, else_end)])
+    }
+    /// Build a loop that runs the instructions built by `body` while the condition built by
+    /// `cond` is true.
+    ///
+    /// `cond` is called with this builder positioned in a new block that every iteration starts
+    /// in, and `body` with it positioned in a new block that runs when the condition is true.
+    /// Values that change between iterations should be kept in stack variables. This leaves the
+    /// builder positioned in the block after the loop.
+    pub fn loop_while<'a, C, B>(&'a self, cond: C, body: B) where C: FnOnce(&'a Builder) -> &'a Value, B: FnOnce(&'a Builder) {
+        let function = self.get_function();
+        let (head, body_block, exit) = (function.append("loop_cond"), function.append("loop_body"), function.append("loop_end"));
+        self.build_br(head);
+        self.position_at_end(head);
+        let cond = cond(self);
+        self.build_cond_br(cond, body_block, Some(exit));
+        self.position_at_end(body_block);
+        body(self);
+        self.build_br(head);
+        self.position_at_end(exit);
+    }
+    /// Build an instruction that jumps to `cases[value]`, or `default` if `value` is out of range.
+    ///
+    /// This emits a `switch` over dense case values, which LLVM lowers to a jump table.
+    pub fn build_dense_switch(&self, value: &Value, default: &BasicBlock, cases: &[&BasicBlock]) -> &Value {
+        let ty = value.get_type();
+        let cases: Vec<_> = cases.iter().enumerate().map(|(index, &block)| {
+            let case:&Value = unsafe { core::LLVMConstInt(ty.into(), index as c_ulonglong, 0) }.into();
+            (case, block)
+        }).collect();
+        self.build_switch(value, default, &cases)
+    }
+    /// Build instructions that jump to `cases[value]`, or `default` if `value` is out of range,
+    /// using an explicit table of block addresses and an `indirectbr`.
+    ///
+    /// This is useful for interpreter dispatch, where the table can be reused by other jumps.
+    pub fn build_jump_table(&self, value: &Value, default: &BasicBlock, cases: &[&BasicBlock]) -> &Value {
+        let function = self.get_function();
+        let ctx = function.get_context();
+        let ty = value.get_type();
+        let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+        let table = unsafe {
+            let mut addresses:Vec<LLVMValueRef> = cases.iter().map(|&block| block.get_address().into()).collect();
+            let table:&Value = core::LLVMConstArray(byte_ptr.into(), addresses.as_mut_ptr(), addresses.len() as c_uint).into();
+            let global = function.get_parent().add_global_variable("jump_table", table);
+            global.set_linkage(Linkage::Private);
+            global.set_constant(true);
+            global
+        };
+        let len:&Value = unsafe { core::LLVMConstInt(ty.into(), cases.len() as c_ulonglong, 0) }.into();
+        let in_range = self.build_icmp(value, len, IntPredicate::UnsignedLessThan);
+        self.build_check(in_range, CheckFailure::Jump(default), &[]);
+        let zero:&Value = unsafe { core::LLVMConstInt(ty.into(), 0, 0) }.into();
+        let address = self.build_load(self.build_inbounds_gep(table, &[zero, value]));
+        self.build_indirect_br(address, cases)
+    }
+    /// Build an instruction that jumps to the block whose address is `address`, which must
+    /// be one of `possible_dests`.
+    ///
+    /// Block addresses come from `BasicBlock::get_address`, and more destinations can be added
+    /// with `IndirectBr::add_destination`.
+    ///
+    /// LLVM 3.8 has no `callbr`, so inline asm can't jump to blocks the way `asm goto` does.
+    /// Have the asm return a value that picks the destination instead, and jump to its address
+    /// with this.
+    pub fn build_indirect_br(&self, address: &Value, possible_dests: &[&BasicBlock]) -> &IndirectBr {
+        let branch = unsafe { core::LLVMBuildIndirectBr(self.into(), address.into(), possible_dests.len() as c_uint) };
+        self.check_inserted(branch);
+        let branch: &IndirectBr = branch.into();
+        for &block in possible_dests {
+            branch.add_destination(block);
+        }
+        branch
+    }
     un_op!{build_load, LLVMBuildLoad}
     un_op!{build_neg, LLVMBuildNeg}
     un_op!{build_not, LLVMBuildNot}
@@ -160,32 +981,437 @@ impl Builder {
     bin_op!{build_ashr, LLVMBuildAShr}
     bin_op!{build_and, LLVMBuildAnd}
     bin_op!{build_or, LLVMBuildOr}
-    /// Build an instruction to compare the values `a` and `b` with the predicate / comparative operator `pred`.
-    pub fn build_cmp(&self, a: &Value, b: &Value, pred: Predicate) -> &Value {
-        let (at, bt) = (a.get_type(), b.get_type());
-        assert_eq!(at, bt);
-        if at.is_integer() {
-            let pred = match pred {
-                Predicate::Equal => LLVMIntPredicate::LLVMIntEQ,
-                Predicate::NotEqual => LLVMIntPredicate::LLVMIntNE,
-                Predicate::GreaterThan => LLVMIntPredicate::LLVMIntSGT,
-                Predicate::GreaterThanOrEqual => LLVMIntPredicate::LLVMIntSGE,
-                Predicate::LessThan => LLVMIntPredicate::LLVMIntSLT,
-                Predicate::LessThanOrEqual => LLVMIntPredicate::LLVMIntSLE
-            };
-            unsafe { core::LLVMBuildICmp(self.into(), pred, a.into(), b.into(), NULL_NAME.as_ptr()) }.into()
-        } else if at.is_float() {
-            let pred = match pred {
-                Predicate::Equal => LLVMRealPredicate::LLVMRealOEQ,
-                Predicate::NotEqual => LLVMRealPredicate::LLVMRealONE,
-                Predicate::GreaterThan => LLVMRealPredicate::LLVMRealOGT,
-                Predicate::GreaterThanOrEqual => LLVMRealPredicate::LLVMRealOGE,
-                Predicate::LessThan => LLVMRealPredicate::LLVMRealOLT,
-                Predicate::LessThanOrEqual => LLVMRealPredicate::LLVMRealOLE
-            };
-            unsafe { core::LLVMBuildFCmp(self.into(), pred, a.into(), b.into(), NULL_NAME.as_ptr()) }.into()
-        } else {
-            panic!("expected numzextbers, got {:?}", at)
+    bin_op!{build_xor, LLVMBuildXor}
+    bin_op!{build_udiv, LLVMBuildUDiv}
+    bin_op!{build_lshr, LLVMBuildLShr}
+    bin_op!{build_nsw_add, LLVMBuildNSWAdd}
+    bin_op!{build_nuw_add, LLVMBuildNUWAdd}
+    bin_op!{build_nsw_sub, LLVMBuildNSWSub}
+    bin_op!{build_nuw_sub, LLVMBuildNUWSub}
+    bin_op!{build_nsw_mul, LLVMBuildNSWMul}
+    bin_op!{build_nuw_mul, LLVMBuildNUWMul}
+    /// Build an `sdiv` instruction marked `exact`, which lets the optimizer assume `left` is a
+    /// multiple of `right`.
+    ///
+    /// The 3.8 C API can only mark `add`, `sub` and `mul` as `nsw` or `nuw`, and `sdiv` as
+    /// `exact`. It has no way to build `shl` with `nsw` or `nuw`, or `udiv`, `lshr` and `ashr`
+    /// with `exact`, so there are no builders for those.
+    pub fn build_exact_sdiv(&self, left: &Value, right: &Value) -> &Value {
+        self.check_inserted(unsafe { core::LLVMBuildExactSDiv(self.into(), left.into(), right.into(), NULL_NAME.as_ptr()) })
+    }
+    overflow_op!{build_sadd_with_overflow, "sadd"}
+    overflow_op!{build_uadd_with_overflow, "uadd"}
+    overflow_op!{build_ssub_with_overflow, "ssub"}
+    overflow_op!{build_usub_with_overflow, "usub"}
+    overflow_op!{build_smul_with_overflow, "smul"}
+    overflow_op!{build_umul_with_overflow, "umul"}
+    /// Build a call to the `with.overflow` intrinsic for `op` on the integers `left` and `right`,
+    /// and return its result and whether it overflowed.
+    ///
+    /// This panics if the operands aren't integers of the same type.
+    fn build_with_overflow(&self, op: &str, left: &Value, right: &Value) -> (&Value, &Value) {
+        let ty = left.get_type();
+        if !ty.is_integer() || right.get_type() != ty {
+            panic!("expected integer operands of the same type for {}, got {:?} and {:?}", op, left, right)
         }
+        let ctx = ty.get_context();
+        let ret = StructType::new(ctx, &[ty, Type::get::<bool>(ctx)], false);
+        let func = self.get_overloaded_intrinsic(&format!("llvm.{}.with.overflow", op), &[ty], ret, &[ty, ty]);
+        let pair = self.build_call(func, &[left, right]);
+        (self.build_extract_value(pair, &[0]), self.build_extract_value(pair, &[1]))
+    }
+    /// Build an instruction that compares the integers or pointers `a` and `b` with `pred`.
+    pub fn build_icmp(&self, a: &Value, b: &Value, pred: IntPredicate) -> &Value {
+        assert_eq!(a.get_type(), b.get_type());
+        self.check_inserted(unsafe { core::LLVMBuildICmp(self.into(), pred.into(), a.into(), b.into(), NULL_NAME.as_ptr()) })
+    }
+    /// Build an instruction that compares the floating-point numbers `a` and `b` with `pred`.
+    pub fn build_fcmp(&self, a: &Value, b: &Value, pred: RealPredicate) -> &Value {
+        assert_eq!(a.get_type(), b.get_type());
+        self.check_inserted(unsafe { core::LLVMBuildFCmp(self.into(), pred.into(), a.into(), b.into(), NULL_NAME.as_ptr()) })
+    }
+}
+
+/// What `Builder::finalize` does with blocks that don't end with a terminator.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Unterminated {
+    /// End them with an `unreachable` instruction.
+    Unreachable,
+    /// Fail with an `UnterminatedBlock` error.
+    Fail
+}
+
+/// The error returned by `Builder::finalize` when a block doesn't end with a terminator.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnterminatedBlock {
+    /// The name of the function the block is in.
+    pub function: String,
+    /// The name of the block.
+    pub block: String
+}
+impl fmt::Display for UnterminatedBlock {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "block {} in {} has no terminator", self.block, self.function)
+    }
+}
+impl error::Error for UnterminatedBlock {
+    fn description(&self) -> &str {
+        "block has no terminator"
+    }
+}
+
+/// A wrapper around a builder whose integer arithmetic operations check for overflow using
+/// the `with.overflow` intrinsics.
+///
+/// This dereferences to the builder it wraps, so every other operation is still available.
+#[derive(Copy, Clone)]
+pub struct CheckedBuilder<'a> {
+    builder: &'a Builder,
+    signed: bool,
+    on_overflow: CheckFailure<'a>
+}
+macro_rules! checked_op(
+    ($name:ident, $op:expr) => (
+        /// Build instructions that compute the operation on two integers of the same type
+        /// and check it for overflow.
+        ///
+        /// This leaves the builder positioned in a new block that runs when no overflow happened.
+        pub fn $name(&self, left: &'a Value, right: &'a Value) -> &'a Value {
+            self.build_checked(left, right, $op)
+        }
+    );
+);
+impl<'a> CheckedBuilder<'a> {
+    checked_op!{build_add, "add"}
+    checked_op!{build_sub, "sub"}
+    checked_op!{build_mul, "mul"}
+    fn build_checked(&self, left: &'a Value, right: &'a Value, op: &str) -> &'a Value {
+        let builder = self.builder;
+        let op = format!("{}{}", if self.signed { 's' } else { 'u' }, op);
+        let (result, overflowed) = builder.build_with_overflow(&op, left, right);
+        builder.build_check(builder.build_not(overflowed), self.on_overflow, &[left, right]);
+        result
+    }
+}
+impl<'a> Deref for CheckedBuilder<'a> {
+    type Target = Builder;
+    fn deref(&self) -> &Builder {
+        self.builder
+    }
+}
+
+/// How atomic operations wider than the target supports natively are lowered.
+#[derive(Copy, Clone)]
+pub enum AtomicLowering<'a> {
+    /// Call the `__atomic_*_N` functions from libatomic or compiler-rt.
+    Libcall,
+    /// Call `lock` with the address of the operand as a byte pointer before doing the operation
+    /// non-atomically, then call `unlock` with the same address.
+    Lock {
+        /// The function that locks the address it is given.
+        lock: &'a Function,
+        /// The function that unlocks the address it is given.
+        unlock: &'a Function
+    }
+}
+
+/// The options that control how an `AtomicBuilder` lowers atomic operations.
+#[derive(Copy, Clone)]
+pub struct AtomicOptions<'a> {
+    /// The width in bits of the widest integer the target can operate on atomically, or zero if
+    /// it has no native atomics at all.
+    pub max_width: usize,
+    /// How operations wider than `max_width` are lowered.
+    pub lowering: AtomicLowering<'a>
+}
+
+/// The ordering constraints of an atomic operation, which say what other memory accesses
+/// it synchronizes with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AtomicOrdering {
+    /// The operation is atomic but doesn't order anything, like a Java shared variable.
+    Unordered,
+    /// The operation has a single total order with other operations on the same address, like
+    /// `memory_order_relaxed` in C11.
+    Monotonic,
+    /// Later accesses can't move before the operation.
+    Acquire,
+    /// Earlier accesses can't move after the operation.
+    Release,
+    /// Both `Acquire` and `Release`.
+    AcquireRelease,
+    /// `AcquireRelease`, and there is a single total order of every sequentially consistent
+    /// operation.
+    SequentiallyConsistent
+}
+impl AtomicOrdering {
+    /// Returns how strong this ordering is, where `Acquire` and `Release` are as strong as
+    /// each other, even though neither implies the other.
+    fn strength(self) -> u8 {
+        match self {
+            AtomicOrdering::Unordered => 0,
+            AtomicOrdering::Monotonic => 1,
+            AtomicOrdering::Acquire | AtomicOrdering::Release => 2,
+            AtomicOrdering::AcquireRelease => 3,
+            AtomicOrdering::SequentiallyConsistent => 4
+        }
+    }
+    /// Returns the C11 `memory_order` constant that libatomic takes for this ordering.
+    fn to_c_abi(self) -> u32 {
+        match self {
+            AtomicOrdering::Unordered | AtomicOrdering::Monotonic => 0,
+            AtomicOrdering::Acquire => 2,
+            AtomicOrdering::Release => 3,
+            AtomicOrdering::AcquireRelease => 4,
+            AtomicOrdering::SequentiallyConsistent => 5
+        }
+    }
+}
+impl From<AtomicOrdering> for LLVMAtomicOrdering {
+    fn from(ordering: AtomicOrdering) -> LLVMAtomicOrdering {
+        match ordering {
+            AtomicOrdering::Unordered => LLVMAtomicOrdering::LLVMAtomicOrderingUnordered,
+            AtomicOrdering::Monotonic => LLVMAtomicOrdering::LLVMAtomicOrderingMonotonic,
+            AtomicOrdering::Acquire => LLVMAtomicOrdering::LLVMAtomicOrderingAcquire,
+            AtomicOrdering::Release => LLVMAtomicOrdering::LLVMAtomicOrderingRelease,
+            AtomicOrdering::AcquireRelease => LLVMAtomicOrdering::LLVMAtomicOrderingAcquireRelease,
+            AtomicOrdering::SequentiallyConsistent => LLVMAtomicOrdering::LLVMAtomicOrderingSequentiallyConsistent
+        }
+    }
+}
+
+/// An operation that atomically reads, modifies and writes memory.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AtomicOp {
+    /// Replace the value.
+    Xchg,
+    /// Add to the value.
+    Add,
+    /// Subtract from the value.
+    Sub,
+    /// Bitwise and with the value.
+    And,
+    /// Bitwise and with the value, then invert the result.
+    Nand,
+    /// Bitwise or with the value.
+    Or,
+    /// Bitwise exclusive or with the value.
+    Xor,
+    /// Keep the larger of the values, treated as signed.
+    Max,
+    /// Keep the smaller of the values, treated as signed.
+    Min,
+    /// Keep the larger of the values, treated as unsigned.
+    UMax,
+    /// Keep the smaller of the values, treated as unsigned.
+    UMin
+}
+impl From<AtomicOp> for LLVMAtomicRMWBinOp {
+    fn from(op: AtomicOp) -> LLVMAtomicRMWBinOp {
+        match op {
+            AtomicOp::Xchg => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpXchg,
+            AtomicOp::Add => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpAdd,
+            AtomicOp::Sub => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpSub,
+            AtomicOp::And => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpAnd,
+            AtomicOp::Nand => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpNand,
+            AtomicOp::Or => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpOr,
+            AtomicOp::Xor => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpXor,
+            AtomicOp::Max => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpMax,
+            AtomicOp::Min => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpMin,
+            AtomicOp::UMax => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpUMax,
+            AtomicOp::UMin => LLVMAtomicRMWBinOp::LLVMAtomicRMWBinOpUMin
+        }
+    }
+}
+
+/// A wrapper around a builder that builds sequentially consistent atomic operations on integers,
+/// lowering the ones wider than the target supports to library calls or locks.
+///
+/// This is for targets without native atomics for every width, like small embedded targets,
+/// where LLVM would otherwise fail to select instructions or emit calls the platform lacks.
+/// This dereferences to the builder it wraps, so every other operation is still available.
+#[derive(Copy, Clone)]
+pub struct AtomicBuilder<'a> {
+    builder: &'a Builder,
+    options: AtomicOptions<'a>
+}
+impl<'a> AtomicBuilder<'a> {
+    /// Build instructions that atomically load the integer `ptr` points to.
+    ///
+    /// The C API of the LLVM this binds can't mark a load as atomic, so loads of native widths
+    /// call `__atomic_load_N` too, which is lock-free for them and doesn't write to `ptr`, so
+    /// libatomic or compiler-rt must be available even when wider operations use locks.
+    pub fn build_atomic_load(&self, ptr: &'a Value) -> &'a Value {
+        let ty = self.get_element(ptr);
+        if self.is_native(ty) {
+            return self.build_libcall("load", ptr, None)
+        }
+        match self.options.lowering {
+            AtomicLowering::Libcall => self.build_libcall("load", ptr, None),
+            AtomicLowering::Lock { lock, unlock } => {
+                self.build_locked(lock, ptr);
+                let value = self.builder.build_load(ptr);
+                self.build_locked(unlock, ptr);
+                value
+            }
+        }
+    }
+    /// Build instructions that atomically store the integer `value` to `ptr`.
+    pub fn build_atomic_store(&self, value: &'a Value, ptr: &'a Value) {
+        let ty = self.get_element(ptr);
+        if self.is_native(ty) {
+            self.build_native(AtomicOp::Xchg, ptr, value);
+            return
+        }
+        match self.options.lowering {
+            AtomicLowering::Libcall => {
+                self.build_libcall("store", ptr, Some(value));
+            },
+            AtomicLowering::Lock { lock, unlock } => {
+                self.build_locked(lock, ptr);
+                self.builder.build_store(value, ptr);
+                self.build_locked(unlock, ptr);
+            }
+        }
+    }
+    /// Build instructions that atomically apply `op` to the integer `ptr` points to and `value`,
+    /// store the result to `ptr` and return the old value.
+    pub fn build_atomic_rmw(&self, op: AtomicOp, ptr: &'a Value, value: &'a Value) -> &'a Value {
+        let ty = self.get_element(ptr);
+        if self.is_native(ty) {
+            return self.build_native(op, ptr, value)
+        }
+        match self.options.lowering {
+            AtomicLowering::Libcall => {
+                let name = match op {
+                    AtomicOp::Xchg => "exchange",
+                    AtomicOp::Add => "fetch_add",
+                    AtomicOp::Sub => "fetch_sub",
+                    AtomicOp::And => "fetch_and",
+                    AtomicOp::Nand => "fetch_nand",
+                    AtomicOp::Or => "fetch_or",
+                    AtomicOp::Xor => "fetch_xor",
+                    _ => return self.build_cas_loop(op, ptr, value)
+                };
+                self.build_libcall(name, ptr, Some(value))
+            },
+            AtomicLowering::Lock { lock, unlock } => {
+                self.build_locked(lock, ptr);
+                let old = self.builder.build_load(ptr);
+                self.builder.build_store(self.build_op(op, old, value), ptr);
+                self.build_locked(unlock, ptr);
+                old
+            }
+        }
+    }
+    /// Returns the type of the integer `ptr` points to.
+    fn get_element(&self, ptr: &'a Value) -> &'a Type {
+        match PointerType::from_super(ptr.get_type()) {
+            Some(ptr_ty) if ptr_ty.get_element().is_integer() => ptr_ty.get_element(),
+            _ => panic!("expected pointer to integer, got {:?}", ptr.get_type())
+        }
+    }
+    /// Returns true if the target supports atomic operations on `ty` natively.
+    fn is_native(&self, ty: &Type) -> bool {
+        unsafe { core::LLVMGetIntTypeWidth(ty.into()) as usize <= self.options.max_width }
+    }
+    fn build_native(&self, op: AtomicOp, ptr: &'a Value, value: &'a Value) -> &'a Value {
+        self.builder.build_atomic_rmw(op, ptr, value, AtomicOrdering::SequentiallyConsistent)
+    }
+    /// Build a call to `lock` or `unlock` with `ptr` as a byte pointer.
+    fn build_locked(&self, func: &'a Function, ptr: &'a Value) {
+        let byte_ptr = PointerType::new(Type::get::<u8>(ptr.get_context()));
+        self.builder.build_call(func, &[self.builder.build_bit_cast(ptr, byte_ptr)]);
+    }
+    /// Build a call to the libatomic function `__atomic_{name}_N`, where `N` is the size of the
+    /// integer `ptr` points to in bytes.
+    fn build_libcall(&self, name: &str, ptr: &'a Value, value: Option<&'a Value>) -> &'a Value {
+        let ty = self.get_element(ptr);
+        let ctx = ty.get_context();
+        let order = 5u32.compile(ctx);
+        let ret = if name == "store" { Type::get::<()>(ctx) } else { ty };
+        let func = match value {
+            Some(_) => self.builder.get_intrinsic(&libcall_name(name, ty), ret, &[ptr.get_type(), ty, Type::get::<u32>(ctx)]),
+            None => self.builder.get_intrinsic(&libcall_name(name, ty), ret, &[ptr.get_type(), Type::get::<u32>(ctx)])
+        };
+        match value {
+            Some(value) => self.builder.build_call(func, &[ptr, value, order]),
+            None => self.builder.build_call(func, &[ptr, order])
+        }
+    }
+    /// Build a loop that applies `op` with `__atomic_compare_exchange_N` until no other thread
+    /// changed the value in between, and return the old value.
+    fn build_cas_loop(&self, op: AtomicOp, ptr: &'a Value, value: &'a Value) -> &'a Value {
+        let builder = self.builder;
+        let ty = self.get_element(ptr);
+        let ctx = ty.get_context();
+        let function = builder.get_function();
+        let (retry, done) = (function.append("cas_retry"), function.append("cas_done"));
+        let expected = builder.build_entry_alloca(ty);
+        builder.build_store(self.build_libcall("load", ptr, None), expected);
+        builder.build_br(retry);
+        builder.position_at_end(retry);
+        let old = builder.build_load(expected);
+        let new = self.build_op(op, old, value);
+        let order = 5u32.compile(ctx);
+        let bool_ty = Type::get::<bool>(ctx);
+        let cas = builder.get_intrinsic(&libcall_name("compare_exchange", ty), bool_ty, &[ptr.get_type(), expected.get_type(), ty, Type::get::<u32>(ctx), Type::get::<u32>(ctx)]);
+        let exchanged = builder.build_call(cas, &[ptr, expected, new, order, order]);
+        builder.build_cond_br(exchanged, done, Some(retry));
+        builder.position_at_end(done);
+        old
+    }
+    /// Build the instructions that compute the new value of a read-modify-write operation.
+    fn build_op(&self, op: AtomicOp, old: &'a Value, value: &'a Value) -> &'a Value {
+        let builder = self.builder;
+        let select = |pred| {
+            builder.build_select(builder.build_icmp(old, value, pred), old, value)
+        };
+        match op {
+            AtomicOp::Xchg => value,
+            AtomicOp::Add => builder.build_add(old, value),
+            AtomicOp::Sub => builder.build_sub(old, value),
+            AtomicOp::And => builder.build_and(old, value),
+            AtomicOp::Nand => builder.build_not(builder.build_and(old, value)),
+            AtomicOp::Or => builder.build_or(old, value),
+            AtomicOp::Xor => builder.build_xor(old, value),
+            AtomicOp::Max => select(IntPredicate::SignedGreaterThan),
+            AtomicOp::Min => select(IntPredicate::SignedLessThan),
+            AtomicOp::UMax => select(IntPredicate::UnsignedGreaterThan),
+            AtomicOp::UMin => select(IntPredicate::UnsignedLessThan)
+        }
+    }
+}
+impl<'a> Deref for AtomicBuilder<'a> {
+    type Target = Builder;
+    fn deref(&self) -> &Builder {
+        self.builder
+    }
+}
+
+/// Returns the name of the libatomic function `__atomic_{name}_N` for operands of type `ty`.
+fn libcall_name(name: &str, ty: &Type) -> String {
+    if !ty.is_integer() {
+        panic!("no atomic library call for {:?}", ty)
+    }
+    match unsafe { core::LLVMGetIntTypeWidth(ty.into()) } {
+        width @ 8 | width @ 16 | width @ 32 | width @ 64 | width @ 128 => format!("__atomic_{}_{}", name, width / 8),
+        _ => panic!("no atomic library call for {:?}", ty)
+    }
+}
+
+/// Returns the element type of `ty` if it is a vector type, or `ty` itself otherwise.
+fn scalar_type(ty: &Type) -> &Type {
+    VectorType::from_super(ty).map(|ty| ty.get_element()).unwrap_or(ty)
+}
+
+/// Returns the width in bits of the floating-point type `ty`.
+fn float_width(ty: &Type) -> usize {
+    match ty.kind() {
+        TypeKind::Half => 16,
+        TypeKind::Float => 32,
+        TypeKind::Double => 64,
+        TypeKind::X86FP80 => 80,
+        _ => 128
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,139 @@
+use ffi::{core, LLVMOpcode};
+use std::cmp;
+use module::Module;
+use util::Sub;
+use value::{CallSite, Function, Value};
+
+/// The functions of a module and which of them call each other directly.
+///
+/// Calls through pointers aren't edges of the graph, so functions that make them are marked
+/// by `calls_unknown` instead.
+pub struct CallGraph<'a> {
+    functions: Vec<&'a Function>,
+    callees: Vec<Vec<usize>>,
+    unknown: Vec<bool>
+}
+impl<'a> CallGraph<'a> {
+    /// Make the call graph of the functions in `module` as they are now.
+    pub fn new(module: &'a Module) -> CallGraph<'a> {
+        let functions: Vec<&'a Function> = module.into_iter().collect();
+        let mut callees = vec![Vec::new(); functions.len()];
+        let mut unknown = vec![false; functions.len()];
+        for (index, &func) in functions.iter().enumerate() {
+            unsafe {
+                let mut block = core::LLVMGetFirstBasicBlock(func.into());
+                while !block.is_null() {
+                    let mut inst = core::LLVMGetFirstInstruction(block);
+                    while !inst.is_null() {
+                        match core::LLVMGetInstructionOpcode(inst) {
+                            LLVMOpcode::LLVMCall | LLVMOpcode::LLVMInvoke => {
+                                let call: &Value = inst.into();
+                                let callee = CallSite::from_super(call).unwrap().get_called_function();
+                                match callee.and_then(|callee| functions.iter().position(|&other| other == callee)) {
+                                    Some(callee) => if !callees[index].contains(&callee) {
+                                        callees[index].push(callee)
+                                    },
+                                    None => unknown[index] = true
+                                }
+                            },
+                            _ => ()
+                        }
+                        inst = core::LLVMGetNextInstruction(inst);
+                    }
+                    block = core::LLVMGetNextBasicBlock(block);
+                }
+            }
+        }
+        CallGraph {
+            functions: functions,
+            callees: callees,
+            unknown: unknown
+        }
+    }
+    /// Returns every function in the graph, in the order the module has them.
+    pub fn get_functions(&self) -> &[&'a Function] {
+        &self.functions
+    }
+    /// Returns the functions that `func` calls directly.
+    pub fn get_callees(&self, func: &Function) -> Vec<&'a Function> {
+        self.callees[self.index_of(func)].iter().map(|&callee| self.functions[callee]).collect()
+    }
+    /// Returns the functions that call `func` directly.
+    pub fn get_callers(&self, func: &Function) -> Vec<&'a Function> {
+        let index = self.index_of(func);
+        self.functions.iter().zip(&self.callees).filter(|&(_, callees)| callees.contains(&index)).map(|(&caller, _)| caller).collect()
+    }
+    /// Returns true if `func` calls a function through a pointer, so it may call any function.
+    pub fn calls_unknown(&self, func: &Function) -> bool {
+        self.unknown[self.index_of(func)]
+    }
+    /// Returns the strongly connected components of the graph, which are the groups of
+    /// functions that can call each other recursively, in bottom-up order.
+    ///
+    /// Every component comes after the components of all the functions it calls, except
+    /// through pointers, so an analysis can visit the callees of a function before it.
+    pub fn get_sccs(&self) -> Vec<Vec<&'a Function>> {
+        let mut search = SccSearch {
+            graph: self,
+            next_index: 0,
+            indices: vec![None; self.functions.len()],
+            low_links: vec![0; self.functions.len()],
+            stack: Vec::new(),
+            on_stack: vec![false; self.functions.len()],
+            sccs: Vec::new()
+        };
+        for func in 0..self.functions.len() {
+            if search.indices[func].is_none() {
+                search.visit(func);
+            }
+        }
+        search.sccs
+    }
+    fn index_of(&self, func: &Function) -> usize {
+        self.functions.iter().position(|&other| other == func).expect("function is not in the call graph")
+    }
+}
+
+/// The state of Tarjan's algorithm for finding strongly connected components, which finds
+/// them in reverse topological order.
+struct SccSearch<'a, 'b> where 'a: 'b {
+    graph: &'b CallGraph<'a>,
+    next_index: usize,
+    indices: Vec<Option<usize>>,
+    low_links: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    sccs: Vec<Vec<&'a Function>>
+}
+impl<'a, 'b> SccSearch<'a, 'b> {
+    fn visit(&mut self, func: usize) {
+        self.indices[func] = Some(self.next_index);
+        self.low_links[func] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(func);
+        self.on_stack[func] = true;
+        let graph = self.graph;
+        for &callee in &graph.callees[func] {
+            match self.indices[callee] {
+                None => {
+                    self.visit(callee);
+                    self.low_links[func] = cmp::min(self.low_links[func], self.low_links[callee]);
+                },
+                Some(index) if self.on_stack[callee] => self.low_links[func] = cmp::min(self.low_links[func], index),
+                Some(_) => ()
+            }
+        }
+        if Some(self.low_links[func]) == self.indices[func] {
+            let mut scc = Vec::new();
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack[member] = false;
+                scc.push(graph.functions[member]);
+                if member == func {
+                    break;
+                }
+            }
+            self.sccs.push(scc);
+        }
+    }
+}
@@ -154,10 +154,10 @@ macro_rules! compile_array(
         impl<'a, T> Compile<'a> for $ty where T: Copy + Compile<'a> + 'a {
             fn compile(self, context: &'a Context) -> &'a Value {
                 let values:Vec<_> = self.iter().map(|&value| value.compile(context)).collect();
-                unsafe { core::LLVMConstVector(values.as_ptr() as *mut LLVMValueRef, $num) }.into()
+                unsafe { core::LLVMConstArray(Type::get::<T>(context).into(), values.as_ptr() as *mut LLVMValueRef, $num) }.into()
             }
             fn get_type(context: &'a Context) -> &'a Type {
-                VectorType::new(Type::get::<T>(context), $num)
+                ArrayType::new(Type::get::<T>(context), $num)
             }
         }
     )
@@ -169,9 +169,44 @@ compile_array!{[T; 3], 3}
 compile_array!{[T; 4], 4}
 compile_array!{[T; 5], 5}
 compile_array!{[T; 6], 6}
+compile_array!{[T; 7], 7}
+compile_array!{[T; 8], 8}
+compile_array!{[T; 16], 16}
+compile_array!{[T; 32], 32}
 
+impl<'a, 'b, T> Compile<'a> for Option<&'b T> where T: Compile<'a> {
+    fn compile(self, context: &'a Context) -> &'a Value {
+        let ty = Self::get_type(context);
+        unsafe {
+            match self {
+                Some(reference) => {
+                    let address = (reference as *const T as usize).compile(context);
+                    core::LLVMConstIntToPtr(address.into(), ty.into())
+                },
+                None => core::LLVMConstNull(ty.into())
+            }
+        }.into()
+    }
+    fn get_type(context: &'a Context) -> &'a Type {
+        PointerType::new(T::get_type(context))
+    }
+}
+
+/// A function pointer type whose LLVM signature can be derived from its Rust type.
+///
+/// This is implemented for `extern "C"` function pointers whose arguments and return type
+/// implement `Compile`, so `extern fn(u32, f64) -> u8` maps to `i8 (i32, double)`.
+pub trait FnSignature<'a>: Copy {
+    /// Get the function signature for this function pointer type in the context given.
+    fn get_signature(context: &'a Context) -> &'a FunctionType;
+}
 macro_rules! compile_func(
     ($($name:ident),*) => (
+        impl<'a, R, $($name),*> FnSignature<'a> for extern fn($($name),*) -> R where R:Compile<'a>, $($name:Compile<'a>),* {
+            fn get_signature(context: &'a Context) -> &'a FunctionType {
+                FunctionType::new(R::get_type(context), &[$($name::get_type(context)),*])
+            }
+        }
         impl<'a, R, $($name),*> Compile<'a> for fn($($name),*) -> R where R:Compile<'a>, $($name:Compile<'a>),* {
             fn compile(self, context: &'a Context) -> &'a Value {
                 unsafe {
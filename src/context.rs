@@ -1,7 +1,10 @@
+use libc::{c_char, c_uint};
 use ffi::prelude::LLVMContextRef;
 use ffi::{core, LLVMContext};
+use std::cell::Cell;
 use std::marker::PhantomData;
 use cbox::CBox;
+use module::Module;
 
 /// Contains all the LLVM entities - mainly modules.
 ///
@@ -22,9 +25,93 @@ impl Context {
     pub fn new() -> CBox<Self> {
         CBox::new(unsafe { core::LLVMContextCreate() })
     }
+    /// Returns the ID of the metadata kind called `name`, which is registered in this context
+    /// if it hasn't been used before.
+    ///
+    /// LLVM already keeps the IDs in a hash map, so this is cheap to call every time metadata
+    /// is attached.
+    pub fn metadata_kind_id(&self, name: &str) -> u32 {
+        unsafe { core::LLVMGetMDKindIDInContext(self.into(), name.as_ptr() as *const c_char, name.len() as c_uint) as u32 }
+    }
+    /// Returns the ID of the builtin metadata kind given.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let context = Context::new();
+    /// assert_eq!(context.get_metadata_kind(MetadataKind::Range), context.metadata_kind_id("range"));
+    /// ```
+    pub fn get_metadata_kind(&self, kind: MetadataKind) -> u32 {
+        self.metadata_kind_id(kind.as_str())
+    }
 }
 dispose!(Context, LLVMContext, core::LLVMContextDispose);
 
+/// The kinds of metadata that LLVM understands, which every context has.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MetadataKind {
+    /// The debug location of an instruction.
+    Dbg,
+    /// Type-based alias analysis information.
+    Tbaa,
+    /// Profiling information, like branch weights.
+    Prof,
+    /// The accuracy a floating-point operation needs.
+    FpMath,
+    /// The range of values an integer load or call can produce.
+    Range,
+    /// Type-based alias analysis information for struct copies.
+    TbaaStruct,
+    /// Marks a load from memory that doesn't change.
+    InvariantLoad,
+    /// The alias scopes an access belongs to.
+    AliasScope,
+    /// The alias scopes an access doesn't alias.
+    NoAlias,
+    /// Marks an access that isn't expected to be reused soon, so it shouldn't be cached.
+    NonTemporal,
+    /// Marks accesses in a loop that don't depend on other iterations.
+    MemParallelLoopAccess,
+    /// Marks a pointer load that never produces null.
+    NonNull,
+    /// The number of bytes a loaded pointer can be dereferenced for.
+    Dereferenceable,
+    /// The number of bytes a loaded pointer can be dereferenced for if it isn't null.
+    DereferenceableOrNull,
+    /// Marks a null check that can be folded into a faulting memory access.
+    MakeImplicit,
+    /// Marks a branch or switch whose direction can't be predicted.
+    Unpredictable,
+    /// Marks accesses to memory that doesn't change within a group.
+    InvariantGroup,
+    /// The alignment of a loaded pointer.
+    Align
+}
+impl MetadataKind {
+    /// Returns the name of this kind in IR.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MetadataKind::Dbg => "dbg",
+            MetadataKind::Tbaa => "tbaa",
+            MetadataKind::Prof => "prof",
+            MetadataKind::FpMath => "fpmath",
+            MetadataKind::Range => "range",
+            MetadataKind::TbaaStruct => "tbaa.struct",
+            MetadataKind::InvariantLoad => "invariant.load",
+            MetadataKind::AliasScope => "alias.scope",
+            MetadataKind::NoAlias => "noalias",
+            MetadataKind::NonTemporal => "nontemporal",
+            MetadataKind::MemParallelLoopAccess => "llvm.mem.parallel_loop_access",
+            MetadataKind::NonNull => "nonnull",
+            MetadataKind::Dereferenceable => "dereferenceable",
+            MetadataKind::DereferenceableOrNull => "dereferenceable_or_null",
+            MetadataKind::MakeImplicit => "make.implicit",
+            MetadataKind::Unpredictable => "unpredictable",
+            MetadataKind::InvariantGroup => "invariant.group",
+            MetadataKind::Align => "align"
+        }
+    }
+}
+
 /// Implemented by everything that is owned by a context.
 pub trait GetContext {
     /// Returns a reference to the context that owns this value.
@@ -37,3 +124,53 @@ pub trait GetContext {
     /// ```
     fn get_context(&self) -> &Context;
 }
+
+/// Tracks how much code has been built in a context, so a long-running JIT server can tell
+/// when to recycle it.
+///
+/// LLVM keeps the types and constants of a context until the context is dropped, even after
+/// every module using them is gone, and there's no way to purge them. A server that builds code
+/// in the same context forever keeps growing, so it should instead drop the context and its
+/// engines once this says to, and start again with a new context.
+pub struct ContextUsage {
+    max_modules: usize,
+    max_bytes: usize,
+    modules: Cell<usize>,
+    bytes: Cell<usize>
+}
+impl ContextUsage {
+    /// Start tracking a fresh context that should be recycled after `max_modules` modules,
+    /// or after the modules' estimated sizes add up to `max_bytes`.
+    pub fn new(max_modules: usize, max_bytes: usize) -> ContextUsage {
+        ContextUsage {
+            max_modules: max_modules,
+            max_bytes: max_bytes,
+            modules: Cell::new(0),
+            bytes: Cell::new(0)
+        }
+    }
+    /// Record that `module` was built in the context.
+    ///
+    /// This should be done once the module is complete, since it estimates its size then.
+    pub fn record(&self, module: &Module) {
+        self.modules.set(self.modules.get() + 1);
+        self.bytes.set(self.bytes.get() + module.estimate_size());
+    }
+    /// Returns the number of modules recorded.
+    pub fn get_modules(&self) -> usize {
+        self.modules.get()
+    }
+    /// Returns the sum of the estimated sizes of the modules recorded, in bytes.
+    pub fn get_bytes(&self) -> usize {
+        self.bytes.get()
+    }
+    /// Returns true if the context has been used enough that it should be recycled.
+    pub fn should_recycle(&self) -> bool {
+        self.modules.get() >= self.max_modules || self.bytes.get() >= self.max_bytes
+    }
+    /// Start tracking again after the context has been recycled.
+    pub fn reset(&self) {
+        self.modules.set(0);
+        self.bytes.set(0);
+    }
+}
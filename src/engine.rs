@@ -1,14 +1,15 @@
-use libc::{c_int, c_uint, c_ulonglong};
-use ffi::{core, target};
+use libc::{c_int, c_uint, c_ulonglong, c_void};
+use ffi::core;
 use ffi::execution_engine as engine;
 use ffi::execution_engine::*;
 use ffi::target_machine::LLVMCodeModel;
 use cbox::{CBox, CSemiBox, DisposeRef};
 use std::marker::PhantomData;
 use std::{mem, ptr};
-use compile::Compile;
+use compile::{Compile, FnSignature};
 use context::{Context, GetContext};
 use module::Module;
+use target::{Target, TargetError};
 use types::{StructType, Type};
 use util::{self, Sub};
 use value::{Function, Value};
@@ -21,7 +22,7 @@ pub trait ExecutionEngine<'a>:'a + Sized + DisposeRef where LLVMExecutionEngineR
     type Options : Copy;
     /// Create a new execution engine with the given `Module` and optiions, or return a
     /// description of the error.
-    fn new(module: &'a Module, options: Self::Options) -> Result<CSemiBox<'a, Self>, CBox<str>>;
+    fn new(module: &'a Module, options: Self::Options) -> Result<CSemiBox<'a, Self>, TargetError>;
 
     /// Add a module to the list of modules to interpret or compile.
     fn add_module(&'a self, module: &'a Module) {
@@ -35,6 +36,13 @@ pub trait ExecutionEngine<'a>:'a + Sized + DisposeRef where LLVMExecutionEngineR
             out.into()
         }
     }
+    /// Map the global given to the host address given, so code referencing it uses `addr`.
+    ///
+    /// This is marked as unsafe because the data at `addr` cannot be guaranteed to match
+    /// the type of the global.
+    unsafe fn add_global_mapping(&'a self, global: &'a Value, addr: *const c_void) {
+        engine::LLVMAddGlobalMapping(self.into(), global.into(), addr as *mut c_void)
+    }
     /// Execute all of the static constructors for this program.
     fn run_static_constructors(&'a self) {
         unsafe { engine::LLVMRunStaticConstructors(self.into()) }
@@ -90,6 +98,14 @@ pub struct JitOptions {
     pub opt_level: usize
 }
 /// The MCJIT backend, which compiles functions and values into machine code.
+///
+/// Every object this engine emits is registered with the GDB JIT interface
+/// (`__jit_debug_register_code`), so GDB and LLDB can see JIT-compiled functions by name
+/// and break on them. Source-level stepping additionally needs the module to carry
+/// `!dbg` metadata, such as a module loaded with `Module::parse_bitcode` from a frontend
+/// that emits debug info, because the 3.8 C API can't build debug info metadata.
+/// `FrameTable::has_debug_info` tells whether a compiled function's line table reached the
+/// debugger.
 pub struct JitEngine(PhantomData<[u8]>);
 native_ref!{&JitEngine = LLVMExecutionEngineRef}
 dispose!{JitEngine, LLVMOpaqueExecutionEngine, LLVMDisposeExecutionEngine}
@@ -121,6 +137,18 @@ impl<'a> JitEngine {
     pub unsafe fn with_function_unchecked<C, A, R>(&self, function: &'a Function, cb: C) where A:Compile<'a>, R:Compile<'a>, C:FnOnce(extern fn(A) -> R) {
         cb(self.get_function::<A, R>(function));
     }
+    /// Returns the machine code for the function `function` as the function pointer type `F`,
+    /// or `None` if the signature of `F` doesn't match the signature of `function`.
+    pub fn get_typed_function<F>(&self, function: &'a Function) -> Option<F> where F:FnSignature<'a> {
+        let ctx = function.get_context();
+        if F::get_signature(ctx) != function.get_signature() || mem::size_of::<F>() != mem::size_of::<usize>() {
+            return None;
+        }
+        unsafe {
+            let ptr:&u8 = self.get_global(function);
+            Some(mem::transmute_copy(&ptr))
+        }
+    }
     /// Returns a pointer to the machine code for the function `function`.
     ///
     /// This is marked as unsafe because the types given as arguments and return could be different
@@ -132,17 +160,18 @@ impl<'a> JitEngine {
 }
 impl<'a> ExecutionEngine<'a> for JitEngine {
     type Options = JitOptions;
-    fn new(module: &'a Module, options: JitOptions) -> Result<CSemiBox<'a, JitEngine>, CBox<str>> {
+    fn new(module: &'a Module, options: JitOptions) -> Result<CSemiBox<'a, JitEngine>, TargetError> {
+        if let Err(message) = module.verify() {
+            return Err(TargetError::InvalidModule((*message).to_owned()))
+        }
+        let target = try!(Target::native());
+        if !target.has_jit() {
+            return Err(TargetError::JitNotSupportedOnPlatform(target.get_name().to_owned()))
+        }
         unsafe {
             let mut ee = mem::uninitialized();
             let mut out = mem::zeroed();
             engine::LLVMLinkInMCJIT();
-            if target::LLVM_InitializeNativeTarget() == 1 {
-                return Err("failed to initialize native target".into())
-            }
-            if target::LLVM_InitializeNativeAsmPrinter() == 1 {
-                return Err("failed to initialize native asm printer".into())
-            }
             let mut options = LLVMMCJITCompilerOptions {
                 OptLevel: options.opt_level as c_uint,
                 CodeModel: LLVMCodeModel::LLVMCodeModelDefault,
@@ -155,7 +184,7 @@ impl<'a> ExecutionEngine<'a> for JitEngine {
             if result == 0 {
                 Ok(ee.into())
             } else {
-                Err(CBox::new(out))
+                Err(TargetError::EngineCreation((*CBox::<str>::new(out)).to_owned()))
             }
         }
     }
@@ -166,7 +195,7 @@ native_ref!{&Interpreter = LLVMExecutionEngineRef}
 dispose!{Interpreter, LLVMOpaqueExecutionEngine, LLVMDisposeExecutionEngine}
 impl<'a> ExecutionEngine<'a> for Interpreter {
     type Options = ();
-    fn new(module: &'a Module, _: ()) -> Result<CSemiBox<'a, Interpreter>, CBox<str>> {
+    fn new(module: &'a Module, _: ()) -> Result<CSemiBox<'a, Interpreter>, TargetError> {
         unsafe {
             let mut ee = mem::uninitialized();
             let mut out = mem::zeroed();
@@ -175,7 +204,7 @@ impl<'a> ExecutionEngine<'a> for Interpreter {
             if result == 0 {
                 Ok(ee.into())
             } else {
-                Err(CBox::new(out))
+                Err(TargetError::EngineCreation((*CBox::<str>::new(out)).to_owned()))
             }
         }
     }
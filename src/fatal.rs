@@ -0,0 +1,95 @@
+use libc::c_char;
+use ffi::error_handling;
+use std::cell::RefCell;
+use std::error;
+use std::{fmt, ptr};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Once, ONCE_INIT};
+use std::thread;
+use util;
+
+static INSTALL: Once = ONCE_INIT;
+/// The first fatal error reported on a thread running `catch_fatal_errors`, which is leaked.
+static POISONED: AtomicPtr<FatalError> = AtomicPtr::new(ptr::null_mut());
+
+thread_local!(static FATAL_ERRORS: RefCell<Option<Sender<Result<(), FatalError>>>> = RefCell::new(None));
+
+/// A fatal error reported by LLVM, such as failing to select an instruction or to resolve
+/// a symbol, which would normally exit the process.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FatalError(pub String);
+impl fmt::Display for FatalError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "LLVM fatal error: {}", self.0)
+    }
+}
+impl error::Error for FatalError {
+    fn description(&self) -> &str {
+        "LLVM fatal error"
+    }
+}
+
+/// Run `func` on a new thread, returning the fatal error LLVM reported if it reported one
+/// instead of letting LLVM exit the process.
+///
+/// Unwinding through LLVM isn't possible, so when LLVM reports a fatal error on that thread,
+/// the thread is parked forever instead of returning to LLVM, which would exit the process.
+/// Everything `func` was working on, such as its context, modules and engine, is leaked with
+/// it, so `func` should create its own context and only send plain data back.
+///
+/// Any lock LLVM held when it failed, such as a global `ManagedStatic` lock or the lock of an
+/// engine, is never released, so any later call into LLVM may deadlock. Once a fatal error has
+/// been reported, LLVM is poisoned for the rest of the process: this returns that error again
+/// without running `func`, and the host should stop using LLVM, for example by saving its work
+/// and restarting. Work that must survive LLVM failing repeatedly belongs in a child process.
+/// Other panics in `func` are propagated as usual.
+pub fn catch_fatal_errors<F, R>(func: F) -> Result<R, FatalError> where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+    if let Some(error) = get_fatal_error() {
+        return Err(error)
+    }
+    INSTALL.call_once(|| unsafe {
+        error_handling::LLVMInstallFatalErrorHandler(Some(fatal_error_handler))
+    });
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        FATAL_ERRORS.with(|errors| *errors.borrow_mut() = Some(sender.clone()));
+        let result = func();
+        FATAL_ERRORS.with(|errors| *errors.borrow_mut() = None);
+        sender.send(Ok(())).ok();
+        result
+    });
+    match receiver.recv() {
+        Ok(Err(error)) => Err(error),
+        _ => match worker.join() {
+            Ok(result) => Ok(result),
+            Err(payload) => ::std::panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Returns the first fatal error LLVM reported while running `catch_fatal_errors`, after which
+/// LLVM shouldn't be called again in this process, or `None` if it hasn't reported one.
+pub fn get_fatal_error() -> Option<FatalError> {
+    let error = POISONED.load(Ordering::SeqCst);
+    if error.is_null() {
+        None
+    } else {
+        Some(unsafe { (*error).clone() })
+    }
+}
+
+extern "C" fn fatal_error_handler(reason: *const c_char) {
+    let reason = unsafe { util::to_str(reason as *mut c_char) }.to_owned();
+    let sender = FATAL_ERRORS.with(|errors| errors.borrow_mut().take());
+    if let Some(sender) = sender {
+        let error = Box::into_raw(Box::new(FatalError(reason.clone())));
+        if POISONED.compare_exchange(ptr::null_mut(), error, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            drop(unsafe { Box::from_raw(error) });
+        }
+        sender.send(Err(FatalError(reason))).ok();
+        loop {
+            thread::park();
+        }
+    }
+}
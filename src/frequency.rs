@@ -0,0 +1,196 @@
+use libc::c_uint;
+use ffi::core;
+use ffi::prelude::{LLVMBasicBlockRef, LLVMValueRef};
+use std::collections::HashMap;
+use std::{ptr, slice};
+use block::BasicBlock;
+use context::{GetContext, MetadataKind};
+use value::Function;
+
+/// The weight LLVM's branch probability heuristics give to an edge that stays in a loop.
+const LOOP_TAKEN_WEIGHT: f64 = 124.0;
+/// The weight LLVM's branch probability heuristics give to an edge that leaves a loop.
+const LOOP_EXIT_WEIGHT: f64 = 4.0;
+
+/// Static estimates of how often each block of a function runs, relative to its entry, and of
+/// how likely each edge between its blocks is to be taken.
+///
+/// The C API doesn't expose LLVM's block frequency analysis, so this estimates them the way
+/// it does. Branches with `branch_weights` metadata use it, loops with a trip count that
+/// `BasicBlock::get_trip_count` can find go around that many times, and otherwise an edge that
+/// stays in a loop is much more likely than one that leaves it. The frequencies are then found
+/// by solving the flow equations exactly, which takes time cubic in the number of blocks.
+pub struct BlockFrequencies<'a> {
+    blocks: Vec<&'a BasicBlock>,
+    frequencies: Vec<f64>,
+    probabilities: Vec<Vec<(usize, f64)>>
+}
+impl<'a> BlockFrequencies<'a> {
+    /// Estimate the block frequencies of `function`, which must have a body.
+    pub fn new(function: &'a Function) -> BlockFrequencies<'a> {
+        let mut blocks: Vec<&'a BasicBlock> = Vec::new();
+        unsafe {
+            let mut block = core::LLVMGetFirstBasicBlock(function.into());
+            while !block.is_null() {
+                blocks.push(block.into());
+                block = core::LLVMGetNextBasicBlock(block);
+            }
+        }
+        assert!(!blocks.is_empty(), "expected {} to have a body", function.get_name().unwrap_or("<unnamed>"));
+        let indices: HashMap<LLVMBasicBlockRef, usize> = blocks.iter().enumerate().map(|(index, &block)| (block.into(), index)).collect();
+        let successors: Vec<Vec<usize>> = blocks.iter().map(|block| {
+            block.get_successors().into_iter().map(|successor| {
+                let successor: LLVMBasicBlockRef = successor.into();
+                indices[&successor]
+            }).collect()
+        }).collect();
+        let loops = find_loops(&successors);
+        let prof = function.get_context().get_metadata_kind(MetadataKind::Prof);
+        let probabilities: Vec<Vec<(usize, f64)>> = blocks.iter().enumerate().map(|(index, block)| {
+            let exits: Vec<bool> = successors[index].iter().map(|&successor| {
+                loops.iter().any(|members| members[index] && !members[successor])
+            }).collect();
+            let weights = branch_weights(block, prof, successors[index].len()).unwrap_or_else(|| {
+                match (block.get_trip_count(), exits.iter().filter(|&&exit| exit).count()) {
+                    (Some(count), 1) if exits.len() == 2 => exits.iter().map(|&exit| if exit { 1.0 } else { count as f64 }).collect(),
+                    (_, num_exits) if num_exits > 0 && num_exits < exits.len() => {
+                        exits.iter().map(|&exit| if exit { LOOP_EXIT_WEIGHT } else { LOOP_TAKEN_WEIGHT }).collect()
+                    },
+                    _ => vec![1.0; exits.len()]
+                }
+            });
+            let total: f64 = weights.iter().sum();
+            successors[index].iter().zip(weights).map(|(&successor, weight)| {
+                (successor, if total > 0.0 { weight / total } else { 1.0 / exits.len() as f64 })
+            }).collect()
+        }).collect();
+        BlockFrequencies {
+            frequencies: solve(&probabilities),
+            blocks: blocks,
+            probabilities: probabilities
+        }
+    }
+    /// Returns how many times `block` is expected to run each time the function is called.
+    pub fn get_frequency(&self, block: &BasicBlock) -> f64 {
+        self.frequencies[self.index_of(block)]
+    }
+    /// Returns the probability that `to` runs next after `from`, which is zero if `from` can't
+    /// jump to it.
+    pub fn get_probability(&self, from: &BasicBlock, to: &BasicBlock) -> f64 {
+        let to = self.index_of(to);
+        self.probabilities[self.index_of(from)].iter().filter(|&&(successor, _)| successor == to).map(|&(_, probability)| probability).sum()
+    }
+    /// Returns every block with its frequency, from the most frequent to the least.
+    pub fn get_hottest(&self) -> Vec<(&'a BasicBlock, f64)> {
+        let mut blocks: Vec<(&'a BasicBlock, f64)> = self.blocks.iter().cloned().zip(self.frequencies.iter().cloned()).collect();
+        blocks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        blocks
+    }
+    fn index_of(&self, block: &BasicBlock) -> usize {
+        self.blocks.iter().position(|&other| other == block).expect("block is not in the function")
+    }
+}
+
+/// Returns the weights in the `branch_weights` metadata of the terminator of `block`, if it
+/// has one weight for each of its `num_successors` successors.
+fn branch_weights(block: &BasicBlock, prof: u32, num_successors: usize) -> Option<Vec<f64>> {
+    unsafe {
+        let terminator = core::LLVMGetBasicBlockTerminator(block.into());
+        let node = if terminator.is_null() { ptr::null_mut() } else { core::LLVMGetMetadata(terminator, prof as c_uint) };
+        if node.is_null() || core::LLVMGetMDNodeNumOperands(node) as usize != num_successors + 1 {
+            return None;
+        }
+        let mut operands: Vec<LLVMValueRef> = vec![ptr::null_mut(); num_successors + 1];
+        core::LLVMGetMDNodeOperands(node, operands.as_mut_ptr());
+        let mut len = 0;
+        let name = core::LLVMGetMDString(operands[0], &mut len);
+        if name.is_null() || slice::from_raw_parts(name as *const u8, len as usize) != b"branch_weights" {
+            return None;
+        }
+        if operands[1..].iter().any(|&weight| core::LLVMIsAConstantInt(weight).is_null()) {
+            return None;
+        }
+        Some(operands[1..].iter().map(|&weight| core::LLVMConstIntGetZExtValue(weight) as f64).collect())
+    }
+}
+
+/// Returns the blocks in each natural loop of the control flow graph with the edges given,
+/// where each loop is marked by the blocks that reach one of its back edges without leaving it.
+fn find_loops(successors: &[Vec<usize>]) -> Vec<Vec<bool>> {
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (block, targets) in successors.iter().enumerate() {
+        for &target in targets {
+            predecessors[target].push(block);
+        }
+    }
+    // a depth-first search finds the back edges, which go to a block still on its stack
+    let mut on_stack = vec![false; successors.len()];
+    let mut visited = vec![false; successors.len()];
+    let mut back_edges = Vec::new();
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    on_stack[0] = true;
+    while let Some((block, next)) = stack.pop() {
+        if next == successors[block].len() {
+            on_stack[block] = false;
+            continue;
+        }
+        stack.push((block, next + 1));
+        let target = successors[block][next];
+        if on_stack[target] {
+            back_edges.push((block, target));
+        } else if !visited[target] {
+            visited[target] = true;
+            on_stack[target] = true;
+            stack.push((target, 0));
+        }
+    }
+    let mut loops: HashMap<usize, Vec<bool>> = HashMap::new();
+    for (latch, header) in back_edges {
+        let members = loops.entry(header).or_insert_with(|| vec![false; successors.len()]);
+        members[header] = true;
+        let mut work = vec![latch];
+        while let Some(block) = work.pop() {
+            if !members[block] {
+                members[block] = true;
+                work.extend(predecessors[block].iter().cloned());
+            }
+        }
+    }
+    loops.into_iter().map(|(_, members)| members).collect()
+}
+
+/// Returns the frequency of each block given the probabilities of its outgoing edges, by
+/// solving the equations that say each block runs as often as its incoming edges are taken,
+/// plus once for the entry block.
+fn solve(probabilities: &[Vec<(usize, f64)>]) -> Vec<f64> {
+    let len = probabilities.len();
+    let mut matrix = vec![vec![0.0; len + 1]; len];
+    for (block, row) in matrix.iter_mut().enumerate() {
+        row[block] = 1.0;
+    }
+    for (block, edges) in probabilities.iter().enumerate() {
+        for &(successor, probability) in edges {
+            matrix[successor][block] -= probability;
+        }
+    }
+    matrix[0][len] = 1.0;
+    for column in 0..len {
+        let pivot = (column..len).max_by(|&a, &b| matrix[a][column].abs().partial_cmp(&matrix[b][column].abs()).unwrap()).unwrap();
+        matrix.swap(column, pivot);
+        // a loop that never exits runs forever, so give it a huge frequency instead
+        if matrix[column][column].abs() < 1e-12 {
+            matrix[column][column] = 1e-12;
+        }
+        let pivot_row = matrix[column].clone();
+        for (index, row) in matrix.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            if index != column && factor != 0.0 {
+                for (value, pivot_value) in row.iter_mut().zip(&pivot_row).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    (0..len).map(|block| matrix[block][len] / matrix[block][block]).collect()
+}
@@ -0,0 +1,103 @@
+use libc::c_uint;
+use ffi::{core, LLVMOpcode};
+use value::{Attribute, Function, Instruction, Linkage};
+
+/// The cost of an ordinary instruction.
+const INSTRUCTION_COST: i32 = 5;
+/// The extra cost of a call, for saving registers and setting up its arguments.
+const CALL_PENALTY: i32 = 25;
+/// The threshold for functions without any inlining attributes.
+const DEFAULT_THRESHOLD: i32 = 225;
+/// The threshold for functions marked `inlinehint`.
+const HINT_THRESHOLD: i32 = 325;
+/// The threshold when the caller or callee is optimized for size.
+const OPT_SIZE_THRESHOLD: i32 = 75;
+/// The bonus for inlining the only call to an internal function, which can then be removed.
+const LAST_CALL_TO_STATIC_BONUS: i32 = 15000;
+
+/// The estimated cost of inlining a function at a call site, and the threshold that cost
+/// should be below for it to be inlined.
+///
+/// The C API doesn't expose LLVM's inline cost analysis, so this is a simpler estimate using
+/// the same constants. Every instruction in the callee costs the same, except for casts and
+/// GEPs that are free and calls that cost more, and the call itself is subtracted since
+/// inlining removes it. The threshold depends on the `inlinehint` and `optsize` attributes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InlineCost {
+    /// The callee must be inlined, because it is marked `alwaysinline`.
+    Always,
+    /// The callee can't be inlined, because it is only declared, is marked `noinline`, calls
+    /// itself directly, or uses features that can't be inlined like varargs.
+    Never,
+    /// The callee can be inlined, and is worth inlining if `cost` is below `threshold`.
+    Variable {
+        /// The estimated cost of inlining the callee.
+        cost: i32,
+        /// The cost that inlining is allowed to have.
+        threshold: i32
+    }
+}
+impl InlineCost {
+    /// Estimate the cost of inlining `callee` at the call instruction `call`.
+    pub fn new(call: &Instruction, callee: &Function) -> InlineCost {
+        let caller = call.get_parent().and_then(|block| block.get_parent()).expect("call is not in a function");
+        if callee.is_declaration() || callee.has_attribute(Attribute::NoInline) || callee.has_attribute(Attribute::ReturnsTwice) ||
+            callee.get_signature().is_variadic() || caller == callee {
+            return InlineCost::Never;
+        }
+        if callee.has_attribute(Attribute::AlwaysInline) {
+            return InlineCost::Always;
+        }
+        let mut threshold = DEFAULT_THRESHOLD;
+        if callee.has_attribute(Attribute::InlineHint) {
+            threshold = HINT_THRESHOLD;
+        }
+        if callee.has_attribute(Attribute::OptimizeForSize) || caller.has_attribute(Attribute::OptimizeForSize) {
+            threshold = OPT_SIZE_THRESHOLD;
+        }
+        let num_args = callee.get_signature().num_params() as i32;
+        let mut cost = -INSTRUCTION_COST * (num_args + 1) - CALL_PENALTY;
+        unsafe {
+            let mut block = core::LLVMGetFirstBasicBlock(callee.into());
+            while !block.is_null() {
+                let mut inst = core::LLVMGetFirstInstruction(block);
+                while !inst.is_null() {
+                    cost += match core::LLVMGetInstructionOpcode(inst) {
+                        LLVMOpcode::LLVMIndirectBr => return InlineCost::Never,
+                        LLVMOpcode::LLVMBitCast | LLVMOpcode::LLVMPHI | LLVMOpcode::LLVMAlloca => 0,
+                        LLVMOpcode::LLVMGetElementPtr => {
+                            let constant = (1..core::LLVMGetNumOperands(inst) as c_uint).all(|index| {
+                                !core::LLVMIsAConstant(core::LLVMGetOperand(inst, index)).is_null()
+                            });
+                            if constant { 0 } else { INSTRUCTION_COST }
+                        },
+                        LLVMOpcode::LLVMCall | LLVMOpcode::LLVMInvoke => INSTRUCTION_COST + CALL_PENALTY,
+                        _ => INSTRUCTION_COST
+                    };
+                    inst = core::LLVMGetNextInstruction(inst);
+                }
+                block = core::LLVMGetNextBasicBlock(block);
+            }
+            let local = match callee.get_linkage() {
+                Linkage::Internal | Linkage::Private => true,
+                _ => false
+            };
+            let first_use = core::LLVMGetFirstUse(callee.into());
+            if local && !first_use.is_null() && core::LLVMGetNextUse(first_use).is_null() {
+                cost -= LAST_CALL_TO_STATIC_BONUS;
+            }
+        }
+        InlineCost::Variable {
+            cost: cost,
+            threshold: threshold
+        }
+    }
+    /// Returns true if the callee should be inlined at the call site.
+    pub fn should_inline(&self) -> bool {
+        match *self {
+            InlineCost::Always => true,
+            InlineCost::Never => false,
+            InlineCost::Variable { cost, threshold } => cost < threshold
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use libc::c_uint;
+use ffi::core;
+use ffi::prelude::LLVMValueRef;
+use block::BasicBlock;
+use builder::Builder;
+use compile::Compile;
+use context::GetContext;
+use module::Module;
+use types::{FunctionType, PointerType, Type};
+use value::{Function, Linkage, Value};
+
+/// The dispatch state of a threaded interpreter, which handlers use to read their
+/// operands and jump to the next handler.
+pub struct Dispatch<'a> {
+    pc: &'a Value,
+    table: &'a Value,
+    blocks: Vec<&'a BasicBlock>
+}
+impl<'a> Dispatch<'a> {
+    /// Returns the block of the handler for `opcode`.
+    pub fn get_handler(&self, opcode: usize) -> &'a BasicBlock {
+        self.blocks[opcode]
+    }
+    /// Returns the pointer to the variable holding the program counter, which points
+    /// to the next word of code to read.
+    pub fn get_pc(&self) -> &'a Value {
+        self.pc
+    }
+    /// Build instructions that read the next word of code, such as an operand, and advance
+    /// the program counter past it.
+    pub fn build_read(&self, builder: &'a Builder) -> &'a Value {
+        let ctx = self.pc.get_context();
+        let pc = builder.build_load(self.pc);
+        let word = builder.build_load(pc);
+        builder.build_store(builder.build_inbounds_gep(pc, &[1u32.compile(ctx)]), self.pc);
+        word
+    }
+    /// Build instructions that read the next opcode and jump straight to its handler.
+    ///
+    /// This should end every handler that doesn't exit, so each handler has its own
+    /// indirect branch, which is what makes the dispatch threaded.
+    pub fn build_next(&self, builder: &'a Builder) -> &'a Value {
+        let ctx = self.pc.get_context();
+        let opcode = self.build_read(builder);
+        let address = builder.build_load(builder.build_inbounds_gep(self.table, &[0u32.compile(ctx), opcode]));
+        builder.build_indirect_br(address, &self.blocks)
+    }
+    /// Build an instruction that returns from the interpreter.
+    pub fn build_exit(&self, builder: &'a Builder) -> &'a Value {
+        builder.build_ret_void()
+    }
+}
+
+/// Build a threaded interpreter function called `name`, which takes a pointer to an
+/// array of 32-bit code words and returns nothing.
+///
+/// The interpreter starts by dispatching on the first word of code. Each handler emits the
+/// code for the opcode at its index in `handlers`, then either calls `Dispatch::build_next` to
+/// jump to the handler of the next opcode or `Dispatch::build_exit` to return. Opcodes are not
+/// bounds-checked, so code must only contain opcodes that have a handler.
+pub fn build_threaded_interpreter<'a>(module: &'a Module, builder: &'a Builder, name: &str, handlers: &[(&str, &Fn(&'a Builder, &Dispatch<'a>))]) -> &'a Function {
+    let ctx = module.get_context();
+    let word = Type::get::<u32>(ctx);
+    let code_ty = PointerType::new(word);
+    let func: &'a Function = module.add_function(name, FunctionType::new(Type::get::<()>(ctx), &[code_ty]));
+    let entry = func.append("entry");
+    let blocks: Vec<&BasicBlock> = handlers.iter().map(|&(name, _)| func.append(name)).collect();
+    let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+    let table = unsafe {
+        let mut addresses:Vec<LLVMValueRef> = blocks.iter().map(|&block| block.get_address().into()).collect();
+        let table:&Value = core::LLVMConstArray(byte_ptr.into(), addresses.as_mut_ptr(), addresses.len() as c_uint).into();
+        let global = module.add_global_variable(&format!("{}.dispatch", name), table);
+        global.set_linkage(Linkage::Private);
+        global.set_constant(true);
+        global
+    };
+    builder.position_at_end(entry);
+    let pc = builder.build_alloca(code_ty);
+    builder.build_store(&func[0], pc);
+    let dispatch = Dispatch {
+        pc: pc,
+        table: table,
+        blocks: blocks
+    };
+    dispatch.build_next(builder);
+    for (index, &(_, build)) in handlers.iter().enumerate() {
+        builder.position_at_end(dispatch.get_handler(index));
+        build(builder, &dispatch);
+    }
+    func
+}
@@ -10,28 +10,57 @@ extern crate cbox;
 
 #[macro_use]
 mod macros;
+mod aggregate;
+mod alias;
+pub mod aot;
 mod buffer;
 mod block;
 mod builder;
+mod call_graph;
 mod compile;
 mod context;
 mod engine;
+mod fatal;
+mod frequency;
+mod inline;
+mod interp;
 mod module;
+mod multiversion;
 mod object;
+mod record;
+mod reduce;
+mod repl;
+mod source;
+mod split;
 mod target;
+pub mod testing;
 pub mod types;
 pub mod value;
+mod unwind;
 mod util;
 
 pub use cbox::{CBox, CSemiBox};
-pub use builder::Builder;
-pub use block::BasicBlock;
-pub use compile::Compile;
-pub use context::{Context, GetContext};
+pub use aggregate::{Slice, StructRef, TaggedUnion};
+pub use alias::{AliasAnalysis, AliasResult};
+pub use builder::{AtomicBuilder, AtomicLowering, AtomicOp, AtomicOptions, AtomicOrdering, Builder, CheckFailure, CheckedBuilder, Unterminated, UnterminatedBlock};
+pub use block::{BasicBlock, ParamBlock};
+pub use buffer::MemoryBuffer;
+pub use call_graph::CallGraph;
+pub use compile::{Compile, FnSignature};
+pub use context::{Context, ContextUsage, GetContext, MetadataKind};
 pub use engine::{JitEngine, JitOptions, Interpreter, ExecutionEngine, GenericValue, GenericValueCast};
-pub use module::{AddressSpace, Module, Functions};
+pub use fatal::{catch_fatal_errors, get_fatal_error, FatalError};
+pub use frequency::BlockFrequencies;
+pub use inline::InlineCost;
+pub use interp::{build_threaded_interpreter, Dispatch};
+pub use module::{AddressSpace, DuplicateName, ExportedSymbol, FunctionSpec, Functions, Globals, Module, NameConflict, SymbolKind, TypeMismatch};
+pub use multiversion::Multiversion;
 pub use object::{ObjectFile, Symbol, Symbols};
-pub use target::{TargetData, Target};
+pub use record::{Call, Recorder};
+pub use repl::{Interposition, ReplSession};
+pub use source::{SourceLocation, SourceMap};
+pub use target::{DenormalMode, FastMathFlags, FloatAbi, InvalidFeature, RelocMode, TargetData, TargetError, TargetFeatures, Target, TargetMachine, TargetPreset, THUMBV7EM_NONE_EABI};
 pub use types::*;
-pub use value::{Alias, Arg, Attribute, Value, Function, GlobalValue, GlobalVariable, Linkage, Predicate};
+pub use value::{Alias, Arg, Attribute, Attributes, CallingConv, CallSite, DLLStorageClass, Value, Function, GlobalValue, GlobalVariable, IndirectBr, InlineAsm, Instruction, LandingPad, Linkage, IntPredicate, PhiNode, RealPredicate, Switch, UnnamedAddr, Visibility};
+pub use unwind::{deregister_frame, register_frame, CodeSize, Frame, FrameTable};
 pub use util::Sub;
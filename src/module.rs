@@ -1,7 +1,8 @@
-use libc::{c_char, c_uint};
-use ffi::prelude::{LLVMValueRef, LLVMModuleRef};
+use libc::{c_char, c_uint, size_t};
+use ffi::prelude::{LLVMContextRef, LLVMValueRef, LLVMModuleRef, LLVMPassManagerRef};
 use ffi::analysis::LLVMVerifierFailureAction;
-use ffi::{analysis, core, linker, LLVMModule};
+use ffi::{analysis, core, ir_reader, linker, LLVMModule, LLVMTypeKind};
+use ffi::transforms::{ipo, scalar};
 use ffi::transforms::pass_manager_builder as builder;
 use ffi::bit_writer as writer;
 use ffi::bit_reader as reader;
@@ -10,15 +11,21 @@ use std::ffi::CString;
 use std::iter::{Iterator, IntoIterator};
 use std::io::{Error, ErrorKind};
 use std::io::Result as IoResult;
-use std::{env, fmt, mem};
+use std::{env, error, fmt, mem, ptr, slice};
 use std::marker::PhantomData;
 use std::path::Path;
 use std::process::Command;
+use alias::AliasAnalysis;
 use buffer::MemoryBuffer;
-use context::{Context, GetContext};
-use value::{Alias, Function, GlobalValue, GlobalVariable, Value};
-use types::Type;
-use util;
+use call_graph::CallGraph;
+use builder::{Builder, NULL_NAME};
+use compile::FnSignature;
+use context::{Context, GetContext, MetadataKind};
+use reduce;
+use split;
+use value::{Alias, Attribute, CallingConv, Function, GlobalValue, GlobalVariable, Linkage, Value, Visibility};
+use types::{ArrayType, FunctionType, PointerType, Type};
+use util::{self, Sub};
 
 /// Represents a single compilation unit of code.
 ///
@@ -48,6 +55,34 @@ impl Module {
             core::LLVMAddGlobal(self.into(), ty.into(), ptr).into()
         })
     }
+    /// Add a global to the module with the given type and name, resolving a clash with an
+    /// existing global or function of the same name according to `policy`.
+    ///
+    /// Unlike `add_global`, this never silently gives the new global a different name than the
+    /// one asked for, unless `policy` is `NameConflict::Rename`.
+    pub fn try_add_global<'a>(&'a self, name: &str, ty: &'a Type, policy: NameConflict) -> Result<&'a GlobalVariable, DuplicateName> {
+        if self.get_function(name).is_some() {
+            return match policy {
+                NameConflict::Rename => Ok(self.add_global(name, ty)),
+                _ => Err(DuplicateName(name.to_owned()))
+            }
+        }
+        let existing = match self.get_global(name) {
+            Some(existing) => existing,
+            None => return Ok(self.add_global(name, ty))
+        };
+        match policy {
+            NameConflict::Reuse => {
+                let existing_ty: &Type = unsafe { core::LLVMGetElementType(existing.get_type().into()) }.into();
+                match GlobalVariable::from_super(existing) {
+                    Some(existing) if existing_ty == ty => Ok(existing),
+                    _ => Err(DuplicateName(name.to_owned()))
+                }
+            },
+            NameConflict::Rename => Ok(self.add_global(name, ty)),
+            NameConflict::Fail => Err(DuplicateName(name.to_owned()))
+        }
+    }
     /// Add a global variable to the module with the given type, name and initial value.
     pub fn add_global_in_address_space<'a>(&'a self, name: &str, ty: &'a Type, address: AddressSpace) -> &'a GlobalVariable {
         util::with_cstr(name, |ptr| unsafe {
@@ -66,6 +101,159 @@ impl Module {
             core::LLVMAddAlias(self.into(), val.get_type().into(), val.into(), ptr).into()
         })
     }
+    /// Embed `bytes` in the module as a private constant global with the name given, which will
+    /// be emitted to `section` of the object file.
+    ///
+    /// The data is aligned to 16 bytes so it can hold any kind of bytecode or resource.
+    pub fn embed_binary_blob<'a>(&'a self, name: &str, bytes: &[u8], section: &str) -> &'a GlobalVariable {
+        let data: &Value = unsafe {
+            let ptr = bytes.as_ptr() as *const c_char;
+            core::LLVMConstStringInContext(self.get_context().into(), ptr, bytes.len() as c_uint, 1).into()
+        };
+        let global = self.add_global_variable(name, data);
+        global.set_linkage(Linkage::Private);
+        global.set_constant(true);
+        global.set_section(section);
+        global.set_alignment(16);
+        global
+    }
+    /// Returns the bytes of the binary blob with the name given, or `None` if no global with that
+    /// name exists or it doesn't hold a constant byte array.
+    pub fn get_binary_blob<'a>(&'a self, name: &str) -> Option<&'a [u8]> {
+        let global = match self.get_global(name).and_then(GlobalVariable::from_super) {
+            Some(global) => global,
+            None => return None
+        };
+        global.get_initializer().and_then(|data| unsafe {
+            if core::LLVMIsAConstantDataSequential(data.into()).is_null() || core::LLVMIsConstantString(data.into()) == 0 {
+                return None;
+            }
+            let mut len: size_t = 0;
+            let ptr = core::LLVMGetAsString(data.into(), &mut len);
+            Some(slice::from_raw_parts(ptr as *const u8, len as usize))
+        })
+    }
+    /// Embed this module's own bitcode in it, like Clang's `-fembed-bitcode`, so the object files
+    /// it is compiled into carry a bitcode section for platforms and tools that need one.
+    ///
+    /// The bitcode is stored in `llvm.embedded.module` and `cmdline` in `llvm.cmdline`, which are
+    /// emitted to the `__LLVM` segment on Darwin and the `.llvmbc` and `.llvmcmd` sections elsewhere.
+    pub fn embed_bitcode(&self, cmdline: &str) {
+        let is_darwin = {
+            let target = self.get_target();
+            target.contains("apple") || target.contains("darwin")
+        };
+        let (bitcode_section, cmdline_section) = if is_darwin {
+            ("__LLVM,__bitcode", "__LLVM,__cmdline")
+        } else {
+            (".llvmbc", ".llvmcmd")
+        };
+        let buf: CBox<MemoryBuffer> = CBox::new(unsafe { writer::LLVMWriteBitcodeToMemoryBuffer(self.into()) });
+        let bitcode = self.embed_binary_blob("llvm.embedded.module", buf.as_bytes(), bitcode_section);
+        let cmdline = self.embed_binary_blob("llvm.cmdline", cmdline.as_bytes(), cmdline_section);
+        bitcode.set_alignment(1);
+        cmdline.set_alignment(1);
+        let ctx = self.get_context();
+        let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+        let used: Vec<&Value> = [bitcode, cmdline].iter().map(|&global| unsafe {
+            core::LLVMConstBitCast(global.into(), byte_ptr.into())
+        }.into()).collect();
+        self.append_to_global_array("llvm.compiler.used", byte_ptr, &used);
+    }
+    /// Record the tool that produced this module and its version in the `llvm.ident` metadata,
+    /// which is emitted to the `.comment` section of ELF object files.
+    pub fn set_producer(&self, producer: &str, version: &str) {
+        let ident = format!("{} version {}", producer, version);
+        unsafe {
+            let ctx: LLVMContextRef = self.get_context().into();
+            let mut string = core::LLVMMDStringInContext(ctx, ident.as_ptr() as *const c_char, ident.len() as c_uint);
+            let node = core::LLVMMDNodeInContext(ctx, &mut string, 1);
+            util::with_cstr("llvm.ident", |name| core::LLVMAddNamedMetadataOperand(self.into(), name, node));
+        }
+    }
+    /// Add an ELF note with the owner `owner`, the note type `kind` and the contents `desc` to
+    /// the section `section`, which should start with `.note` so it is emitted as a note section.
+    ///
+    /// This can be used to stamp object files with provenance information, such as the version
+    /// of the compiler that produced them, that tools like `readelf -n` can read back.
+    pub fn add_note<'a>(&'a self, section: &str, owner: &str, kind: u32, desc: &[u8]) -> &'a GlobalVariable {
+        fn pad(bytes: &mut Vec<u8>) {
+            while bytes.len() % 4 != 0 {
+                bytes.push(0);
+            }
+        }
+        let big_endian = self.get_data_layout().starts_with("E");
+        let mut note = Vec::new();
+        let name_size = owner.len() as u32 + 1;
+        for &word in &[name_size, desc.len() as u32, kind] {
+            let word = if big_endian { word.to_be() } else { word.to_le() };
+            let bytes: [u8; 4] = unsafe { mem::transmute(word) };
+            note.extend(bytes.iter().cloned());
+        }
+        note.extend(owner.bytes());
+        note.push(0);
+        pad(&mut note);
+        note.extend(desc.iter().cloned());
+        pad(&mut note);
+        let global = self.embed_binary_blob(section, &note, section);
+        global.set_alignment(4);
+        let byte_ptr = PointerType::new(Type::get::<u8>(self.get_context()));
+        let used: &Value = unsafe { core::LLVMConstBitCast(global.into(), byte_ptr.into()) }.into();
+        self.append_to_global_array("llvm.used", byte_ptr, &[used]);
+        global
+    }
+    /// Append `values`, which must have the type `elem_ty`, to the appending-linkage array global
+    /// called `name`, like `llvm.global_ctors` or a custom registry, creating it if it doesn't exist.
+    ///
+    /// Since the type of the array changes with its length, this replaces the global with a new
+    /// one and updates every use of the old one, so it can be called any number of times.
+    pub fn append_to_global_array<'a>(&'a self, name: &str, elem_ty: &'a Type, values: &[&'a Value]) -> &'a GlobalVariable {
+        let old = self.get_global(name).map(|old| GlobalVariable::from_super(old).expect("expected a global variable"));
+        let mut elems: Vec<LLVMValueRef> = Vec::new();
+        if let Some(old) = old {
+            unsafe {
+                let init = core::LLVMGetInitializer(old.into());
+                let array_ty: &Type = core::LLVMGetElementType(old.get_type().into()).into();
+                let array_ty = match ArrayType::from_super(array_ty) {
+                    Some(array_ty) if array_ty.get_element() == elem_ty => array_ty,
+                    _ => panic!("expected {} to be an array of {}, got {:?}", name, elem_ty, old)
+                };
+                let len = array_ty.get_length() as c_uint;
+                if init.is_null() || !core::LLVMIsAConstantAggregateZero(init).is_null() {
+                    elems.extend((0..len).map(|_| core::LLVMConstNull(elem_ty.into())));
+                } else if !core::LLVMIsAUndefValue(init).is_null() {
+                    elems.extend((0..len).map(|_| core::LLVMGetUndef(elem_ty.into())));
+                } else if !core::LLVMIsAConstantDataSequential(init).is_null() {
+                    elems.extend((0..len).map(|index| core::LLVMGetElementAsConstant(init, index)));
+                } else {
+                    elems.extend((0..len).map(|index| core::LLVMGetOperand(init, index)));
+                }
+            }
+            old.set_name("");
+        }
+        for value in values {
+            if value.get_type() != elem_ty {
+                panic!("expected {} for element of {}, got {:?}", elem_ty, name, value)
+            }
+            elems.push(value.into());
+        }
+        let array: &Value = unsafe { core::LLVMConstArray(elem_ty.into(), elems.as_mut_ptr(), elems.len() as c_uint) }.into();
+        let global = self.add_global_variable(name, array);
+        global.set_linkage(Linkage::Appending);
+        if name == "llvm.used" || name == "llvm.compiler.used" {
+            global.set_section("llvm.metadata");
+        }
+        if let Some(old) = old {
+            if let Some(section) = old.get_section() {
+                global.set_section(section);
+            }
+            unsafe {
+                core::LLVMReplaceAllUsesWith(old.into(), core::LLVMConstBitCast(global.into(), old.get_type().into()));
+                core::LLVMDeleteGlobal(old.into());
+            }
+        }
+        global
+    }
     /// Get the global with the name given, or `None` if no global with that name exists.
     pub fn get_global<'a>(&'a self, name: &str) -> Option<&'a GlobalValue> {
         util::with_cstr(name, |ptr| unsafe {
@@ -86,6 +274,20 @@ impl Module {
             }
         }
     }
+    /// Parse the textual IR `ir` into a module, or return an error string.
+    pub fn parse_ir<'a>(context: &'a Context, ir: &str) -> Result<CSemiBox<'a, Module>, CBox<str>> {
+        unsafe {
+            let mut out = mem::uninitialized();
+            let mut err = mem::uninitialized();
+            // the parser takes ownership of the buffer
+            let buf = core::LLVMCreateMemoryBufferWithMemoryRangeCopy(ir.as_ptr() as *const c_char, ir.len() as size_t, NULL_NAME.as_ptr());
+            if ir_reader::LLVMParseIRInContext(context.into(), buf, &mut out, &mut err) == 1 {
+                Err(CBox::new(err))
+            } else {
+                Ok(CSemiBox::new(out))
+            }
+        }
+    }
     /// Write this module's bitcode to the path given.
     pub fn write_bitcode(&self, path: &str) -> IoResult<()> {
         util::with_cstr(path, |cpath| unsafe {
@@ -96,11 +298,220 @@ impl Module {
             }
         })
     }
+    /// Write this module's bitcode to a new memory buffer.
+    pub fn to_bitcode(&self) -> CBox<MemoryBuffer> {
+        unsafe { CBox::new(writer::LLVMWriteBitcodeToMemoryBuffer(self.into())) }
+    }
+    /// Returns the number of instructions in the functions of this module.
+    pub fn num_instructions(&self) -> usize {
+        let mut count = 0;
+        self.for_each_instruction(&mut |_| count += 1);
+        count
+    }
+    /// Remove the `!dbg` location from every instruction in this module, and the calls to the
+    /// `llvm.dbg` intrinsics that describe variables.
+    ///
+    /// This only drops the line table and the variable locations. The C API has no way to erase
+    /// named metadata or module flags, so `llvm.dbg.cu` and the "Debug Info Version" flag are
+    /// kept, and the compile unit with the subprograms and types it lists is still emitted.
+    pub fn strip_debug_info(&self) {
+        let dbg = self.get_context().get_metadata_kind(MetadataKind::Dbg);
+        let mut intrinsic_calls = Vec::new();
+        self.for_each_instruction(&mut |inst| unsafe {
+            if !core::LLVMIsACallInst(inst).is_null() {
+                let callee: &Value = core::LLVMGetOperand(inst, core::LLVMGetNumOperands(inst) as c_uint - 1).into();
+                if callee.get_name().map(|name| name.starts_with("llvm.dbg.")).unwrap_or(false) {
+                    intrinsic_calls.push(inst);
+                    return
+                }
+            }
+            core::LLVMSetMetadata(inst, dbg as c_uint, ptr::null_mut());
+        });
+        for inst in intrinsic_calls {
+            unsafe { core::LLVMInstructionEraseFromParent(inst) }
+        }
+    }
+    /// Remove the names of every argument, basic block and instruction in this module, which
+    /// the engine never needs.
+    ///
+    /// Global names are kept, since they are how symbols are linked and looked up.
+    pub fn strip_value_names(&self) {
+        self.for_each_instruction(&mut |inst| unsafe { core::LLVMSetValueName(inst, NULL_NAME.as_ptr()) });
+        for func in self {
+            for index in 0..func.get_signature().num_params() {
+                func[index].set_name("");
+            }
+            unsafe {
+                let mut block = core::LLVMGetFirstBasicBlock(func.into());
+                while !block.is_null() {
+                    core::LLVMSetValueName(core::LLVMBasicBlockAsValue(block), NULL_NAME.as_ptr());
+                    block = core::LLVMGetNextBasicBlock(block);
+                }
+            }
+        }
+    }
+    /// Call `f` with every instruction in the functions of this module.
+    fn for_each_instruction(&self, f: &mut FnMut(LLVMValueRef)) {
+        unsafe {
+            let mut func = core::LLVMGetFirstFunction(self.into());
+            while !func.is_null() {
+                let mut block = core::LLVMGetFirstBasicBlock(func);
+                while !block.is_null() {
+                    let mut inst = core::LLVMGetFirstInstruction(block);
+                    while !inst.is_null() {
+                        f(inst);
+                        inst = core::LLVMGetNextInstruction(inst);
+                    }
+                    block = core::LLVMGetNextBasicBlock(block);
+                }
+                func = core::LLVMGetNextFunction(func);
+            }
+        }
+    }
+    /// Estimate how many bytes of memory this module uses, from the size of its bitcode.
+    ///
+    /// The in-memory representation is several times bigger than the bitcode, so this is only
+    /// useful for comparing modules and tracking growth over time.
+    pub fn estimate_size(&self) -> usize {
+        self.to_bitcode().as_bytes().len()
+    }
     /// Add a function to the module with the name given.
     pub fn add_function<'a>(&'a self, name: &str, sig: &'a Type) -> &'a mut Function {
         let c_name = CString::new(name).unwrap();
         unsafe { core::LLVMAddFunction(self.into(), c_name.as_ptr(), sig.into()) }.into()
     }
+    /// Declare a function with the name given and the signature of the function pointer type `F`.
+    ///
+    /// This is useful for declaring host functions that JIT-compiled code calls into.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let context = Context::new();
+    /// let module = Module::new("host", &context);
+    /// let func = module.declare_function::<extern fn(u32) -> f64>("host_func");
+    /// assert_eq!(func.get_signature().num_params(), 1);
+    /// ```
+    pub fn declare_function<'a, F>(&'a self, name: &str) -> &'a mut Function where F: FnSignature<'a> {
+        self.add_function(name, F::get_signature(self.get_context()))
+    }
+    /// Returns the function with the name given, or declares it with the signature `sig` if
+    /// nothing with that name exists yet.
+    ///
+    /// This returns an error if a function with that name exists with a different signature,
+    /// or if the name is taken by a global variable, so repeated declarations from different
+    /// parts of a frontend always agree.
+    pub fn get_or_insert_function<'a>(&'a self, name: &str, sig: &'a Type) -> Result<&'a Function, TypeMismatch<'a>> {
+        if let Some(func) = self.get_function(name) {
+            let found: &Type = func.get_signature();
+            return if found == sig {
+                Ok(func)
+            } else {
+                Err(TypeMismatch {
+                    name: name.to_owned(),
+                    expected: sig,
+                    found: found
+                })
+            }
+        }
+        if let Some(global) = self.get_global(name) {
+            return Err(TypeMismatch {
+                name: name.to_owned(),
+                expected: sig,
+                found: unsafe { core::LLVMGetElementType(global.get_type().into()) }.into()
+            })
+        }
+        Ok(self.add_function(name, sig))
+    }
+    /// Declare the function `spec` describes, with its attributes and calling convention, or
+    /// give them to the function with that name if it has already been declared.
+    ///
+    /// This returns an error if the name is taken by something with a different type, like
+    /// `get_or_insert_function`.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let context = Context::new();
+    /// let module = Module::new("libc", &context);
+    /// let (ptr, size) = (PointerType::new(Type::get::<i8>(&context)), Type::get::<u64>(&context));
+    /// let memcpy = module.declare(&FunctionSpec {
+    ///     name: "memcpy",
+    ///     ret: ptr,
+    ///     params: &[(ptr, &[Attribute::NoAlias, Attribute::NoCapture]),
+    ///               (ptr, &[Attribute::NoAlias, Attribute::NoCapture, Attribute::ReadOnly]),
+    ///               (size, &[])],
+    ///     variadic: false,
+    ///     call_conv: CallingConv::C,
+    ///     attributes: &[Attribute::NoUnwind]
+    /// }).unwrap();
+    /// assert!(memcpy[1].has_attributes(&[Attribute::NoAlias, Attribute::ReadOnly]));
+    /// ```
+    pub fn declare<'a>(&'a self, spec: &FunctionSpec<'a>) -> Result<&'a Function, TypeMismatch<'a>> {
+        let params: Vec<&Type> = spec.params.iter().map(|&(ty, _)| ty).collect();
+        let sig = if spec.variadic {
+            FunctionType::new_variadic(spec.ret, &params)
+        } else {
+            FunctionType::new(spec.ret, &params)
+        };
+        let func = try!(self.get_or_insert_function(spec.name, sig));
+        for (index, &(_, attrs)) in spec.params.iter().enumerate() {
+            if !attrs.is_empty() {
+                func[index].add_attributes(attrs);
+            }
+        }
+        if !spec.attributes.is_empty() {
+            func.add_attributes(spec.attributes);
+        }
+        func.set_call_conv(spec.call_conv);
+        Ok(func)
+    }
+    /// Returns the intrinsic with the name given, such as `llvm.x86.avx2.pmadd.wd`, declaring it
+    /// with the signature `sig` if it hasn't been declared yet.
+    ///
+    /// This returns an error string if LLVM doesn't know an intrinsic with that name, if `sig`
+    /// doesn't match the signature LLVM defines for it, or if it has already been declared with a
+    /// different signature. Overloaded intrinsics need their name to be suffixed with their
+    /// overloaded types, like `llvm.ctpop.i32`.
+    pub fn get_intrinsic<'a>(&'a self, name: &str, sig: &'a Type) -> Result<&'a Function, CBox<str>> {
+        if FunctionType::from_super(sig).is_none() {
+            return Err(CBox::from(&*format!("expected a function type for {}, got {:?}", name, sig)))
+        }
+        let existed = self.get_function(name).is_some();
+        let func = match self.get_or_insert_function(name, sig) {
+            Ok(func) => func,
+            Err(err) => return Err(CBox::from(&*err.to_string()))
+        };
+        if let Err(err) = check_intrinsic(self.get_context(), name, sig) {
+            if !existed {
+                unsafe { core::LLVMDeleteFunction(func.into()) }
+            }
+            return Err(err)
+        }
+        Ok(func)
+    }
+    /// Returns the overloaded intrinsic with the name given, such as `llvm.sqrt`, for the types
+    /// `overloads`, declaring it with the signature `sig` if it hasn't been declared yet.
+    ///
+    /// This suffixes the name with the types the way LLVM does, so `llvm.memcpy` overloaded on
+    /// `i8*`, `i8*` and `i64` is `llvm.memcpy.p0i8.p0i8.i64`. The LLVM 3.8 C API can't look up
+    /// the signature of an intrinsic, so it still has to be given, and is checked like in
+    /// `get_intrinsic`.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let context = Context::new();
+    /// let module = Module::new("math", &context);
+    /// let double = Type::get::<f64>(&context);
+    /// let sqrt = module.get_overloaded_intrinsic("llvm.sqrt", &[double], Type::get::<fn(f64) -> f64>(&context)).unwrap();
+    /// assert_eq!(sqrt.get_name(), Some("llvm.sqrt.f64"));
+    /// ```
+    pub fn get_overloaded_intrinsic<'a>(&'a self, name: &str, overloads: &[&Type], sig: &'a Type) -> Result<&'a Function, CBox<str>> {
+        let mut mangled = name.to_owned();
+        for &ty in overloads {
+            mangled.push('.');
+            mangled.push_str(&intrinsic_suffix(ty));
+        }
+        self.get_intrinsic(&mangled, sig)
+    }
     /// Returns the function with the name given, or `None` if no function with that name exists.
     pub fn get_function<'a>(&'a self, name: &str) -> Option<&'a Function> {
         let c_name = CString::new(name).unwrap();
@@ -136,6 +547,123 @@ impl Module {
             core::LLVMRunPassManager(pass_manager, self.into());
         }
     }
+    /// Returns the graph of which functions in this module call each other.
+    pub fn call_graph(&self) -> CallGraph {
+        CallGraph::new(self)
+    }
+    /// Returns an alias analysis for the pointers in this module.
+    ///
+    /// This uses the data layout of the module as it is now, so it should be set first.
+    pub fn alias_analysis(&self) -> AliasAnalysis {
+        AliasAnalysis::new(self)
+    }
+    /// Shrink a copy of this module as far as possible while `interesting` stays true for it,
+    /// to make a small reproducer for a bug in code that generated or compiled it.
+    ///
+    /// Like `llvm-reduce`, this repeatedly tries removing functions, function bodies, global
+    /// variables, branches, blocks and instructions, replacing their uses with undef, and keeps
+    /// each change that leaves a valid module `interesting` is still true for. `interesting`
+    /// should be true for this module, and is called on a new copy for every change tried.
+    pub fn reduce<'a>(&'a self, interesting: &Fn(&Module) -> bool) -> CSemiBox<'a, Module> {
+        reduce::reduce(self, interesting)
+    }
+    /// Split a copy of this module into `count` modules that each define some of its functions
+    /// and global variables and declare the rest, so they can be compiled on separate threads
+    /// and the object files linked together.
+    ///
+    /// The definitions are spread so the modules have about as many instructions each. Local
+    /// functions and variables become hidden, so the other modules can refer to them without
+    /// them being exported from the program. An alias can't refer to a declaration, so the
+    /// aliases and what they refer to become `weak_odr` and are defined in every module. Global
+    /// constructors and destructors are only kept in the first module.
+    pub fn split<'a>(&'a self, count: usize) -> Vec<CSemiBox<'a, Module>> {
+        split::split(self, count)
+    }
+    /// Make every function and global variable this module defines internal, except those
+    /// named in `exported`, then remove the ones that are no longer used.
+    ///
+    /// This shrinks a module before it is compiled, such as one linked with a big runtime
+    /// library that it only uses a few functions of. Intrinsics and appending arrays like
+    /// `llvm.global_ctors` are kept as they are.
+    pub fn internalize_and_prune(&self, exported: &[&str]) {
+        for global in self.named_symbols() {
+            let keep = exported.contains(&global.get_name().unwrap());
+            if !keep && !global.is_declaration() {
+                match global.get_linkage() {
+                    Linkage::Private | Linkage::Internal | Linkage::Appending => (),
+                    _ => {
+                        global.set_linkage(Linkage::Internal);
+                        global.set_visibility(Visibility::Default);
+                    }
+                }
+            }
+        }
+        self.run_passes(&|pass_manager| unsafe { ipo::LLVMAddGlobalDCEPass(pass_manager) });
+    }
+    /// Inline every call to a function marked `alwaysinline`, without running any other passes.
+    ///
+    /// This is much faster than `optimize`, for JIT users that only want small helpers stitched
+    /// into their callers.
+    pub fn run_always_inliner(&self) {
+        self.run_passes(&|pass_manager| unsafe { ipo::LLVMAddAlwaysInlinerPass(pass_manager) });
+    }
+    /// Optimize this module like `optimize`, calling `snapshot` with the name of each phase of
+    /// the pipeline and this module after it has run, to find the phase that miscompiles it.
+    ///
+    /// Like Clang, this first runs the early simplification passes on each function, in the
+    /// phase named `function`, then the rest of the pipeline, in the phase named `module`.
+    /// The IR can be printed with `{:?}` or checked with `verify` in `snapshot`.
+    pub fn optimize_with_snapshots(&self, opt_level: usize, size_level: usize, snapshot: &mut FnMut(&str, &Module)) {
+        unsafe {
+            let builder = builder::LLVMPassManagerBuilderCreate();
+            builder::LLVMPassManagerBuilderSetOptLevel(builder, opt_level as c_uint);
+            builder::LLVMPassManagerBuilderSetSizeLevel(builder, size_level as c_uint);
+            let function_passes = core::LLVMCreateFunctionPassManagerForModule(self.into());
+            builder::LLVMPassManagerBuilderPopulateFunctionPassManager(builder, function_passes);
+            core::LLVMInitializeFunctionPassManager(function_passes);
+            for function in self {
+                core::LLVMRunFunctionPassManager(function_passes, function.into());
+            }
+            core::LLVMFinalizeFunctionPassManager(function_passes);
+            core::LLVMDisposePassManager(function_passes);
+            snapshot("function", self);
+            let module_passes = core::LLVMCreatePassManager();
+            builder::LLVMPassManagerBuilderPopulateModulePassManager(builder, module_passes);
+            builder::LLVMPassManagerBuilderDispose(builder);
+            core::LLVMRunPassManager(module_passes, self.into());
+            core::LLVMDisposePassManager(module_passes);
+            snapshot("module", self);
+        }
+    }
+    /// Run the passes named in `passes` on this module one at a time, in the order given,
+    /// calling `snapshot` with the name of each pass and this module after it has run, like
+    /// `opt -print-after-all`.
+    ///
+    /// The names are the ones `opt` uses, like `mem2reg`, `instcombine` and `gvn`. This returns
+    /// an error without running anything if one of them isn't a pass the C API can add.
+    pub fn run_passes_with_snapshots(&self, passes: &[&str], snapshot: &mut FnMut(&str, &Module)) -> Result<(), CBox<str>> {
+        let mut adds = Vec::with_capacity(passes.len());
+        for &name in passes {
+            match named_pass(name) {
+                Some(add) => adds.push(add),
+                None => return Err(CBox::from(&*format!("unknown pass {}", name)))
+            }
+        }
+        for (&name, add) in passes.iter().zip(adds) {
+            self.run_passes(&|pass_manager| unsafe { add(pass_manager) });
+            snapshot(name, self);
+        }
+        Ok(())
+    }
+    /// Run the passes that `add` adds to a new pass manager on this module.
+    fn run_passes(&self, add: &Fn(LLVMPassManagerRef)) {
+        unsafe {
+            let pass_manager = core::LLVMCreatePassManager();
+            add(pass_manager);
+            core::LLVMRunPassManager(pass_manager, self.into());
+            core::LLVMDisposePassManager(pass_manager);
+        }
+    }
 
     /// Returns the target data of this module represented as a string
     pub fn get_target(&self) -> &str {
@@ -151,6 +679,20 @@ impl Module {
         unsafe { core::LLVMSetTarget(self.into(), c_target.as_ptr()) }
     }
 
+    /// Returns the data layout string of this module.
+    pub fn get_data_layout(&self) -> &str {
+        unsafe {
+            let layout = core::LLVMGetDataLayout(self.into());
+            util::to_str(layout as *mut c_char)
+        }
+    }
+
+    /// Set the data layout of this module to the data layout string given.
+    pub fn set_data_layout(&self, layout: &str) {
+        let c_layout = CString::new(layout).unwrap();
+        unsafe { core::LLVMSetDataLayout(self.into(), c_layout.as_ptr()) }
+    }
+
     /// Verify that the module is safe to run, returning a string detailing the error
     /// when an error occurs.
     pub fn verify(&self) -> Result<(), CBox<str>> {
@@ -215,6 +757,134 @@ impl Module {
             }
         }
     }
+
+    /// Iterate through the global variables in this module.
+    pub fn globals<'a>(&'a self) -> Globals<'a> {
+        Globals {
+            value: unsafe { core::LLVMGetFirstGlobal(self.into()) },
+            marker: PhantomData
+        }
+    }
+
+    /// Returns the global variables in this module that are emitted to the section `name`,
+    /// like every record a registration macro put in a custom section.
+    pub fn globals_in_section<'a>(&'a self, name: &str) -> Vec<&'a GlobalVariable> {
+        self.globals().filter(|global| global.get_section() == Some(name)).collect()
+    }
+    /// Returns the global variables in this module with the linkage given.
+    pub fn globals_with_linkage<'a>(&'a self, linkage: Linkage) -> Vec<&'a GlobalVariable> {
+        self.globals().filter(|global| global.get_linkage() == linkage).collect()
+    }
+
+    /// Rename every named function and global variable in this module to the name `rename`
+    /// returns for its current name, skipping intrinsics.
+    ///
+    /// Instructions refer to globals directly rather than by name, so every use follows the
+    /// rename. Declarations are renamed too, so `rename` should return the name unchanged for
+    /// symbols that are resolved outside the module. If the new name is already taken, LLVM
+    /// adds a number to the end of it.
+    pub fn rename_symbols(&self, rename: &Fn(&str) -> String) {
+        for global in self.named_symbols() {
+            let new_name = rename(global.get_name().unwrap());
+            if Some(&*new_name) != global.get_name() {
+                global.set_name(&new_name);
+            }
+        }
+    }
+    /// Add `prefix` to the names of the functions and global variables that are only visible
+    /// inside this module, so several modules can be linked into one without their private
+    /// symbols clashing.
+    pub fn add_prefix_to_internal_symbols(&self, prefix: &str) {
+        for global in self.named_symbols() {
+            match global.get_linkage() {
+                Linkage::Internal | Linkage::Private => global.set_name(&format!("{}{}", prefix, global.get_name().unwrap())),
+                _ => ()
+            }
+        }
+    }
+    /// Returns the functions and global variables in this module with names, except intrinsics.
+    fn named_symbols<'a>(&'a self) -> Vec<&'a GlobalValue> {
+        let functions = self.into_iter().map(|func: &'a Function| &**func);
+        let globals = self.globals().map(|global| &**global);
+        functions.chain(globals).filter(|global| match global.get_name() {
+            Some(name) => !name.is_empty() && !name.starts_with("llvm."),
+            None => false
+        }).collect()
+    }
+
+    /// Returns a list of every symbol this module defines that other modules can link to,
+    /// so host-side bindings can be generated for them.
+    ///
+    /// The `llvm.` globals and arrays with appending linkage, such as `llvm.used` and
+    /// `llvm.global_ctors`, are for LLVM itself, so they aren't listed.
+    pub fn export_manifest<'a>(&'a self) -> Vec<ExportedSymbol<'a>> {
+        let functions = self.into_iter().map(|func| (&**func, SymbolKind::Function, &**func.get_signature()));
+        let globals = self.globals().map(|global| {
+            let ty: &Type = unsafe { core::LLVMGetElementType(core::LLVMTypeOf(global.into())) }.into();
+            (&**global, SymbolKind::Variable, ty)
+        });
+        functions.chain(globals).filter_map(|(global, kind, ty)| {
+            let linkage = global.get_linkage();
+            match linkage {
+                Linkage::Internal | Linkage::Private | Linkage::Appending => return None,
+                _ if global.is_declaration() => return None,
+                _ => ()
+            }
+            global.get_name().and_then(|name| if name.is_empty() || name.starts_with("llvm.") {
+                None
+            } else {
+                Some(name)
+            }).map(|name| ExportedSymbol {
+                name: name,
+                kind: kind,
+                ty: ty,
+                linkage: linkage,
+                visibility: global.get_visibility()
+            })
+        }).collect()
+    }
+
+    /// Generate a C header declaring every function and global variable this module exports,
+    /// wrapped in the include guard `guard`.
+    ///
+    /// LLVM integers are mapped to signed fixed-width C integers, since LLVM doesn't track
+    /// signedness. Symbols whose types have no C equivalent are listed in comments instead.
+    /// Symbols whose names aren't C identifiers, such as `a.b`, can't be declared in C, so
+    /// they are left out, and struct names are mangled into C identifiers.
+    pub fn to_c_header(&self, guard: &str) -> String {
+        let mut structs = Vec::new();
+        let mut decls = Vec::new();
+        for symbol in self.export_manifest() {
+            if !is_c_identifier(symbol.name) {
+                continue;
+            }
+            let decl = match symbol.kind {
+                SymbolKind::Function => c_function(symbol.name, symbol.ty, &mut structs),
+                SymbolKind::Variable => {
+                    let is_constant = GlobalVariable::from_super(self.get_global(symbol.name).unwrap()).map(|global| global.get_constant()).unwrap_or(false);
+                    c_declarator(symbol.ty, symbol.name, &mut structs).map(|decl|
+                        format!("extern {}{};", if is_constant { "const " } else { "" }, decl)
+                    )
+                }
+            };
+            decls.push(decl.unwrap_or_else(|| format!("/* {}: unsupported type {:?} */", symbol.name, symbol.ty)));
+        }
+        let mut header = format!("#ifndef {0}\n#define {0}\n\n#include <stdbool.h>\n#include <stdint.h>\n\n", guard);
+        header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+        for name in &structs {
+            header.push_str(&format!("struct {};\n", name));
+        }
+        if !structs.is_empty() {
+            header.push('\n');
+        }
+        for decl in &decls {
+            header.push_str(decl);
+            header.push('\n');
+        }
+        header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        header.push_str(&format!("#endif /* {} */\n", guard));
+        header
+    }
 }
 impl<'a> IntoIterator for &'a Module {
     type Item = &'a Function;
@@ -242,13 +912,147 @@ impl<'a> Iterator for Functions<'a> {
         if self.value.is_null() {
             None
         } else {
-            let c_next = unsafe { core::LLVMGetNextFunction(self.value) };
-            self.value = c_next;
-            Some(self.value.into())
+            let current = self.value;
+            self.value = unsafe { core::LLVMGetNextFunction(current) };
+            Some(current.into())
+        }
+    }
+}
+#[derive(Copy, Clone)]
+/// An iterator through the global variables contained in a module.
+pub struct Globals<'a> {
+    value: LLVMValueRef,
+    marker: PhantomData<&'a ()>
+}
+impl<'a> Iterator for Globals<'a> {
+    type Item = &'a GlobalVariable;
+    fn next(&mut self) -> Option<&'a GlobalVariable> {
+        if self.value.is_null() {
+            None
+        } else {
+            let current = self.value;
+            self.value = unsafe { core::LLVMGetNextGlobal(current) };
+            Some(current.into())
         }
     }
 }
 
+/// The kind of entity a symbol refers to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SymbolKind {
+    /// The symbol is a function.
+    Function,
+    /// The symbol is a global variable.
+    Variable
+}
+
+/// A symbol that a module exports, as listed by `Module::export_manifest`.
+#[derive(Copy, Clone, Debug)]
+pub struct ExportedSymbol<'a> {
+    /// The name of the symbol.
+    pub name: &'a str,
+    /// Whether the symbol is a function or a global variable.
+    pub kind: SymbolKind,
+    /// The signature of the function, or the type of the global variable's value.
+    pub ty: &'a Type,
+    /// The linkage of the symbol.
+    pub linkage: Linkage,
+    /// The visibility of the symbol.
+    pub visibility: Visibility
+}
+
+/// What `Module::try_add_global` does when the name given is already taken.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NameConflict {
+    /// Return the existing global if it is a global variable of the same type, or fail otherwise.
+    Reuse,
+    /// Add the new global under a fresh name made by adding a numeric suffix, like `name.1`.
+    Rename,
+    /// Fail without adding anything.
+    Fail
+}
+
+/// An error returned when a symbol can't be added to a module because its name is taken.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateName(pub String);
+impl fmt::Display for DuplicateName {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "a symbol named {} already exists", self.0)
+    }
+}
+impl error::Error for DuplicateName {
+    fn description(&self) -> &str {
+        "duplicate symbol name"
+    }
+}
+
+/// Returns the function that adds the pass `opt` calls `name` to a pass manager, or `None` if
+/// the C API can't add it.
+fn named_pass(name: &str) -> Option<unsafe extern "C" fn(LLVMPassManagerRef)> {
+    let add: unsafe extern "C" fn(LLVMPassManagerRef) = match name {
+        "adce" => scalar::LLVMAddAggressiveDCEPass,
+        "always-inline" => ipo::LLVMAddAlwaysInlinerPass,
+        "constmerge" => ipo::LLVMAddConstantMergePass,
+        "deadargelim" => ipo::LLVMAddDeadArgEliminationPass,
+        "dse" => scalar::LLVMAddDeadStoreEliminationPass,
+        "early-cse" => scalar::LLVMAddEarlyCSEPass,
+        "globaldce" => ipo::LLVMAddGlobalDCEPass,
+        "globalopt" => ipo::LLVMAddGlobalOptimizerPass,
+        "gvn" => scalar::LLVMAddGVNPass,
+        "inline" => ipo::LLVMAddFunctionInliningPass,
+        "instcombine" => scalar::LLVMAddInstructionCombiningPass,
+        "ipsccp" => ipo::LLVMAddIPSCCPPass,
+        "licm" => scalar::LLVMAddLICMPass,
+        "loop-unroll" => scalar::LLVMAddLoopUnrollPass,
+        "mem2reg" => scalar::LLVMAddPromoteMemoryToRegisterPass,
+        "reassociate" => scalar::LLVMAddReassociatePass,
+        "sccp" => scalar::LLVMAddSCCPPass,
+        "simplifycfg" => scalar::LLVMAddCFGSimplificationPass,
+        "sroa" => scalar::LLVMAddScalarReplAggregatesPass,
+        "tailcallelim" => scalar::LLVMAddTailCallEliminationPass,
+        _ => return None
+    };
+    Some(add)
+}
+
+/// A description of a function to declare with `Module::declare`.
+#[derive(Copy, Clone)]
+pub struct FunctionSpec<'a> {
+    /// The name of the function.
+    pub name: &'a str,
+    /// The type the function returns.
+    pub ret: &'a Type,
+    /// The type of each parameter, with the attributes it has.
+    pub params: &'a [(&'a Type, &'a [Attribute])],
+    /// Whether the function takes extra arguments after its parameters.
+    pub variadic: bool,
+    /// The calling convention the function uses.
+    pub call_conv: CallingConv,
+    /// The attributes of the function itself.
+    pub attributes: &'a [Attribute]
+}
+
+/// An error returned when a symbol already exists in a module with a different type than expected.
+#[derive(Clone, Debug)]
+pub struct TypeMismatch<'a> {
+    /// The name of the symbol.
+    pub name: String,
+    /// The type that was asked for.
+    pub expected: &'a Type,
+    /// The type the existing symbol has.
+    pub found: &'a Type
+}
+impl<'a> fmt::Display for TypeMismatch<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "expected {} to have type {:?}, but it has type {:?}", self.name, self.expected, self.found)
+    }
+}
+impl<'a> error::Error for TypeMismatch<'a> {
+    fn description(&self) -> &str {
+        "symbol type mismatch"
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
 pub enum AddressSpace {
@@ -258,3 +1062,162 @@ pub enum AddressSpace {
     Const = 4,
     Local = 5,
 }
+
+/// Check that `name` is an intrinsic with the signature `sig` by verifying a call to it with
+/// null arguments in a scratch module.
+fn check_intrinsic(context: &Context, name: &str, sig: &Type) -> Result<(), CBox<str>> {
+    let module = Module::new("intrinsic", context);
+    let func = module.add_function(name, sig);
+    if unsafe { core::LLVMGetIntrinsicID((&*func).into()) } == 0 {
+        return Err(CBox::from(&*format!("{} is not an intrinsic", name)))
+    }
+    let check = module.add_function("check", FunctionType::new(Type::get::<()>(context), &[]));
+    let builder = Builder::new(context);
+    builder.position_at_end(check.append("entry"));
+    let args: Vec<&Value> = func.get_signature().get_params().into_iter().map(|param| {
+        let arg: &Value = unsafe { core::LLVMConstNull(param.into()) }.into();
+        arg
+    }).collect();
+    builder.build_call(func, &args);
+    builder.build_ret_void();
+    module.verify()
+}
+
+/// Returns the suffix LLVM gives the name of an intrinsic overloaded on `ty`, like `f64` or `p0i8`.
+fn intrinsic_suffix(ty: &Type) -> String {
+    unsafe {
+        match core::LLVMGetTypeKind(ty.into()) {
+            LLVMTypeKind::LLVMPointerTypeKind => {
+                let elem: &Type = core::LLVMGetElementType(ty.into()).into();
+                format!("p{}{}", core::LLVMGetPointerAddressSpace(ty.into()), intrinsic_suffix(elem))
+            },
+            LLVMTypeKind::LLVMArrayTypeKind => {
+                let elem: &Type = core::LLVMGetElementType(ty.into()).into();
+                format!("a{}{}", core::LLVMGetArrayLength(ty.into()), intrinsic_suffix(elem))
+            },
+            LLVMTypeKind::LLVMVectorTypeKind => {
+                let elem: &Type = core::LLVMGetElementType(ty.into()).into();
+                format!("v{}{}", core::LLVMGetVectorSize(ty.into()), intrinsic_suffix(elem))
+            },
+            // LLVM 3.8 can't mangle literal structs, so they get no suffix and fail the check
+            LLVMTypeKind::LLVMStructTypeKind => util::to_null_str(core::LLVMGetStructName(ty.into()) as *mut c_char).unwrap_or("").to_owned(),
+            LLVMTypeKind::LLVMFunctionTypeKind => {
+                let sig = FunctionType::from_super(ty).unwrap();
+                let mut suffix = format!("f_{}", intrinsic_suffix(sig.get_return()));
+                for param in sig.get_params() {
+                    suffix.push_str(&intrinsic_suffix(param));
+                }
+                if sig.is_variadic() {
+                    suffix.push_str("vararg");
+                }
+                suffix.push('f');
+                suffix
+            },
+            LLVMTypeKind::LLVMIntegerTypeKind => format!("i{}", core::LLVMGetIntTypeWidth(ty.into())),
+            LLVMTypeKind::LLVMHalfTypeKind => "f16".to_owned(),
+            LLVMTypeKind::LLVMFloatTypeKind => "f32".to_owned(),
+            LLVMTypeKind::LLVMDoubleTypeKind => "f64".to_owned(),
+            LLVMTypeKind::LLVMX86_FP80TypeKind => "f80".to_owned(),
+            LLVMTypeKind::LLVMFP128TypeKind => "f128".to_owned(),
+            LLVMTypeKind::LLVMPPC_FP128TypeKind => "ppcf128".to_owned(),
+            LLVMTypeKind::LLVMX86_MMXTypeKind => "x86mmx".to_owned(),
+            LLVMTypeKind::LLVMMetadataTypeKind => "Metadata".to_owned(),
+            LLVMTypeKind::LLVMVoidTypeKind => "isVoid".to_owned(),
+            _ => format!("{:?}", ty)
+        }
+    }
+}
+
+/// Returns the C declaration of the function `name` with the signature `ty`.
+fn c_function(name: &str, ty: &Type, structs: &mut Vec<String>) -> Option<String> {
+    let sig = match FunctionType::from_super(ty) {
+        Some(sig) => sig,
+        None => return None
+    };
+    let mut params = Vec::new();
+    for param in sig.get_params() {
+        match c_declarator(param, "", structs) {
+            Some(param) => params.push(param),
+            None => return None
+        }
+    }
+    if unsafe { core::LLVMIsFunctionVarArg(sig.into()) } != 0 {
+        params.push("...".to_owned());
+    } else if params.is_empty() {
+        params.push("void".to_owned());
+    }
+    let ret = match c_declarator(sig.get_return(), "", structs) {
+        Some(ret) => ret,
+        None => return None
+    };
+    Some(format!("{} {}({});", ret, name, params.join(", ")))
+}
+
+const C_KEYWORDS: &'static [&'static str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double",
+    "else", "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while"
+];
+
+/// Returns true if `name` can be used as an identifier in C.
+fn is_c_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !C_KEYWORDS.contains(&name)
+}
+
+/// Returns `name` with every character that can't be in a C identifier replaced by `_`.
+fn c_identifier(name: &str) -> String {
+    let mut ident: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if !is_c_identifier(&ident) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// Returns the C declaration of `name` with the type `ty`, or just the C type if `name` is empty.
+fn c_declarator(ty: &Type, name: &str, structs: &mut Vec<String>) -> Option<String> {
+    let sep = if name.is_empty() { "" } else { " " };
+    let base = unsafe {
+        match core::LLVMGetTypeKind(ty.into()) {
+            LLVMTypeKind::LLVMVoidTypeKind => "void".to_owned(),
+            LLVMTypeKind::LLVMFloatTypeKind => "float".to_owned(),
+            LLVMTypeKind::LLVMDoubleTypeKind => "double".to_owned(),
+            LLVMTypeKind::LLVMIntegerTypeKind => match core::LLVMGetIntTypeWidth(ty.into()) {
+                1 => "bool".to_owned(),
+                width @ 8 | width @ 16 | width @ 32 | width @ 64 => format!("int{}_t", width),
+                _ => return None
+            },
+            LLVMTypeKind::LLVMPointerTypeKind => {
+                let elem: &Type = core::LLVMGetElementType(ty.into()).into();
+                let inner = if FunctionType::is(elem) {
+                    "void".to_owned()
+                } else {
+                    c_declarator(elem, "", structs).unwrap_or("void".to_owned())
+                };
+                return Some(format!("{}*{}{}", inner, sep, name));
+            },
+            LLVMTypeKind::LLVMStructTypeKind => {
+                let struct_name = match util::to_null_str(core::LLVMGetStructName(ty.into()) as *mut c_char) {
+                    Some(struct_name) => c_identifier(struct_name),
+                    None => return None
+                };
+                if !structs.contains(&struct_name) {
+                    structs.push(struct_name.clone());
+                }
+                format!("struct {}", struct_name)
+            },
+            LLVMTypeKind::LLVMArrayTypeKind if !name.is_empty() => {
+                let elem: &Type = core::LLVMGetElementType(ty.into()).into();
+                let len = core::LLVMGetArrayLength(ty.into());
+                return c_declarator(elem, &format!("{}[{}]", name, len), structs);
+            },
+            _ => return None
+        }
+    };
+    Some(format!("{}{}{}", base, sep, name))
+}
@@ -0,0 +1,99 @@
+use libc::c_void;
+use ffi::execution_engine::{self as engine, LLVMExecutionEngineRef};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use builder::Builder;
+use context::GetContext;
+use engine::ExecutionEngine;
+use module::Module;
+use target::TargetFeatures;
+use types::{FunctionType, Type};
+use util::Sub;
+use value::{Function, GlobalVariable, Instruction, Value, Visibility};
+
+/// A function with several versions compiled for different sets of target features, such as
+/// a baseline version and one that uses AVX2, which calls the best version the host supports.
+///
+/// The function dispatches through a pointer to the selected version, which starts out as the
+/// first version and is changed by `select` once the module has been compiled.
+pub struct Multiversion<'a> {
+    dispatcher: &'a Function,
+    pointer: &'a GlobalVariable,
+    versions: Vec<(TargetFeatures, &'a Function)>
+}
+impl<'a> Multiversion<'a> {
+    /// Add the function `name` with the signature `sig` to `module`, with a version for each
+    /// of the sets of target features in `versions`, like `TargetFeatures::parse("+avx2,+fma")`.
+    ///
+    /// The closure paired with each set of features builds the body of that version, which is
+    /// a function with the signature `sig` that is compiled with those features enabled. Versions
+    /// should be ordered from the most portable to the most specialized, and the first is used
+    /// until another is selected, so it should use no features.
+    pub fn new(module: &'a Module, name: &str, sig: &'a Type, versions: &[(&TargetFeatures, &Fn(&'a Function))]) -> Multiversion<'a> {
+        assert!(!versions.is_empty(), "expected at least one version of {}", name);
+        let sig = FunctionType::from_super(sig).expect("expected a function type");
+        let versions: Vec<(TargetFeatures, &'a Function)> = versions.iter().enumerate().map(|(index, &(features, build))| {
+            let version: &'a Function = module.add_function(&format!("{}.v{}", name, index), sig);
+            if *features != TargetFeatures::new() {
+                version.set_target_features(features);
+            }
+            build(version);
+            (features.clone(), version)
+        }).collect();
+        let pointer = module.add_global_variable(&format!("{}.dispatch", name), versions[0].1);
+        // this can't be private because the engine only finds external symbols
+        pointer.set_visibility(Visibility::Hidden);
+        let dispatcher: &'a Function = module.add_function(name, sig);
+        let builder = Builder::new(module.get_context());
+        builder.position_at_end(dispatcher.append("entry"));
+        // `select` may change the pointer while other threads call this, and the 3.8 C API
+        // can't make loads atomic, so this is volatile to load it once per call
+        let target = builder.build_load(pointer);
+        Instruction::from_super(target).unwrap().set_volatile(true);
+        let args: Vec<&Value> = (0..sig.num_params()).map(|index| &*dispatcher[index]).collect();
+        let result = builder.build_tail_call(target, &args);
+        if sig.get_return().is_void() {
+            builder.build_ret_void();
+        } else {
+            builder.build_ret(result);
+        }
+        Multiversion {
+            dispatcher: dispatcher,
+            pointer: pointer,
+            versions: versions
+        }
+    }
+    /// Returns the function that calls the selected version.
+    pub fn get_dispatcher(&self) -> &'a Function {
+        self.dispatcher
+    }
+    /// Returns the version at `index`.
+    pub fn get_version(&self, index: usize) -> &'a Function {
+        self.versions[index].1
+    }
+    /// Returns the target features of the version at `index`.
+    pub fn get_features(&self, index: usize) -> &TargetFeatures {
+        &self.versions[index].0
+    }
+    /// Returns the number of versions.
+    pub fn num_versions(&self) -> usize {
+        self.versions.len()
+    }
+    /// Make the dispatcher call the last version whose features are all supported by the host,
+    /// and return the index of that version.
+    ///
+    /// `supported` is called with the name of each feature a version enables, like `"avx2"`,
+    /// and should return true if the host CPU supports it. Features a version disables don't
+    /// need any support from the host. This must be called after `ee` has compiled the module,
+    /// and the pointer is swapped atomically, so other threads may call the dispatcher meanwhile.
+    pub fn select<E>(&self, ee: &'a E, supported: &Fn(&str) -> bool) -> usize where E: ExecutionEngine<'a>, LLVMExecutionEngineRef: From<&'a E> {
+        let index = self.versions.iter().rposition(|&(ref features, _)| {
+            features.get_enabled().into_iter().all(|feature| supported(feature))
+        }).unwrap_or(0);
+        unsafe {
+            let addr = engine::LLVMGetPointerToGlobal(ee.into(), self.versions[index].1.into());
+            let slot = engine::LLVMGetPointerToGlobal(ee.into(), self.pointer.into()) as *mut *mut c_void;
+            (*(slot as *const AtomicPtr<c_void>)).store(addr, Ordering::SeqCst);
+        }
+        index
+    }
+}
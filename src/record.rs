@@ -0,0 +1,145 @@
+use libc::c_void;
+use ffi::core;
+use ffi::execution_engine::LLVMExecutionEngineRef;
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::slice;
+use builder::Builder;
+use compile::Compile;
+use context::GetContext;
+use engine::ExecutionEngine;
+use types::{ArrayType, FunctionType, PointerType, Type, TypeKind};
+use value::{Function, Value};
+
+/// A call into a host function that was logged by a `Recorder`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Call {
+    /// The name of the host function that was called.
+    pub function: String,
+    /// The arguments the function was called with, each widened or bitcast to 64 bits.
+    pub args: Vec<u64>
+}
+
+/// An execution engine wrapper that logs every call into host-registered functions.
+///
+/// Calls are logged in the order they happen, so the log of a single-threaded program is
+/// deterministic and can be compared against a golden trace in tests.
+pub struct Recorder<'a, E> where E: 'a {
+    engine: &'a E,
+    names: RefCell<Vec<String>>,
+    calls: RefCell<Vec<Call>>
+}
+impl<'a, E> Recorder<'a, E> where E: ExecutionEngine<'a>, LLVMExecutionEngineRef: From<&'a E> {
+    /// Wrap the engine given in a recorder with an empty log.
+    pub fn new(engine: &'a E) -> Recorder<'a, E> {
+        Recorder {
+            engine: engine,
+            names: RefCell::new(Vec::new()),
+            calls: RefCell::new(Vec::new())
+        }
+    }
+    /// Register `function`, which must be a declaration in a module added to the engine, as
+    /// the host function at `addr`, logging every call to it.
+    ///
+    /// This gives `function` a body that logs its arguments then calls `addr`, so this must be
+    /// done before the engine compiles the module.
+    ///
+    /// Only integers up to 64 bits, floating-point numbers up to `double` and pointers can be
+    /// logged, so this panics if `function` takes any other type, such as a struct.
+    ///
+    /// This is marked as unsafe because the function at `addr` must match the signature of
+    /// `function`, and the recorder must outlive every call into the compiled code.
+    pub unsafe fn add_host_function(&'a self, function: &'a Function, addr: *const c_void) {
+        let name = function.get_name().unwrap_or("").to_owned();
+        let ctx = function.get_context();
+        let module = function.get_parent();
+        let sig = function.get_signature();
+        for param in sig.get_params() {
+            if !can_log(param) {
+                panic!("can't log arguments of type {:?} to {}", param, name)
+            }
+        }
+        let host = module.add_function(&format!("{}.host", name), sig);
+        self.engine.add_global_mapping(host, addr);
+        let word = Type::get::<u64>(ctx);
+        let log_sig = FunctionType::new(Type::get::<()>(ctx), &[
+            PointerType::new(Type::get::<u8>(ctx)),
+            Type::get::<u32>(ctx),
+            Type::get::<u32>(ctx),
+            PointerType::new(word)
+        ]);
+        let log = module.add_function(&format!("{}.record", name), log_sig);
+        self.engine.add_global_mapping(log, record_call::<E> as *const c_void);
+        let id = {
+            let mut names = self.names.borrow_mut();
+            names.push(name);
+            names.len() - 1
+        };
+        let builder = Builder::new(ctx);
+        builder.position_at_end(function.append("entry"));
+        let num_args = sig.num_params();
+        let args_ptr = builder.build_alloca(ArrayType::new(word, num_args));
+        let args: Vec<&Value> = (0..num_args).map(|index| &*function[index]).collect();
+        for (index, &arg) in args.iter().enumerate() {
+            let slot = builder.build_inbounds_gep(args_ptr, &[0usize.compile(ctx), index.compile(ctx)]);
+            builder.build_store(to_word(&builder, arg, word), slot);
+        }
+        let this = builder.build_int_to_ptr((self as *const Self as usize).compile(ctx), PointerType::new(Type::get::<u8>(ctx)));
+        let args_ptr = builder.build_bit_cast(args_ptr, PointerType::new(word));
+        builder.build_call(log, &[this, (id as u32).compile(ctx), (num_args as u32).compile(ctx), args_ptr]);
+        let result = builder.build_call(host, &args);
+        if sig.get_return().is_void() {
+            builder.build_ret_void();
+        } else {
+            builder.build_ret(result);
+        }
+    }
+    /// Returns a copy of the calls logged so far, in the order they happened.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.borrow().clone()
+    }
+    /// Clear the calls logged so far.
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear()
+    }
+}
+impl<'a, E> Deref for Recorder<'a, E> where E: 'a {
+    type Target = E;
+    fn deref(&self) -> &E {
+        self.engine
+    }
+}
+
+/// Returns true if values of the type given fit in a 64-bit integer, so they can be logged.
+fn can_log(ty: &Type) -> bool {
+    match ty.kind() {
+        TypeKind::Pointer | TypeKind::Half | TypeKind::Float | TypeKind::Double => true,
+        TypeKind::Integer => unsafe { core::LLVMGetIntTypeWidth(ty.into()) <= 64 },
+        _ => false
+    }
+}
+
+/// Convert a value whose type `can_log` accepts into a 64-bit integer so it can be logged.
+fn to_word<'a>(builder: &'a Builder, value: &'a Value, word: &'a Type) -> &'a Value {
+    let ty = value.get_type();
+    match ty.kind() {
+        TypeKind::Pointer => builder.build_ptr_to_int(value, word),
+        TypeKind::Integer if ty == word => value,
+        TypeKind::Integer => builder.build_zext(value, word),
+        TypeKind::Half => builder.build_zext(builder.build_bit_cast(value, Type::get::<u16>(ty.get_context())), word),
+        TypeKind::Float => builder.build_zext(builder.build_bit_cast(value, Type::get::<u32>(ty.get_context())), word),
+        TypeKind::Double => builder.build_bit_cast(value, word),
+        _ => unreachable!()
+    }
+}
+
+extern "C" fn record_call<'a, E>(recorder: *const Recorder<'a, E>, id: u32, num_args: u32, args: *const u64) where E: 'a {
+    unsafe {
+        let recorder = &*recorder;
+        let name = recorder.names.borrow()[id as usize].clone();
+        recorder.calls.borrow_mut().push(Call {
+            function: name,
+            args: slice::from_raw_parts(args, num_args as usize).to_vec()
+        });
+    }
+}
@@ -0,0 +1,204 @@
+use libc::c_uint;
+use ffi::{core, LLVMLinkage, LLVMOpcode, LLVMTypeKind};
+use ffi::prelude::{LLVMModuleRef, LLVMValueRef};
+use cbox::CSemiBox;
+use module::Module;
+
+/// A kind of change that `reduce` tries making to each part of a module it applies to.
+#[derive(Copy, Clone)]
+enum Edit {
+    /// Remove a function, replacing its uses with undef.
+    DeleteFunction,
+    /// Turn a function definition into a declaration.
+    StripBody,
+    /// Remove a global variable, replacing its uses with undef.
+    DeleteGlobal,
+    /// Make a conditional branch always jump to one of its two successors.
+    SimplifyBranch(u32),
+    /// Remove a block that nothing jumps to.
+    DeleteBlock,
+    /// Remove an instruction that isn't a terminator, replacing its uses with undef.
+    DeleteInstruction
+}
+impl Edit {
+    /// Returns true if this removes the part it applies to, so the next part takes its index.
+    fn removes(self) -> bool {
+        match self {
+            Edit::StripBody | Edit::SimplifyBranch(_) => false,
+            _ => true
+        }
+    }
+}
+
+/// The edits to try, from the ones that remove the most to the ones that remove the least.
+const EDITS: [Edit; 7] = [
+    Edit::DeleteFunction,
+    Edit::StripBody,
+    Edit::DeleteGlobal,
+    Edit::SimplifyBranch(0),
+    Edit::SimplifyBranch(1),
+    Edit::DeleteBlock,
+    Edit::DeleteInstruction
+];
+
+/// Returns the smallest copy of `module` that `reduce` can find where `interesting` is still true.
+pub fn reduce<'a>(module: &'a Module, interesting: &Fn(&Module) -> bool) -> CSemiBox<'a, Module> {
+    let mut current = module.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &edit in &EDITS {
+            let mut index = 0;
+            loop {
+                let candidate: CSemiBox<'a, Module> = CSemiBox::new(unsafe { core::LLVMCloneModule((&*current).into()) });
+                match unsafe { apply((&*candidate).into(), edit, index) } {
+                    None => break,
+                    Some(true) if candidate.verify().is_ok() && interesting(&candidate) => {
+                        current = candidate;
+                        changed = true;
+                        if edit.removes() {
+                            continue;
+                        }
+                    },
+                    Some(_) => ()
+                }
+                index += 1;
+            }
+        }
+    }
+    current
+}
+
+/// Make `edit` to the part of `module` at `index`, and return true if it applied, false if it
+/// doesn't apply to that part, or `None` if there are no more parts it could apply to.
+unsafe fn apply(module: LLVMModuleRef, edit: Edit, index: usize) -> Option<bool> {
+    match edit {
+        Edit::DeleteFunction => functions(module).get(index).map(|&func| {
+            replace_with_undef(func);
+            core::LLVMDeleteFunction(func);
+            true
+        }),
+        Edit::StripBody => functions(module).get(index).map(|&func| {
+            if core::LLVMIsDeclaration(func) != 0 {
+                return false;
+            }
+            // the blocks refer to each other, so empty them all before deleting any
+            let instructions = function_instructions(func);
+            for &inst in &instructions {
+                replace_with_undef(inst);
+            }
+            for inst in instructions {
+                core::LLVMInstructionEraseFromParent(inst);
+            }
+            while !core::LLVMGetFirstBasicBlock(func).is_null() {
+                core::LLVMDeleteBasicBlock(core::LLVMGetFirstBasicBlock(func));
+            }
+            // a declaration can't be local
+            core::LLVMSetLinkage(func, LLVMLinkage::LLVMExternalLinkage);
+            true
+        }),
+        Edit::DeleteGlobal => globals(module).get(index).map(|&global| {
+            replace_with_undef(global);
+            core::LLVMDeleteGlobal(global);
+            true
+        }),
+        Edit::SimplifyBranch(successor) => instructions(module).get(index).map(|&inst| {
+            if core::LLVMGetInstructionOpcode(inst) as c_uint != LLVMOpcode::LLVMBr as c_uint || core::LLVMGetNumOperands(inst) != 3 {
+                return false;
+            }
+            let builder = core::LLVMCreateBuilderInContext(core::LLVMGetModuleContext(module));
+            core::LLVMPositionBuilderBefore(builder, inst);
+            core::LLVMBuildBr(builder, core::LLVMGetSuccessor(inst, successor));
+            core::LLVMDisposeBuilder(builder);
+            core::LLVMInstructionEraseFromParent(inst);
+            true
+        }),
+        Edit::DeleteBlock => blocks(module).get(index).map(|&block| {
+            let func = core::LLVMGetBasicBlockParent(core::LLVMValueAsBasicBlock(block));
+            if block == core::LLVMBasicBlockAsValue(core::LLVMGetEntryBasicBlock(func)) || !core::LLVMGetFirstUse(block).is_null() {
+                return false;
+            }
+            delete_block(block);
+            true
+        }),
+        Edit::DeleteInstruction => instructions(module).get(index).map(|&inst| {
+            if !core::LLVMIsATerminatorInst(inst).is_null() {
+                return false;
+            }
+            replace_with_undef(inst);
+            core::LLVMInstructionEraseFromParent(inst);
+            true
+        })
+    }
+}
+
+/// Replace every use of `value` with undef, unless it doesn't have a value to use.
+unsafe fn replace_with_undef(value: LLVMValueRef) {
+    let ty = core::LLVMTypeOf(value);
+    if core::LLVMGetTypeKind(ty) as c_uint != LLVMTypeKind::LLVMVoidTypeKind as c_uint {
+        core::LLVMReplaceAllUsesWith(value, core::LLVMGetUndef(ty));
+    }
+}
+
+/// Delete the block `block` and its instructions, replacing the uses of them with undef.
+unsafe fn delete_block(block: LLVMValueRef) {
+    let block = core::LLVMValueAsBasicBlock(block);
+    let mut inst = core::LLVMGetFirstInstruction(block);
+    while !inst.is_null() {
+        replace_with_undef(inst);
+        inst = core::LLVMGetNextInstruction(inst);
+    }
+    core::LLVMDeleteBasicBlock(block);
+}
+
+unsafe fn functions(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut functions = Vec::new();
+    let mut func = core::LLVMGetFirstFunction(module);
+    while !func.is_null() {
+        functions.push(func);
+        func = core::LLVMGetNextFunction(func);
+    }
+    functions
+}
+
+unsafe fn globals(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut globals = Vec::new();
+    let mut global = core::LLVMGetFirstGlobal(module);
+    while !global.is_null() {
+        globals.push(global);
+        global = core::LLVMGetNextGlobal(global);
+    }
+    globals
+}
+
+/// Returns every block in `module`, as a value.
+unsafe fn blocks(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut blocks = Vec::new();
+    for func in functions(module) {
+        let mut block = core::LLVMGetFirstBasicBlock(func);
+        while !block.is_null() {
+            blocks.push(core::LLVMBasicBlockAsValue(block));
+            block = core::LLVMGetNextBasicBlock(block);
+        }
+    }
+    blocks
+}
+
+unsafe fn instructions(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    functions(module).into_iter().flat_map(|func| function_instructions(func)).collect()
+}
+
+/// Returns every instruction in the function `func`.
+unsafe fn function_instructions(func: LLVMValueRef) -> Vec<LLVMValueRef> {
+    let mut instructions = Vec::new();
+    let mut block = core::LLVMGetFirstBasicBlock(func);
+    while !block.is_null() {
+        let mut inst = core::LLVMGetFirstInstruction(block);
+        while !inst.is_null() {
+            instructions.push(inst);
+            inst = core::LLVMGetNextInstruction(inst);
+        }
+        block = core::LLVMGetNextBasicBlock(block);
+    }
+    instructions
+}
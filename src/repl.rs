@@ -0,0 +1,214 @@
+use cbox::{CBox, CSemiBox};
+use ffi::core;
+use ffi::execution_engine as engine;
+use std::cell::{Cell, RefCell};
+use std::ops::Deref;
+use engine::{ExecutionEngine, JitEngine, JitOptions};
+use module::Module;
+use target::TargetError;
+use types::Type;
+use value::{Function, GlobalValue, Linkage};
+
+/// Which definition of a symbol a `ReplSession` uses when more than one input defines it.
+///
+/// Like a static linker, a strong definition always wins over a weak one, and two strong
+/// definitions of the same symbol are an error. This only decides between two weak definitions,
+/// which are those with weak, link-once or common linkage.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Interposition {
+    /// Keep the definition from the earliest input, and resolve later definitions to it.
+    FirstWins,
+    /// Use the definition from the latest input for every input added after it.
+    ///
+    /// Code that was already compiled keeps calling the definition it was compiled against.
+    LastWins
+}
+
+/// A symbol defined by an earlier input of a `ReplSession`.
+struct Definition<'a> {
+    name: String,
+    ty: &'a Type,
+    is_function: bool,
+    global: &'a GlobalValue,
+    overridden: bool
+}
+
+/// An interactive session that JIT-compiles a chain of modules, one per input.
+///
+/// Every module added to the session can use the functions and global variables defined by
+/// the modules added before it, since `prepare` declares them in the new module.
+///
+/// ```rust,no_run
+/// use llvm::*;
+/// let ctx = Context::new();
+/// let prelude = Module::new("prelude", &ctx);
+/// let session = ReplSession::new(&prelude, JitOptions {opt_level: 0}).unwrap();
+/// let input = Module::new("input1", &ctx);
+/// session.prepare(&input);
+/// // build the code for the input in `input` here
+/// session.add(&input).unwrap();
+/// ```
+pub struct ReplSession<'a> {
+    engine: CSemiBox<'a, JitEngine>,
+    definitions: RefCell<Vec<Definition<'a>>>,
+    interposition: Cell<Interposition>
+}
+impl<'a> ReplSession<'a> {
+    /// Start a session that JIT-compiles `prelude`, which may be empty, before any input.
+    pub fn new(prelude: &'a Module, options: JitOptions) -> Result<ReplSession<'a>, TargetError> {
+        let session = ReplSession {
+            engine: try!(JitEngine::new(prelude, options)),
+            definitions: RefCell::new(Vec::new()),
+            interposition: Cell::new(Interposition::LastWins)
+        };
+        session.record(prelude);
+        Ok(session)
+    }
+    /// Set which definition wins when two inputs define the same weak symbol.
+    ///
+    /// This defaults to `Interposition::LastWins`, so redefining a function in a later input
+    /// overrides it for the inputs after that.
+    pub fn set_interposition(&self, policy: Interposition) {
+        self.interposition.set(policy)
+    }
+    /// Returns which definition wins when two inputs define the same weak symbol.
+    pub fn get_interposition(&self) -> Interposition {
+        self.interposition.get()
+    }
+    /// Declare every symbol defined by earlier inputs in `module`, so code built in it can use them.
+    ///
+    /// This skips symbols that `module` already has, so it can be called more than once.
+    pub fn prepare(&'a self, module: &'a Module) {
+        for def in self.definitions.borrow().iter() {
+            let decl: &GlobalValue = if def.is_function {
+                if module.get_function(&def.name).is_some() {
+                    continue;
+                }
+                &**module.add_function(&def.name, def.ty)
+            } else {
+                if module.get_global(&def.name).is_some() {
+                    continue;
+                }
+                &**module.add_global(&def.name, def.ty)
+            };
+            self.bind(decl, def);
+        }
+    }
+    /// Verify `module` and add it to the engine, or return an error string if it is invalid or
+    /// defines a symbol that an earlier input already has a strong definition of.
+    ///
+    /// The symbols it defines are then available to later inputs, according to the interposition
+    /// policy. When a symbol is defined again, later inputs are declared with the winning
+    /// definition's type.
+    pub fn add(&'a self, module: &'a Module) -> Result<(), CBox<str>> {
+        try!(self.resolve(module));
+        try!(module.verify());
+        self.engine.add_module(module);
+        self.record(module);
+        Ok(())
+    }
+    /// Returns the engine that compiles the inputs.
+    pub fn get_engine(&self) -> &JitEngine {
+        &self.engine
+    }
+    /// Turn the definitions in `module` that lose to an earlier input's definition into
+    /// declarations bound to the winner.
+    fn resolve(&'a self, module: &'a Module) -> Result<(), CBox<str>> {
+        for (global, is_function) in exported(module) {
+            let defs = self.definitions.borrow();
+            let def = match defs.iter().find(|def| Some(&*def.name) == global.get_name()) {
+                Some(def) => def,
+                None => continue
+            };
+            let keep_new = match (is_weak(def.global.get_linkage()), is_weak(global.get_linkage())) {
+                (false, false) => return Err(CBox::from(&*format!("{} is already defined", def.name))),
+                (true, false) => true,
+                (false, true) => false,
+                (true, true) => self.interposition.get() == Interposition::LastWins
+            };
+            if !keep_new {
+                self.bind(strip(module, global, is_function), def);
+            }
+        }
+        Ok(())
+    }
+    /// Map the declaration `decl` to the address of the definition `def` if it has been
+    /// overridden, so it doesn't resolve to an older definition with the same name.
+    fn bind(&'a self, decl: &'a GlobalValue, def: &Definition<'a>) {
+        if def.overridden {
+            unsafe {
+                let addr = engine::LLVMGetPointerToGlobal((&*self.engine).into(), def.global.into());
+                self.engine.add_global_mapping(decl, addr);
+            }
+        }
+    }
+    /// Record the externally visible symbols `module` defines.
+    fn record(&self, module: &'a Module) {
+        let mut defs = self.definitions.borrow_mut();
+        for (global, is_function) in exported(module) {
+            let name = global.get_name().unwrap().to_owned();
+            let ty: &Type = unsafe { core::LLVMGetElementType(global.get_type().into()) }.into();
+            let overridden = defs.iter().any(|def| def.name == name);
+            defs.retain(|def| def.name != name);
+            defs.push(Definition {
+                name: name,
+                ty: ty,
+                is_function: is_function,
+                global: global,
+                overridden: overridden
+            });
+        }
+    }
+}
+impl<'a> Deref for ReplSession<'a> {
+    type Target = JitEngine;
+    fn deref(&self) -> &JitEngine {
+        &self.engine
+    }
+}
+
+
+/// Returns true if a definition with the linkage given can be overridden by another definition.
+fn is_weak(linkage: Linkage) -> bool {
+    match linkage {
+        Linkage::LinkOnceAny | Linkage::LinkOnceODR | Linkage::WeakAny | Linkage::WeakODR | Linkage::Common => true,
+        _ => false
+    }
+}
+
+/// Returns the externally visible functions and global variables `module` defines, and whether
+/// each of them is a function.
+fn exported<'a>(module: &'a Module) -> Vec<(&'a GlobalValue, bool)> {
+    let functions = module.into_iter().map(|func: &'a Function| (&**func, true));
+    let globals = module.globals().map(|global| (&**global, false));
+    functions.chain(globals).filter(|&(global, _)| {
+        let internal = match global.get_linkage() {
+            Linkage::Private | Linkage::Internal | Linkage::Appending => true,
+            _ => false
+        };
+        let named = global.get_name().map(|name| !name.starts_with("llvm.")).unwrap_or(false);
+        !global.is_declaration() && !internal && named
+    }).collect()
+}
+
+/// Replace the definition `global` in `module` with a declaration of the same name and type,
+/// and return the declaration.
+fn strip<'a>(module: &'a Module, global: &'a GlobalValue, is_function: bool) -> &'a GlobalValue {
+    let name = global.get_name().unwrap().to_owned();
+    let ty: &Type = unsafe { core::LLVMGetElementType(global.get_type().into()) }.into();
+    global.set_name("");
+    let decl: &GlobalValue = if is_function {
+        &**module.add_function(&name, ty)
+    } else {
+        &**module.add_global(&name, ty)
+    };
+    unsafe {
+        core::LLVMReplaceAllUsesWith(global.into(), decl.into());
+        if is_function {
+            core::LLVMDeleteFunction(global.into())
+        } else {
+            core::LLVMDeleteGlobal(global.into())
+        }
+    }
+    decl
+}
@@ -0,0 +1,95 @@
+use libc::c_uint;
+use ffi::core;
+use ffi::execution_engine::LLVMExecutionEngineRef;
+use ffi::prelude::LLVMValueRef;
+use std::cell::{Cell, RefCell};
+use std::slice;
+use block::BasicBlock;
+use builder::Builder;
+use context::GetContext;
+use engine::ExecutionEngine;
+use module::Module;
+use types::{PointerType, Type};
+use value::{GlobalVariable, Value, Visibility};
+
+/// The source location that the machine code starting at an address was compiled from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+    /// The address the machine code for the location starts at.
+    pub address: usize,
+    /// The source file the code came from.
+    pub file: String,
+    /// The line in the source file the code came from.
+    pub line: u32
+}
+
+/// A lightweight map from JIT-compiled machine code addresses to source locations, for custom
+/// profilers and error reporting that don't want to parse DWARF.
+///
+/// Every call to `set_location` starts a new basic block for the instructions of that location,
+/// and `finish` adds a table of the addresses of these blocks to the module, which the JIT fills
+/// in when it compiles the module. Since the blocks have their addresses taken, the optimizer
+/// can't merge them, so this gives exact boundaries at the cost of some optimizations.
+pub struct SourceMap<'a> {
+    locations: RefCell<Vec<(&'a BasicBlock, String, u32)>>,
+    table: Cell<Option<&'a GlobalVariable>>
+}
+impl<'a> SourceMap<'a> {
+    /// Make an empty source map.
+    pub fn new() -> SourceMap<'a> {
+        SourceMap {
+            locations: RefCell::new(Vec::new()),
+            table: Cell::new(None)
+        }
+    }
+    /// Attribute the instructions built by `builder` from now on to `line` of `file`.
+    ///
+    /// This ends the block the builder is positioned in with a branch to a new block, so the
+    /// builder must be positioned at the end of a block without a terminator.
+    pub fn set_location(&self, builder: &'a Builder, file: &str, line: u32) {
+        let current: &BasicBlock = unsafe { core::LLVMGetInsertBlock(builder.into()) }.into();
+        let function = current.get_parent().expect("builder is not positioned in a function");
+        let block = function.append(&format!("{}:{}", file, line));
+        builder.build_br(block);
+        builder.position_at_end(block);
+        self.locations.borrow_mut().push((block, file.to_owned(), line));
+    }
+    /// Add the table of the addresses of every location to `module`, which must contain all of
+    /// the functions that locations were set in.
+    ///
+    /// This must be done after all the code has been built, and before the module is compiled.
+    pub fn finish(&self, module: &'a Module) -> &'a GlobalVariable {
+        let ctx = module.get_context();
+        let byte_ptr = PointerType::new(Type::get::<u8>(ctx));
+        let locations = self.locations.borrow();
+        unsafe {
+            let mut addresses: Vec<LLVMValueRef> = locations.iter().map(|&(block, _, _)| block.get_address().into()).collect();
+            let table: &Value = core::LLVMConstArray(byte_ptr.into(), addresses.as_mut_ptr(), addresses.len() as c_uint).into();
+            // this can't be private because the engine only finds external symbols
+            let global = module.add_global_variable("source_map", table);
+            global.set_visibility(Visibility::Hidden);
+            global.set_constant(true);
+            self.table.set(Some(global));
+            global
+        }
+    }
+    /// Returns the address of every location in the code `ee` compiled, sorted by address.
+    ///
+    /// The location of an address is the last one at or before it, which can be found with
+    /// a binary search on `address`.
+    pub fn export<E>(&self, ee: &'a E) -> Vec<SourceLocation> where E: ExecutionEngine<'a>, LLVMExecutionEngineRef: From<&'a E> {
+        let table = self.table.get().expect("source map has not been finished");
+        let locations = self.locations.borrow();
+        let addresses = unsafe {
+            let start: &usize = ee.get_global(table);
+            slice::from_raw_parts(start, locations.len())
+        };
+        let mut exported: Vec<SourceLocation> = locations.iter().zip(addresses).map(|(&(_, ref file, line), &address)| SourceLocation {
+            address: address,
+            file: file.clone(),
+            line: line
+        }).collect();
+        exported.sort_by(|a, b| a.address.cmp(&b.address));
+        exported
+    }
+}
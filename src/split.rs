@@ -0,0 +1,206 @@
+use libc::{c_char, c_uint};
+use ffi::{core, LLVMLinkage, LLVMVisibility};
+use ffi::prelude::{LLVMModuleRef, LLVMValueRef};
+use cbox::CSemiBox;
+use std::ffi::CString;
+use builder::NULL_NAME;
+use module::Module;
+use util;
+
+/// Returns `module` split into `count` modules that define its functions and global variables
+/// between them, balanced by the number of instructions in each.
+pub fn split<'a>(module: &'a Module, count: usize) -> Vec<CSemiBox<'a, Module>> {
+    assert!(count > 0, "expected to split into at least one module");
+    let source = module.clone();
+    let owners = unsafe {
+        let source = (&*source).into();
+        let symbols = definitions(source);
+        for (index, &symbol) in symbols.iter().enumerate() {
+            promote(symbol, index);
+        }
+        for &symbol in &symbols {
+            share_if_aliased(symbol);
+        }
+        assign(&symbols, count)
+    };
+    (0..count).map(|part| {
+        let partition: CSemiBox<'a, Module> = CSemiBox::new(unsafe { core::LLVMCloneModule((&*source).into()) });
+        unsafe {
+            let raw = (&*partition).into();
+            // the clone has the same symbols in the same order
+            for (&symbol, &owner) in definitions(raw).iter().zip(&owners) {
+                if owner.map(|owner| owner != part).unwrap_or(false) {
+                    make_declaration(raw, symbol);
+                }
+            }
+            if part > 0 {
+                // constructors, destructors and `llvm.used` only need to be in one module
+                for global in globals(raw) {
+                    if linkage_is(global, LLVMLinkage::LLVMAppendingLinkage) {
+                        core::LLVMDeleteGlobal(global);
+                    }
+                }
+            }
+        }
+        partition
+    }).collect()
+}
+
+/// Returns every function and global variable `module` defines, other than the appending
+/// globals, in the order the module has them.
+unsafe fn definitions(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut definitions: Vec<LLVMValueRef> = functions(module).into_iter().filter(|&func| core::LLVMIsDeclaration(func) == 0).collect();
+    definitions.extend(globals(module).into_iter().filter(|&global| {
+        core::LLVMIsDeclaration(global) == 0 && !linkage_is(global, LLVMLinkage::LLVMAppendingLinkage)
+    }));
+    definitions
+}
+
+/// Make `symbol` visible to the other modules it is split into, without exporting it from
+/// the program if it was local.
+unsafe fn promote(symbol: LLVMValueRef, index: usize) {
+    if linkage_is(symbol, LLVMLinkage::LLVMInternalLinkage) || linkage_is(symbol, LLVMLinkage::LLVMPrivateLinkage) {
+        if util::to_str(core::LLVMGetValueName(symbol) as *mut c_char).is_empty() {
+            util::with_cstr(&format!("split.{}", index), |name| core::LLVMSetValueName(symbol, name));
+        }
+        core::LLVMSetLinkage(symbol, LLVMLinkage::LLVMExternalLinkage);
+        core::LLVMSetVisibility(symbol, LLVMVisibility::LLVMHiddenVisibility);
+    } else if linkage_is(symbol, LLVMLinkage::LLVMLinkOnceAnyLinkage) {
+        // the module that defines it might drop it if it doesn't use it itself
+        core::LLVMSetLinkage(symbol, LLVMLinkage::LLVMWeakAnyLinkage);
+    } else if linkage_is(symbol, LLVMLinkage::LLVMLinkOnceODRLinkage) {
+        core::LLVMSetLinkage(symbol, LLVMLinkage::LLVMWeakODRLinkage);
+    }
+}
+
+/// If an alias refers to `symbol`, make it and its aliases weak so they can be defined in
+/// every module, since an alias can't refer to a declaration.
+unsafe fn share_if_aliased(symbol: LLVMValueRef) {
+    let aliases = aliases_of(symbol);
+    if !aliases.is_empty() {
+        core::LLVMSetLinkage(symbol, LLVMLinkage::LLVMWeakODRLinkage);
+        for alias in aliases {
+            if linkage_is(alias, LLVMLinkage::LLVMInternalLinkage) || linkage_is(alias, LLVMLinkage::LLVMPrivateLinkage) {
+                core::LLVMSetVisibility(alias, LLVMVisibility::LLVMHiddenVisibility);
+            }
+            core::LLVMSetLinkage(alias, LLVMLinkage::LLVMWeakODRLinkage);
+        }
+    }
+}
+
+/// Returns the aliases that refer to `value`, directly or through a constant expression.
+unsafe fn aliases_of(value: LLVMValueRef) -> Vec<LLVMValueRef> {
+    let mut aliases = Vec::new();
+    let mut using = core::LLVMGetFirstUse(value);
+    while !using.is_null() {
+        let user = core::LLVMGetUser(using);
+        if !core::LLVMIsAGlobalAlias(user).is_null() {
+            aliases.push(user);
+        } else if !core::LLVMIsAConstantExpr(user).is_null() {
+            aliases.extend(aliases_of(user));
+        }
+        using = core::LLVMGetNextUse(using);
+    }
+    aliases
+}
+
+/// Returns the index of the module each of `symbols` is defined in, or `None` if it is
+/// defined in all of them, putting the biggest functions first in the emptiest module.
+unsafe fn assign(symbols: &[LLVMValueRef], count: usize) -> Vec<Option<usize>> {
+    let sizes: Vec<usize> = symbols.iter().map(|&symbol| size(symbol)).collect();
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].cmp(&sizes[a]));
+    let mut loads = vec![0; count];
+    let mut owners = vec![None; symbols.len()];
+    for index in order {
+        let symbol = symbols[index];
+        // available externally definitions are never emitted, so every module keeps them
+        if !aliases_of(symbol).is_empty() || linkage_is(symbol, LLVMLinkage::LLVMAvailableExternallyLinkage) {
+            continue;
+        }
+        let emptiest = (0..count).min_by_key(|&part| loads[part]).unwrap();
+        loads[emptiest] += sizes[index];
+        owners[index] = Some(emptiest);
+    }
+    owners
+}
+
+/// Returns how many instructions `symbol` has if it is a function, or one otherwise.
+unsafe fn size(symbol: LLVMValueRef) -> usize {
+    if core::LLVMIsAFunction(symbol).is_null() {
+        return 1;
+    }
+    let mut size = 0;
+    let mut block = core::LLVMGetFirstBasicBlock(symbol);
+    while !block.is_null() {
+        let mut inst = core::LLVMGetFirstInstruction(block);
+        while !inst.is_null() {
+            size += 1;
+            inst = core::LLVMGetNextInstruction(inst);
+        }
+        block = core::LLVMGetNextBasicBlock(block);
+    }
+    size
+}
+
+/// Turn the definition `symbol` into a declaration of the same symbol.
+///
+/// The C API can't remove a function's body or personality or a global variable's initializer,
+/// so this replaces `symbol` with a new declaration and deletes it.
+unsafe fn make_declaration(module: LLVMModuleRef, symbol: LLVMValueRef) {
+    let ty = core::LLVMGetElementType(core::LLVMTypeOf(symbol));
+    let declaration = if !core::LLVMIsAFunction(symbol).is_null() {
+        let declaration = core::LLVMAddFunction(module, NULL_NAME.as_ptr(), ty);
+        core::LLVMSetFunctionCallConv(declaration, core::LLVMGetFunctionCallConv(symbol));
+        core::LLVMAddFunctionAttr(declaration, core::LLVMGetFunctionAttr(symbol));
+        for index in 0..core::LLVMCountParams(symbol) {
+            let attrs = core::LLVMGetAttribute(core::LLVMGetParam(symbol, index));
+            core::LLVMAddAttribute(core::LLVMGetParam(declaration, index), attrs);
+        }
+        if !core::LLVMGetGC(symbol).is_null() {
+            core::LLVMSetGC(declaration, core::LLVMGetGC(symbol));
+        }
+        declaration
+    } else {
+        let space = core::LLVMGetPointerAddressSpace(core::LLVMTypeOf(symbol));
+        let declaration = core::LLVMAddGlobalInAddressSpace(module, ty, NULL_NAME.as_ptr(), space as c_uint);
+        core::LLVMSetThreadLocal(declaration, core::LLVMIsThreadLocal(symbol));
+        core::LLVMSetGlobalConstant(declaration, core::LLVMIsGlobalConstant(symbol));
+        declaration
+    };
+    core::LLVMSetVisibility(declaration, core::LLVMGetVisibility(symbol));
+    core::LLVMSetDLLStorageClass(declaration, core::LLVMGetDLLStorageClass(symbol));
+    core::LLVMReplaceAllUsesWith(symbol, declaration);
+    let name = CString::new(util::to_str(core::LLVMGetValueName(symbol) as *mut c_char)).unwrap();
+    core::LLVMSetValueName(symbol, NULL_NAME.as_ptr());
+    core::LLVMSetValueName(declaration, name.as_ptr());
+    if core::LLVMIsAFunction(symbol).is_null() {
+        core::LLVMDeleteGlobal(symbol);
+    } else {
+        core::LLVMDeleteFunction(symbol);
+    }
+}
+
+unsafe fn linkage_is(global: LLVMValueRef, linkage: LLVMLinkage) -> bool {
+    core::LLVMGetLinkage(global) as c_uint == linkage as c_uint
+}
+
+unsafe fn functions(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut functions = Vec::new();
+    let mut func = core::LLVMGetFirstFunction(module);
+    while !func.is_null() {
+        functions.push(func);
+        func = core::LLVMGetNextFunction(func);
+    }
+    functions
+}
+
+unsafe fn globals(module: LLVMModuleRef) -> Vec<LLVMValueRef> {
+    let mut globals = Vec::new();
+    let mut global = core::LLVMGetFirstGlobal(module);
+    while !global.is_null() {
+        globals.push(global);
+        global = core::LLVMGetNextGlobal(global);
+    }
+    globals
+}
@@ -1,10 +1,12 @@
 use libc::{c_char,  c_uint};
-use ffi::target_machine::{self, LLVMTargetRef};
+use ffi::target_machine::{self, LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMOpaqueTargetMachine, LLVMRelocMode, LLVMTargetMachineRef, LLVMTargetRef};
 use ffi::target::{self, LLVMTargetDataRef, LLVMOpaqueTargetData};
 use cbox::{CBox, DisposeRef};
 use std::ffi::CString;
-use std::fmt;
+use std::{error, fmt, mem};
 use std::marker::PhantomData;
+use buffer::MemoryBuffer;
+use module::Module;
 use types::Type;
 use util;
 
@@ -72,6 +74,38 @@ impl DisposeRef for TargetData {
 pub struct Target(PhantomData<[u8]>);
 native_ref!(&Target = LLVMTargetRef);
 impl Target {
+    /// Returns the target that generates code for `triple`.
+    ///
+    /// The backend of the target must have been initialized already.
+    pub fn from_triple(triple: &str) -> Result<&'static Target, TargetError> {
+        let c_triple = CString::new(triple).unwrap();
+        unsafe {
+            let mut target = mem::uninitialized();
+            let mut error = mem::uninitialized();
+            if target_machine::LLVMGetTargetFromTriple(c_triple.as_ptr(), &mut target, &mut error) == 1 {
+                let message = (*CBox::<str>::new(error)).to_owned();
+                return Err(if target_machine::LLVMGetFirstTarget().is_null() {
+                    TargetError::TargetNotRegistered(triple.to_owned())
+                } else {
+                    TargetError::UnknownTriple(triple.to_owned(), message)
+                })
+            }
+            Ok(target.into())
+        }
+    }
+    /// Initialize the backend of the host and return the target of the host.
+    pub fn native() -> Result<&'static Target, TargetError> {
+        unsafe {
+            if target::LLVM_InitializeNativeTarget() == 1 {
+                return Err(TargetError::NativeTargetUnavailable)
+            }
+            if target::LLVM_InitializeNativeAsmPrinter() == 1 {
+                return Err(TargetError::NativeTargetUnavailable)
+            }
+            let triple = CBox::<str>::new(target_machine::LLVMGetDefaultTargetTriple());
+            Target::from_triple(&triple)
+        }
+    }
     /// Returns the name of this target.
     pub fn get_name(&self) -> &str {
         unsafe { util::to_str(target_machine::LLVMGetTargetName(self.into()) as *mut c_char) }
@@ -93,3 +127,433 @@ impl Target {
         unsafe { target_machine::LLVMTargetHasTargetMachine(self.into()) != 0 }
     }
 }
+
+/// A machine code generator for a specific target triple, CPU and feature set.
+pub struct TargetMachine(PhantomData<[u8]>);
+native_ref!(&TargetMachine = LLVMTargetMachineRef);
+impl TargetMachine {
+    /// Create a target machine for `triple` that generates code for `cpu` with the feature
+    /// string `features`, or return an error if the target is unknown.
+    ///
+    /// The target must have been initialized already.
+    pub fn new(triple: &str, cpu: &str, features: &str, opt_level: usize) -> Result<CBox<TargetMachine>, TargetError> {
+        TargetMachine::new_with_reloc(triple, cpu, features, opt_level, RelocMode::Default)
+    }
+    /// Create a target machine like `new` that generates code for the relocation model given.
+    pub fn new_with_reloc(triple: &str, cpu: &str, features: &str, opt_level: usize, reloc: RelocMode) -> Result<CBox<TargetMachine>, TargetError> {
+        let level = match opt_level {
+            0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            2 => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            _ => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive
+        };
+        let target = try!(Target::from_triple(triple));
+        if !target.has_target_machine() {
+            return Err(TargetError::NoTargetMachine(target.get_name().to_owned()))
+        }
+        let c_triple = CString::new(triple).unwrap();
+        let c_cpu = CString::new(cpu).unwrap();
+        let c_features = CString::new(features).unwrap();
+        unsafe {
+            let machine = target_machine::LLVMCreateTargetMachine(target.into(), c_triple.as_ptr(), c_cpu.as_ptr(), c_features.as_ptr(), level, reloc.into(), LLVMCodeModel::LLVMCodeModelDefault);
+            Ok(CBox::new(machine))
+        }
+    }
+    /// Create a target machine for the host, initializing the native target if needed.
+    pub fn new_native(opt_level: usize) -> Result<CBox<TargetMachine>, TargetError> {
+        try!(Target::native());
+        let triple = unsafe { CBox::<str>::new(target_machine::LLVMGetDefaultTargetTriple()) };
+        TargetMachine::new(&triple, "", "", opt_level)
+    }
+    /// Create a target machine for the preset given.
+    ///
+    /// The backend of the preset's architecture must have been initialized already, and
+    /// modules compiled with it should have the preset applied to them with `TargetPreset::apply`.
+    pub fn from_preset(preset: &TargetPreset, opt_level: usize) -> Result<CBox<TargetMachine>, TargetError> {
+        TargetMachine::new_with_reloc(preset.triple, preset.cpu, preset.features, opt_level, preset.reloc)
+    }
+    /// Returns the target triple this generates code for.
+    pub fn get_triple(&self) -> CBox<str> {
+        unsafe { CBox::new(target_machine::LLVMGetTargetMachineTriple(self.into())) }
+    }
+    /// Returns the target this generates code for.
+    pub fn get_target(&self) -> &Target {
+        unsafe { target_machine::LLVMGetTargetMachineTarget(self.into()) }.into()
+    }
+    /// Returns the data layout of the code this generates, which the modules it compiles should
+    /// have so the optimizer knows the sizes and alignments of types.
+    pub fn get_data_layout(&self) -> CBox<TargetData> {
+        CBox::new(unsafe { target_machine::LLVMCreateTargetDataLayout(self.into()) })
+    }
+    /// Compile `module` into an object file in memory, or return an error string if an error occurs.
+    ///
+    /// The bytes of the object file can be read with `MemoryBuffer::as_bytes`, which lets them be
+    /// persisted, checksummed or shipped without touching the filesystem.
+    pub fn emit_object(&self, module: &Module) -> Result<CBox<MemoryBuffer>, CBox<str>> {
+        self.emit(module, LLVMCodeGenFileType::LLVMObjectFile)
+    }
+    /// Compile `module` into assembly in memory, or return an error string if an error occurs.
+    pub fn emit_assembly(&self, module: &Module) -> Result<CBox<MemoryBuffer>, CBox<str>> {
+        self.emit(module, LLVMCodeGenFileType::LLVMAssemblyFile)
+    }
+    fn emit(&self, module: &Module, file_type: LLVMCodeGenFileType) -> Result<CBox<MemoryBuffer>, CBox<str>> {
+        unsafe {
+            let mut buffer = mem::uninitialized();
+            let mut error = mem::uninitialized();
+            if target_machine::LLVMTargetMachineEmitToMemoryBuffer(self.into(), module.into(), file_type, &mut error, &mut buffer) == 1 {
+                Err(CBox::new(error))
+            } else {
+                Ok(CBox::new(buffer))
+            }
+        }
+    }
+}
+impl DisposeRef for TargetMachine {
+    type RefTo = LLVMOpaqueTargetMachine;
+    unsafe fn dispose(ptr: LLVMTargetMachineRef) {
+        target_machine::LLVMDisposeTargetMachine(ptr)
+    }
+}
+
+/// An error returned when a target, target machine or execution engine can't be created.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TargetError {
+    /// The backend of the host isn't built into LLVM, so it can't be initialized.
+    NativeTargetUnavailable,
+    /// No backend has been initialized, so no triple can be looked up.
+    TargetNotRegistered(String),
+    /// None of the initialized backends supports the triple, with LLVM's error message.
+    UnknownTriple(String, String),
+    /// The target with this name can't make target machines.
+    NoTargetMachine(String),
+    /// The target with this name doesn't support JIT compilation.
+    JitNotSupportedOnPlatform(String),
+    /// The module given to the execution engine is invalid, with the verifier's error message.
+    ///
+    /// This is checked before compiling, since the backend may crash on invalid code.
+    InvalidModule(String),
+    /// LLVM failed to create the execution engine, with its error message.
+    EngineCreation(String)
+}
+impl fmt::Display for TargetError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TargetError::NativeTargetUnavailable =>
+                write!(fmt, "the native target is not available"),
+            TargetError::TargetNotRegistered(ref triple) =>
+                write!(fmt, "no targets are registered to look up {}", triple),
+            TargetError::UnknownTriple(ref triple, ref message) =>
+                write!(fmt, "unknown target triple {}: {}", triple, message),
+            TargetError::NoTargetMachine(ref target) =>
+                write!(fmt, "target {} has no target machine", target),
+            TargetError::JitNotSupportedOnPlatform(ref target) =>
+                write!(fmt, "target {} does not support JIT compilation", target),
+            TargetError::InvalidModule(ref message) =>
+                write!(fmt, "invalid module: {}", message),
+            TargetError::EngineCreation(ref message) =>
+                write!(fmt, "failed to create execution engine: {}", message)
+        }
+    }
+}
+impl error::Error for TargetError {
+    fn description(&self) -> &str {
+        match *self {
+            TargetError::NativeTargetUnavailable => "native target unavailable",
+            TargetError::TargetNotRegistered(_) => "target not registered",
+            TargetError::UnknownTriple(_, _) => "unknown target triple",
+            TargetError::NoTargetMachine(_) => "no target machine",
+            TargetError::JitNotSupportedOnPlatform(_) => "JIT not supported on platform",
+            TargetError::InvalidModule(_) => "invalid module",
+            TargetError::EngineCreation(_) => "execution engine creation failed"
+        }
+    }
+}
+
+/// A set of target features to enable or disable, like `+avx2,-sse4.1`.
+///
+/// Later changes to a feature override earlier ones, so the feature string this makes never
+/// enables and disables the same feature.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TargetFeatures {
+    features: Vec<(String, bool)>
+}
+impl TargetFeatures {
+    /// Make an empty set of features, which uses the defaults of the target CPU.
+    pub fn new() -> TargetFeatures {
+        TargetFeatures {
+            features: Vec::new()
+        }
+    }
+    /// Parse a comma-separated feature string, where every feature is prefixed with `+` to
+    /// enable it or `-` to disable it, or return the first malformed feature.
+    pub fn parse(features: &str) -> Result<TargetFeatures, InvalidFeature> {
+        let mut parsed = TargetFeatures::new();
+        for feature in features.split(',').filter(|feature| !feature.is_empty()) {
+            let (name, enabled) = match feature.chars().next() {
+                Some('+') => (&feature[1..], true),
+                Some('-') => (&feature[1..], false),
+                _ => return Err(InvalidFeature(feature.to_owned()))
+            };
+            try!(validate(name));
+            parsed.set(name, enabled);
+        }
+        Ok(parsed)
+    }
+    /// Returns the features that were enabled when this crate was compiled, which the host
+    /// is guaranteed to support.
+    ///
+    /// This only knows about the common x86 and AArch64 SIMD features, and doesn't detect
+    /// features the host CPU has beyond those.
+    pub fn from_host() -> TargetFeatures {
+        let known = [
+            ("sse", cfg!(target_feature = "sse")),
+            ("sse2", cfg!(target_feature = "sse2")),
+            ("sse3", cfg!(target_feature = "sse3")),
+            ("ssse3", cfg!(target_feature = "ssse3")),
+            ("sse4.1", cfg!(target_feature = "sse4.1")),
+            ("sse4.2", cfg!(target_feature = "sse4.2")),
+            ("avx", cfg!(target_feature = "avx")),
+            ("avx2", cfg!(target_feature = "avx2")),
+            ("fma", cfg!(target_feature = "fma")),
+            ("popcnt", cfg!(target_feature = "popcnt")),
+            ("neon", cfg!(target_feature = "neon"))
+        ];
+        let mut features = TargetFeatures::new();
+        for &(name, enabled) in &known {
+            if enabled {
+                features.set(name, true);
+            }
+        }
+        features
+    }
+    /// Enable the feature given, which may be prefixed with `+`.
+    ///
+    /// This panics if the feature name is malformed.
+    pub fn add(mut self, feature: &str) -> TargetFeatures {
+        let name = if feature.starts_with('+') { &feature[1..] } else { feature };
+        validate(name).unwrap();
+        self.set(name, true);
+        self
+    }
+    /// Disable the feature given, which may be prefixed with `-`.
+    ///
+    /// This panics if the feature name is malformed.
+    pub fn remove(mut self, feature: &str) -> TargetFeatures {
+        let name = if feature.starts_with('-') { &feature[1..] } else { feature };
+        validate(name).unwrap();
+        self.set(name, false);
+        self
+    }
+    /// Returns `Some(true)` if the feature given is enabled, `Some(false)` if it is disabled,
+    /// or `None` if it is left to the target CPU.
+    pub fn is_enabled(&self, feature: &str) -> Option<bool> {
+        self.features.iter().find(|&&(ref name, _)| name == feature).map(|&(_, enabled)| enabled)
+    }
+    /// Returns the names of the features this enables, without their `+` prefix.
+    pub fn get_enabled(&self) -> Vec<&str> {
+        self.features.iter().filter(|&&(_, enabled)| enabled).map(|&(ref name, _)| &**name).collect()
+    }
+    fn set(&mut self, name: &str, enabled: bool) {
+        self.features.retain(|&(ref other, _)| other != name);
+        self.features.push((name.to_owned(), enabled));
+    }
+}
+impl fmt::Display for TargetFeatures {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (index, &(ref name, enabled)) in self.features.iter().enumerate() {
+            if index > 0 {
+                try!(fmt.write_str(","));
+            }
+            try!(write!(fmt, "{}{}", if enabled { '+' } else { '-' }, name));
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when a target feature is malformed.
+///
+/// LLVM only warns about features that are well-formed but unknown to the target when a
+/// target machine is created, so those aren't detected here.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidFeature(pub String);
+impl fmt::Display for InvalidFeature {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid target feature {:?}", self.0)
+    }
+}
+impl error::Error for InvalidFeature {
+    fn description(&self) -> &str {
+        "invalid target feature"
+    }
+}
+
+/// Check that `name` is a well-formed feature name without a prefix.
+fn validate(name: &str) -> Result<(), InvalidFeature> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_digit(36) || c == '.' || c == '-' || c == '_');
+    if valid && !name.starts_with('-') {
+        Ok(())
+    } else {
+        Err(InvalidFeature(name.to_owned()))
+    }
+}
+
+/// The relocation model of the code a target machine generates.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RelocMode {
+    /// Use the default model of the target.
+    Default,
+    /// Generate code that must be loaded at a fixed address.
+    Static,
+    /// Generate position-independent code.
+    PIC,
+    /// Generate code that is dynamic but not position-independent itself.
+    DynamicNoPIC
+}
+impl From<RelocMode> for LLVMRelocMode {
+    fn from(reloc: RelocMode) -> LLVMRelocMode {
+        match reloc {
+            RelocMode::Default => LLVMRelocMode::LLVMRelocDefault,
+            RelocMode::Static => LLVMRelocMode::LLVMRelocStatic,
+            RelocMode::PIC => LLVMRelocMode::LLVMRelocPIC,
+            RelocMode::DynamicNoPIC => LLVMRelocMode::LLVMRelocDynamicNoPic
+        }
+    }
+}
+
+/// The settings needed to generate code for a common target, such as a bare-metal target
+/// without an operating system.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TargetPreset {
+    /// The target triple.
+    pub triple: &'static str,
+    /// The data layout string of the target.
+    pub data_layout: &'static str,
+    /// The CPU to generate code for.
+    pub cpu: &'static str,
+    /// The target features to enable or disable.
+    pub features: &'static str,
+    /// The relocation model.
+    pub reloc: RelocMode,
+    /// The width in bits of the widest integer the target can operate on atomically, for use
+    /// as `AtomicOptions::max_width`.
+    pub max_atomic_width: usize
+}
+impl TargetPreset {
+    /// Set the triple and data layout of `module` to this preset's, and stop LLVM from turning
+    /// loops into calls to `memcpy`, `memset` and other C library functions in its functions,
+    /// since bare-metal targets don't have a C library.
+    ///
+    /// This should be done before the functions are optimized, since it only affects functions
+    /// that are already in `module`.
+    pub fn apply(&self, module: &Module) {
+        module.set_target(self.triple);
+        module.set_data_layout(self.data_layout);
+        for function in module {
+            function.add_target_attribute("no-builtins", "");
+        }
+    }
+}
+
+/// ARM Cortex-M4 and Cortex-M7 microcontrollers without an operating system, using the soft
+/// float calling convention.
+pub const THUMBV7EM_NONE_EABI: TargetPreset = TargetPreset {
+    triple: "thumbv7em-none-eabi",
+    data_layout: "e-m:e-p:32:32-i64:64-v128:64:128-a:0:32-n32-S64",
+    cpu: "cortex-m4",
+    features: "",
+    reloc: RelocMode::Static,
+    max_atomic_width: 32
+};
+
+/// How floating-point values are passed between functions and computed on ARM targets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FloatAbi {
+    /// Pass floats in integer registers and compute them with library calls, for CPUs without an FPU.
+    Soft,
+    /// Pass floats in integer registers but compute them with FPU instructions, which is compatible
+    /// with code built for `Soft`.
+    SoftFP,
+    /// Pass floats in FPU registers and compute them with FPU instructions.
+    Hard
+}
+impl FloatAbi {
+    /// Returns `triple` with its environment changed to the one that selects the calling
+    /// convention of this ABI, which is `eabihf` for `Hard` and `eabi` otherwise.
+    ///
+    /// The calling convention for floats is picked by the triple, so this must be used for the
+    /// triple of both the target machine and the module. Triples without an `eabi` or `eabihf`
+    /// environment are returned unchanged.
+    pub fn adjust_triple(self, triple: &str) -> String {
+        let base = if triple.ends_with("eabihf") {
+            &triple[..triple.len() - 2]
+        } else if triple.ends_with("eabi") {
+            triple
+        } else {
+            return triple.to_owned()
+        };
+        match self {
+            FloatAbi::Hard => format!("{}hf", base),
+            FloatAbi::Soft | FloatAbi::SoftFP => base.to_owned()
+        }
+    }
+}
+
+/// How a function treats denormal floating-point values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DenormalMode {
+    /// Handle denormals as IEEE 754 says.
+    IEEE,
+    /// Flush denormals to zero, keeping their sign.
+    PreserveSign,
+    /// Flush denormals to positive zero.
+    PositiveZero
+}
+impl DenormalMode {
+    /// Returns the value of the `denormal-fp-math` attribute for this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DenormalMode::IEEE => "ieee",
+            DenormalMode::PreserveSign => "preserve-sign",
+            DenormalMode::PositiveZero => "positive-zero"
+        }
+    }
+}
+
+/// The assumptions about floating-point values that a function's code can be optimized with,
+/// which numeric kernels need to be vectorized.
+///
+/// LLVM's C API can't set fast-math flags on individual instructions, so these apply to
+/// whole functions through `Function::set_fast_math`, which the backend uses when selecting
+/// instructions.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FastMathFlags {
+    /// Assume no value is NaN.
+    pub no_nans: bool,
+    /// Assume no value is infinite.
+    pub no_infs: bool,
+    /// Treat the sign of zero as insignificant.
+    pub no_signed_zeros: bool,
+    /// Allow multiplying by the reciprocal instead of dividing.
+    pub allow_reciprocal: bool,
+    /// Allow fusing a multiply and an add into one instruction.
+    pub allow_contract: bool,
+    /// Allow reassociating operations, which changes how they round.
+    pub allow_reassoc: bool
+}
+impl FastMathFlags {
+    /// Returns the flags that allow every assumption, like `-ffast-math` in C compilers.
+    pub fn fast() -> FastMathFlags {
+        FastMathFlags {
+            no_nans: true,
+            no_infs: true,
+            no_signed_zeros: true,
+            allow_reciprocal: true,
+            allow_contract: true,
+            allow_reassoc: true
+        }
+    }
+    /// Returns true if every assumption is allowed.
+    pub fn is_fast(&self) -> bool {
+        *self == FastMathFlags::fast()
+    }
+}
@@ -0,0 +1,268 @@
+//! Utilities for testing code that builds, optimizes or runs LLVM IR.
+
+use cbox::CSemiBox;
+use block::BasicBlock;
+use builder::Builder;
+use compile::Compile;
+use context::{Context, GetContext};
+use module::Module;
+use types::{FunctionType, Type};
+use value::{Function, IntPredicate, Value};
+
+/// The predicates that random comparisons are picked from.
+const PREDICATES: [IntPredicate; 10] = [
+    IntPredicate::Equal,
+    IntPredicate::NotEqual,
+    IntPredicate::UnsignedGreaterThan,
+    IntPredicate::UnsignedGreaterThanOrEqual,
+    IntPredicate::UnsignedLessThan,
+    IntPredicate::UnsignedLessThanOrEqual,
+    IntPredicate::SignedGreaterThan,
+    IntPredicate::SignedGreaterThanOrEqual,
+    IntPredicate::SignedLessThan,
+    IntPredicate::SignedLessThanOrEqual
+];
+
+/// How big the modules made by `random_module` are.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RandomConfig {
+    /// The number of functions in the module.
+    pub num_functions: usize,
+    /// The most parameters a function can have.
+    pub max_params: usize,
+    /// The most blocks a function can have.
+    pub max_blocks: usize,
+    /// The most instructions a block can have, not counting its terminator and the loads
+    /// of its operands.
+    pub max_instructions: usize
+}
+impl Default for RandomConfig {
+    fn default() -> RandomConfig {
+        RandomConfig {
+            num_functions: 4,
+            max_params: 3,
+            max_blocks: 6,
+            max_instructions: 8
+        }
+    }
+}
+
+/// Make a module of random functions that passes the verifier, which is the same for the same
+/// seed and configuration.
+///
+/// Every function takes and returns `i32`s, and is named `f` followed by its index. Its
+/// blocks only branch forwards, so it always returns, and it only calls the functions before
+/// it, so none of them recurse. Values are passed between blocks through allocas, so running
+/// `mem2reg` or the optimizer on the module exercises much more of LLVM than the builder does.
+/// The instructions can't have undefined behaviour, so the functions can be run in a JIT and
+/// compared before and after optimization.
+///
+/// ```rust
+/// use llvm::*;
+/// use llvm::testing::*;
+/// let ctx = Context::new();
+/// let module = random_module(&ctx, 42, &RandomConfig::default());
+/// module.verify().unwrap();
+/// ```
+pub fn random_module<'a>(context: &'a Context, seed: u64, config: &RandomConfig) -> CSemiBox<'a, Module> {
+    let module = Module::new(&format!("random{}", seed), context);
+    fill_module(&module, &mut Rng::new(seed), config);
+    module
+}
+
+fn fill_module(module: &Module, rng: &mut Rng, config: &RandomConfig) {
+    let context = module.get_context();
+    let int = Type::get::<i32>(context);
+    let builder = Builder::new(context);
+    let mut functions: Vec<&Function> = Vec::new();
+    for index in 0..config.num_functions {
+        let params = vec![int; rng.below(config.max_params + 1)];
+        let func = module.add_function(&format!("f{}", index), FunctionType::new(int, &params));
+        FunctionGen {
+            context: context,
+            builder: &builder,
+            rng: &mut *rng,
+            slots: Vec::new(),
+            values: Vec::new()
+        }.build(func, &functions, config);
+        functions.push(func);
+    }
+}
+
+/// Check that `module` is valid and comes back the same after being printed as textual IR,
+/// parsed into a new context and verified again, or return a description of the first difference.
+///
+/// The parser only accepts what LLVM itself can encode, so this catches wrapper code that
+/// builds something the verifier misses, or that prints in a way LLVM can't read back. A value
+/// the wrapper encodes wrongly, like the wrong predicate, still prints and parses the same, so
+/// use `check_ir` to compare against the IR that was meant to be built.
+pub fn check_round_trip(module: &Module) -> Result<(), String> {
+    try!(module.verify().map_err(|err| format!("the module is invalid: {}", &*err)));
+    let printed = format!("{:?}", module);
+    let context = Context::new();
+    let parsed = try!(Module::parse_ir(&context, &printed).map_err(|err| {
+        format!("the printed module doesn't parse: {}", &*err)
+    }));
+    try!(parsed.verify().map_err(|err| format!("the parsed module is invalid: {}", &*err)));
+    compare_lines(&printed, &format!("{:?}", parsed))
+}
+
+/// Panic with the difference if `module` doesn't come back the same after being printed
+/// and parsed, as `check_round_trip` checks.
+pub fn assert_round_trip(module: &Module) {
+    if let Err(err) = check_round_trip(module) {
+        panic!("the module doesn't round-trip: {}", err)
+    }
+}
+
+/// Check that `module` is valid and is the same as the module the textual IR `expected`
+/// describes, or return a description of the first difference.
+///
+/// `expected` is parsed and printed again before comparing, so it only needs to mean the same
+/// thing as the printed module, not be formatted the same. The names of values, blocks and
+/// types are compared too, so they must match the ones the module was built with.
+pub fn check_ir(module: &Module, expected: &str) -> Result<(), String> {
+    try!(module.verify().map_err(|err| format!("the module is invalid: {}", &*err)));
+    let context = Context::new();
+    let expected = try!(Module::parse_ir(&context, expected).map_err(|err| {
+        format!("the expected IR doesn't parse: {}", &*err)
+    }));
+    try!(expected.verify().map_err(|err| format!("the expected IR is invalid: {}", &*err)));
+    compare_lines(&format!("{:?}", expected), &format!("{:?}", module))
+}
+
+/// Panic with the difference if `module` isn't the module `expected` describes, as `check_ir`
+/// checks.
+pub fn assert_ir(module: &Module, expected: &str) {
+    if let Err(err) = check_ir(module, expected) {
+        panic!("the module isn't the expected IR: {}", err)
+    }
+}
+
+/// Compare the printed modules `before` and `after` line by line, or return a description of
+/// the first difference.
+fn compare_lines(before: &str, after: &str) -> Result<(), String> {
+    // the module identifier is the name of the module or the buffer it was parsed from
+    let old_lines = before.lines().filter(|line| !line.starts_with("; ModuleID"));
+    let new_lines = after.lines().filter(|line| !line.starts_with("; ModuleID"));
+    for (number, (old, new)) in old_lines.zip(new_lines).enumerate() {
+        if old != new {
+            return Err(format!("line {} should be `{}` but is `{}`", number + 1, old, new));
+        }
+    }
+    let (old_count, new_count) = (before.lines().count(), after.lines().count());
+    if old_count != new_count {
+        return Err(format!("the module should have {} lines but has {}", old_count, new_count));
+    }
+    Ok(())
+}
+
+/// The state of generating a random function.
+struct FunctionGen<'a, 'b> {
+    context: &'a Context,
+    builder: &'a Builder,
+    rng: &'b mut Rng,
+    /// The allocas that values are passed between blocks in.
+    slots: Vec<&'a Value>,
+    /// The values computed in the current block.
+    values: Vec<&'a Value>
+}
+impl<'a, 'b> FunctionGen<'a, 'b> {
+    fn build(&mut self, func: &Function, callees: &[&Function], config: &RandomConfig) {
+        let int = Type::get::<i32>(self.context);
+        let num_blocks = 1 + self.rng.below(config.max_blocks.max(1));
+        let blocks: Vec<&BasicBlock> = (0..num_blocks).map(|index| func.append(&format!("b{}", index))).collect();
+        self.builder.position_at_end(blocks[0]);
+        let num_params = func.get_signature().num_params();
+        for index in 0..num_params + 2 {
+            let slot = self.builder.build_alloca(int);
+            let init: &Value = if index < num_params { &func[index] } else { (self.rng.next() as i32).compile(self.context) };
+            self.builder.build_store(init, slot);
+            self.slots.push(slot);
+        }
+        for (index, &block) in blocks.iter().enumerate() {
+            self.builder.position_at_end(block);
+            self.values.clear();
+            for _ in 0..1 + self.rng.below(config.max_instructions.max(1)) {
+                let value = self.instruction(callees);
+                self.values.push(value);
+                if self.rng.below(2) == 0 {
+                    let slot = self.slots[self.rng.below(self.slots.len())];
+                    self.builder.build_store(value, slot);
+                }
+            }
+            if index + 1 == num_blocks {
+                let value = self.operand();
+                self.builder.build_ret(value);
+            } else {
+                let target = blocks[index + 1 + self.rng.below(num_blocks - index - 1)];
+                if self.rng.below(2) == 0 {
+                    self.builder.build_br(target);
+                } else {
+                    let other = blocks[index + 1 + self.rng.below(num_blocks - index - 1)];
+                    let cond = self.comparison();
+                    self.builder.build_cond_br(cond, target, Some(other));
+                }
+            }
+        }
+    }
+    /// Build a random instruction that yields an `i32`.
+    fn instruction(&mut self, callees: &[&Function]) -> &'a Value {
+        let builder = self.builder;
+        let choices = if callees.is_empty() { 7 } else { 8 };
+        match self.rng.below(choices) {
+            0 => builder.build_add(self.operand(), self.operand()),
+            1 => builder.build_sub(self.operand(), self.operand()),
+            2 => builder.build_mul(self.operand(), self.operand()),
+            3 => builder.build_and(self.operand(), self.operand()),
+            4 => builder.build_or(self.operand(), self.operand()),
+            5 => {
+                // shifting by the width or more is poison, so mask the amount
+                let amount = builder.build_and(self.operand(), 31i32.compile(self.context));
+                builder.build_lshr(self.operand(), amount)
+            },
+            6 => {
+                let cond = self.comparison();
+                builder.build_select(cond, self.operand(), self.operand())
+            },
+            _ => {
+                let callee = callees[self.rng.below(callees.len())];
+                let args: Vec<&Value> = (0..callee.get_signature().num_params()).map(|_| self.operand()).collect();
+                builder.build_call(callee, &args)
+            }
+        }
+    }
+    /// Build a random comparison of two operands.
+    fn comparison(&mut self) -> &'a Value {
+        let builder = self.builder;
+        let pred = PREDICATES[self.rng.below(PREDICATES.len())];
+        builder.build_icmp(self.operand(), self.operand(), pred)
+    }
+    /// Returns a value computed earlier in the current block, the value of a slot, or a constant.
+    fn operand(&mut self) -> &'a Value {
+        match self.rng.below(3) {
+            0 if !self.values.is_empty() => self.values[self.rng.below(self.values.len())],
+            1 => self.builder.build_load(self.slots[self.rng.below(self.slots.len())]),
+            _ => ((self.rng.next() % 256) as i32 - 128).compile(self.context)
+        }
+    }
+}
+
+/// A xorshift random number generator, so the modules made don't depend on a platform's.
+struct Rng(u64);
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // xorshift never leaves zero
+        Rng(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    /// Returns a number less than `bound`, which must not be zero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
@@ -5,6 +5,7 @@ use compile::Compile;
 use context::{Context, GetContext};
 use target::TargetData;
 use util::{self, Sub};
+use value::Value;
 use std::{fmt, mem};
 use std::marker::PhantomData;
 use std::iter::Iterator;
@@ -83,6 +84,79 @@ impl Type {
     pub fn get_size(&self, target: &TargetData) -> usize {
         unsafe { target::LLVMABISizeOfType(target.into(), self.into()) as usize }
     }
+    /// Returns the kind of this type.
+    ///
+    /// ```rust
+    /// use llvm::*;
+    /// let context = Context::new();
+    /// assert_eq!(Type::get::<f64>(&context).kind(), TypeKind::Double);
+    /// assert_eq!(Type::get::<(u8, u8)>(&context).kind(), TypeKind::Struct);
+    /// ```
+    pub fn kind(&self) -> TypeKind {
+        match unsafe { core::LLVMGetTypeKind(self.into()) } {
+            LLVMTypeKind::LLVMVoidTypeKind => TypeKind::Void,
+            LLVMTypeKind::LLVMHalfTypeKind => TypeKind::Half,
+            LLVMTypeKind::LLVMFloatTypeKind => TypeKind::Float,
+            LLVMTypeKind::LLVMDoubleTypeKind => TypeKind::Double,
+            LLVMTypeKind::LLVMX86_FP80TypeKind => TypeKind::X86FP80,
+            LLVMTypeKind::LLVMFP128TypeKind => TypeKind::FP128,
+            LLVMTypeKind::LLVMPPC_FP128TypeKind => TypeKind::PPCFP128,
+            LLVMTypeKind::LLVMLabelTypeKind => TypeKind::Label,
+            LLVMTypeKind::LLVMIntegerTypeKind => TypeKind::Integer,
+            LLVMTypeKind::LLVMFunctionTypeKind => TypeKind::Function,
+            LLVMTypeKind::LLVMStructTypeKind => TypeKind::Struct,
+            LLVMTypeKind::LLVMArrayTypeKind => TypeKind::Array,
+            LLVMTypeKind::LLVMPointerTypeKind => TypeKind::Pointer,
+            LLVMTypeKind::LLVMVectorTypeKind => TypeKind::Vector,
+            LLVMTypeKind::LLVMMetadataTypeKind => TypeKind::Metadata,
+            LLVMTypeKind::LLVMX86_MMXTypeKind => TypeKind::X86MMX,
+            LLVMTypeKind::LLVMTokenTypeKind => TypeKind::Token
+        }
+    }
+}
+impl fmt::Display for Type {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, fmt)
+    }
+}
+
+/// The kind of a type.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum TypeKind {
+    /// The type of instructions and functions that don't produce a value.
+    Void,
+    /// A 16-bit floating-point number.
+    Half,
+    /// A 32-bit floating-point number.
+    Float,
+    /// A 64-bit floating-point number.
+    Double,
+    /// An 80-bit x87 floating-point number.
+    X86FP80,
+    /// A 128-bit floating-point number.
+    FP128,
+    /// A 128-bit PowerPC floating-point number, made of two doubles.
+    PPCFP128,
+    /// The type of basic block labels.
+    Label,
+    /// An integer of any width.
+    Integer,
+    /// A function signature.
+    Function,
+    /// A struct.
+    Struct,
+    /// A fixed-length array.
+    Array,
+    /// A pointer.
+    Pointer,
+    /// A SIMD vector.
+    Vector,
+    /// The type of metadata.
+    Metadata,
+    /// An x86 MMX vector.
+    X86MMX,
+    /// The type of tokens, which can't be stored or used in phis.
+    Token
 }
 
 /// A structure type, such as a tuple or struct.
@@ -137,6 +211,15 @@ impl FunctionType {
     pub fn new<'a>(ret: &'a Type, args: &[&'a Type]) -> &'a FunctionType {
         unsafe { core::LLVMFunctionType(ret.into(), args.as_ptr() as *mut LLVMTypeRef, args.len() as c_uint, 0) }.into()
     }
+    /// Make a new function signature with the return type and arguments given, which takes
+    /// any number of extra arguments after them like `printf` does.
+    pub fn new_variadic<'a>(ret: &'a Type, args: &[&'a Type]) -> &'a FunctionType {
+        unsafe { core::LLVMFunctionType(ret.into(), args.as_ptr() as *mut LLVMTypeRef, args.len() as c_uint, 1) }.into()
+    }
+    /// Returns true if this signature takes extra arguments after its parameters.
+    pub fn is_variadic(&self) -> bool {
+        unsafe { core::LLVMIsFunctionVarArg(self.into()) != 0 }
+    }
     /// Returns the number of parameters this signature takes.
     pub fn num_params(&self) -> usize {
         unsafe { core::LLVMCountParamTypes(self.into()) as usize }
@@ -171,6 +254,10 @@ impl PointerType {
     pub fn get_element(&self) -> &Type {
         unsafe { mem::transmute(core::LLVMGetElementType(self.into())) }
     }
+    /// Returns the null pointer of this type.
+    pub fn const_null(&self) -> &Value {
+        unsafe { core::LLVMConstPointerNull(self.into()) }.into()
+    }
 }
 
 /// An integer type.
@@ -178,7 +265,7 @@ pub struct IntegerType(PhantomData<[u8]>);
 native_ref!{&IntegerType = LLVMTypeRef}
 get_context!{IntegerType, LLVMGetTypeContext}
 to_str!{IntegerType, LLVMPrintTypeToString}
-sub!{IntegerType, LLVMPointerTypeKind}
+sub!{IntegerType, LLVMIntegerTypeKind}
 impl IntegerType {
     /// Make a new integer type that will be the size of the given number of bits.
     pub fn new(context: &Context, numbits: usize) -> &IntegerType {
@@ -188,6 +275,42 @@ impl IntegerType {
     pub fn get_width(&self) -> usize {
         unsafe { core::LLVMGetIntTypeWidth (self.into()) as usize }
     }
+    /// Returns the constant integer of this type with the value given, truncated to its width.
+    pub fn const_int(&self, value: u64) -> &Value {
+        unsafe { core::LLVMConstInt(self.into(), value, 0) }.into()
+    }
+    /// Returns the constant integer of this type with the signed value given, sign-extended or
+    /// truncated to its width.
+    pub fn const_signed(&self, value: i64) -> &Value {
+        unsafe { core::LLVMConstInt(self.into(), value as u64, 1) }.into()
+    }
+    /// Returns the constant integer of this type with every bit set.
+    pub fn const_all_ones(&self) -> &Value {
+        unsafe { core::LLVMConstAllOnes(self.into()) }.into()
+    }
+}
+
+/// A floating-point type.
+pub struct RealType(PhantomData<[u8]>);
+native_ref!{&RealType = LLVMTypeRef}
+get_context!{RealType, LLVMGetTypeContext}
+to_str!{RealType, LLVMPrintTypeToString}
+deref!{RealType, Type}
+unsafe impl Sub<Type> for RealType {
+    fn is(ty: &Type) -> bool {
+        let kind = unsafe { core::LLVMGetTypeKind(ty.into()) };
+        match kind {
+            LLVMTypeKind::LLVMHalfTypeKind | LLVMTypeKind::LLVMFloatTypeKind | LLVMTypeKind::LLVMDoubleTypeKind |
+            LLVMTypeKind::LLVMX86_FP80TypeKind | LLVMTypeKind::LLVMFP128TypeKind | LLVMTypeKind::LLVMPPC_FP128TypeKind => true,
+            _ => false
+        }
+    }
+}
+impl RealType {
+    /// Returns the constant of this type closest to the value given.
+    pub fn const_float(&self, value: f64) -> &Value {
+        unsafe { core::LLVMConstReal(self.into(), value) }.into()
+    }
 }
 
 /// A vector type.
@@ -0,0 +1,247 @@
+use libc::{c_char, c_void, size_t};
+use ffi::core;
+use ffi::execution_engine::{self as engine, LLVMExecutionEngineRef};
+use ffi::object;
+use std::collections::{HashMap, HashSet};
+use std::{fmt, ptr, slice};
+use util;
+use engine::ExecutionEngine;
+use module::Module;
+use value::Function;
+
+extern "C" {
+    fn __register_frame(begin: *const c_void);
+    fn __deregister_frame(begin: *const c_void);
+    static __jit_debug_descriptor: JitDescriptor;
+}
+
+/// An object file registered with the GDB JIT interface.
+#[repr(C)]
+#[allow(dead_code)]
+struct JitCodeEntry {
+    next: *const JitCodeEntry,
+    prev: *const JitCodeEntry,
+    symfile_addr: *const c_char,
+    symfile_size: u64
+}
+/// The list of objects registered with the GDB JIT interface, which LLVM defines.
+#[repr(C)]
+#[allow(dead_code)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *const JitCodeEntry,
+    first_entry: *const JitCodeEntry
+}
+
+/// Register the `.eh_frame` section starting at `eh_frame` with the host unwinder.
+///
+/// This lets the host unwinder walk through frames of JIT-compiled code, which is needed for
+/// backtraces and for unwinding panics through JIT code.
+///
+/// This is marked as unsafe because `eh_frame` must point to a valid, terminated `.eh_frame`
+/// section that stays alive until it is deregistered.
+pub unsafe fn register_frame(eh_frame: *const c_void) {
+    __register_frame(eh_frame)
+}
+/// Deregister the `.eh_frame` section starting at `eh_frame` from the host unwinder.
+///
+/// This is marked as unsafe because `eh_frame` must have been registered with `register_frame`.
+pub unsafe fn deregister_frame(eh_frame: *const c_void) {
+    __deregister_frame(eh_frame)
+}
+
+/// A single frame of a backtrace that may mix host and JIT-compiled code.
+#[derive(Copy, Clone)]
+pub enum Frame<'a> {
+    /// A frame inside a function compiled by the JIT.
+    Jit {
+        /// The function this frame is inside.
+        function: &'a Function,
+        /// The offset of the address from the start of the function, in bytes.
+        offset: usize
+    },
+    /// A frame outside of any JIT-compiled function.
+    Host(usize)
+}
+impl<'a> fmt::Display for Frame<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Frame::Jit { function, offset } =>
+                write!(fmt, "{}+{:#x}", function.get_name().unwrap_or("<unnamed>"), offset),
+            Frame::Host(address) =>
+                write!(fmt, "{:#x}", address)
+        }
+    }
+}
+
+/// The machine code address and size of a JIT-compiled function.
+#[derive(Copy, Clone)]
+pub struct CodeSize<'a> {
+    /// The function that was compiled.
+    pub function: &'a Function,
+    /// The start address of the function's machine code.
+    pub address: usize,
+    /// The size of the function's machine code in bytes, or `None` if it isn't known.
+    ///
+    /// This is the size of the function's symbol in the object the JIT loaded, so it doesn't
+    /// include any padding after the function. It is only known on hosts where the JIT emits
+    /// ELF objects, which it registers with debuggers along with their symbol sizes.
+    pub size: Option<usize>
+}
+impl<'a> CodeSize<'a> {
+    /// Returns the relocated machine code of the function, as it is in memory, or `None` if
+    /// its size isn't known.
+    ///
+    /// This is marked as unsafe because the engine that compiled the function must still be
+    /// alive.
+    pub unsafe fn as_bytes(&self) -> Option<&'a [u8]> {
+        self.size.map(|size| slice::from_raw_parts(self.address as *const u8, size))
+    }
+}
+impl<'a> fmt::Display for CodeSize<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.function.get_name().unwrap_or("<unnamed>");
+        match self.size {
+            Some(size) => write!(fmt, "{:#x} {:>8} {}", self.address, size, name),
+            None => write!(fmt, "{:#x} {:>8} {}", self.address, "?", name)
+        }
+    }
+}
+
+/// A table of the machine code addresses of JIT-compiled functions, used to symbolize
+/// backtraces that run through JIT frames.
+pub struct FrameTable<'a> {
+    functions: Vec<(usize, &'a Function)>,
+    symbols: JitSymbols
+}
+impl<'a> FrameTable<'a> {
+    /// Collect the addresses of every function defined in `modules` that `ee` has compiled.
+    pub fn new<E>(ee: &'a E, modules: &[&'a Module]) -> FrameTable<'a> where E: ExecutionEngine<'a>, LLVMExecutionEngineRef: From<&'a E> {
+        let mut functions = Vec::new();
+        for module in modules {
+            for function in *module {
+                if function.is_declaration() {
+                    continue;
+                }
+                let address = unsafe { engine::LLVMGetPointerToGlobal(ee.into(), function.into()) } as usize;
+                if address != 0 {
+                    functions.push((address, function));
+                }
+            }
+        }
+        functions.sort_by(|a, b| a.0.cmp(&b.0));
+        FrameTable {
+            functions: functions,
+            symbols: unsafe { jit_symbols() }
+        }
+    }
+    /// Returns the start address of the function given, or `None` if it isn't in this table.
+    pub fn get_address(&self, function: &Function) -> Option<usize> {
+        self.functions.iter().find(|&&(_, func)| func == function).map(|&(address, _)| address)
+    }
+    /// Iterate through the functions in this table and their start addresses, in address order.
+    pub fn functions(&self) -> slice::Iter<(usize, &'a Function)> {
+        self.functions.iter()
+    }
+    /// Returns the address and size of every function in this table, in address order.
+    ///
+    /// This should be taken after the engine has finalized its code, and can be printed one
+    /// line per function to track code size across builds.
+    pub fn code_sizes(&self) -> Vec<CodeSize<'a>> {
+        self.functions.iter().map(|&(address, function)| CodeSize {
+            function: function,
+            address: address,
+            size: self.symbols.sizes.get(&address).cloned()
+        }).collect()
+    }
+    /// Find the JIT-compiled function containing `address`.
+    ///
+    /// This picks the function with the nearest start address below `address`, if `address` is
+    /// inside it. Where the sizes of functions aren't known, a function is assumed to end where
+    /// the next one starts, and addresses after the last one are treated as host addresses.
+    pub fn symbolize(&self, address: usize) -> Frame<'a> {
+        let index = match self.functions.binary_search_by(|&(start, _)| start.cmp(&address)) {
+            Ok(index) => index,
+            Err(0) => return Frame::Host(address),
+            Err(index) => index - 1
+        };
+        let (start, function) = self.functions[index];
+        let end = match self.symbols.sizes.get(&start) {
+            Some(&size) => Some(start + size),
+            None => self.functions.get(index + 1).map(|&(next, _)| next)
+        };
+        match end {
+            Some(end) if address < end => Frame::Jit {
+                function: function,
+                offset: address - start
+            },
+            _ => Frame::Host(address)
+        }
+    }
+    /// Returns true if `function` was registered with debuggers in an object that carries a
+    /// DWARF line table, so GDB and LLDB can set breakpoints on the lines of its source.
+    ///
+    /// The module has to carry debug info metadata for this, since the 3.8 C API can't build
+    /// it. Like the sizes of functions, this is only known on hosts where the JIT emits ELF.
+    pub fn has_debug_info(&self, function: &Function) -> bool {
+        self.get_address(function).map(|address| self.symbols.line_tables.contains(&address)).unwrap_or(false)
+    }
+    /// Symbolize every address in a backtrace.
+    pub fn symbolize_trace(&self, addresses: &[usize]) -> Vec<Frame<'a>> {
+        addresses.iter().map(|&address| self.symbolize(address)).collect()
+    }
+}
+
+/// What the objects the JIT has registered with the GDB JIT interface say about the symbols
+/// they define, by the address each symbol was loaded at.
+struct JitSymbols {
+    /// The size of every symbol with a known size.
+    sizes: HashMap<usize, usize>,
+    /// Every symbol in an object with a `.debug_line` section.
+    line_tables: HashSet<usize>
+}
+
+/// Read the symbols of every object the JIT has registered with the GDB JIT interface.
+///
+/// Only the ELF objects LLVM registers have their sections' load addresses filled in, so this
+/// is empty on other hosts.
+unsafe fn jit_symbols() -> JitSymbols {
+    let mut symbols = JitSymbols {
+        sizes: HashMap::new(),
+        line_tables: HashSet::new()
+    };
+    let mut entry = ptr::read_volatile(&__jit_debug_descriptor.first_entry);
+    while !entry.is_null() {
+        let buffer = core::LLVMCreateMemoryBufferWithMemoryRange((*entry).symfile_addr, (*entry).symfile_size as size_t, b"jit\0".as_ptr() as *const c_char, 0);
+        // this takes ownership of the buffer, even if it fails
+        let file = object::LLVMCreateObjectFile(buffer);
+        if !file.is_null() {
+            let mut has_line_table = false;
+            let sections = object::LLVMGetSections(file);
+            while object::LLVMIsSectionIteratorAtEnd(file, sections) == 0 {
+                if util::to_null_str(object::LLVMGetSectionName(sections) as *mut c_char) == Some(".debug_line") {
+                    has_line_table = true;
+                }
+                object::LLVMMoveToNextSection(sections);
+            }
+            object::LLVMDisposeSectionIterator(sections);
+            let iter = object::LLVMGetSymbols(file);
+            while object::LLVMIsSymbolIteratorAtEnd(file, iter) == 0 {
+                let address = object::LLVMGetSymbolAddress(iter) as usize;
+                let size = object::LLVMGetSymbolSize(iter) as usize;
+                if size != 0 {
+                    symbols.sizes.insert(address, size);
+                }
+                if has_line_table && address != 0 {
+                    symbols.line_tables.insert(address);
+                }
+                object::LLVMMoveToNextSymbol(iter);
+            }
+            object::LLVMDisposeSymbolIterator(iter);
+            object::LLVMDisposeObjectFile(file);
+        }
+        entry = (*entry).next;
+    }
+    symbols
+}
@@ -1,13 +1,18 @@
 use libc::{c_char, c_uint, c_int};
-use ffi::prelude::LLVMValueRef;
+use ffi::prelude::{LLVMBasicBlockRef, LLVMValueRef};
 use ffi::{core, LLVMAttribute};
-use ffi::LLVMLinkage;
+use ffi::transforms::pass_manager_builder as pass_builder;
+use ffi::{LLVMDLLStorageClass, LLVMIntPredicate, LLVMLinkage, LLVMRealPredicate, LLVMVisibility};
 use std::ffi::CString;
 use std::{fmt, mem};
-use std::ops::{Deref, Index};
+use std::ops::{BitOr, Deref, Index};
 use std::marker::PhantomData;
 use block::{BasicBlock, BlockIter};
-use context::{Context, GetContext};
+use builder::Builder;
+use context::{Context, GetContext, MetadataKind};
+use frequency::BlockFrequencies;
+use module::Module;
+use target::{DenormalMode, FastMathFlags, FloatAbi, TargetFeatures};
 use types::{FunctionType, Type};
 use util::{self, Sub};
 
@@ -60,6 +65,16 @@ impl Value {
     pub fn new_undef<'a>(ty: &'a Type) -> &'a Value {
         unsafe { core::LLVMGetUndef(ty.into()).into() }
     }
+    /// Create a new constant expression that computes the address of a subelement of the
+    /// constant `pointer`, such as a field of a global variable.
+    pub fn new_gep<'a>(pointer: &'a Value, indices: &[&'a Value]) -> &'a Value {
+        unsafe { core::LLVMConstGEP(pointer.into(), indices.as_ptr() as *mut LLVMValueRef, indices.len() as c_uint).into() }
+    }
+    /// Create a new constant expression like `new_gep` that is also marked as staying in
+    /// bounds of the object `pointer` points into.
+    pub fn new_inbounds_gep<'a>(pointer: &'a Value, indices: &[&'a Value]) -> &'a Value {
+        unsafe { core::LLVMConstInBoundsGEP(pointer.into(), indices.as_ptr() as *mut LLVMValueRef, indices.len() as c_uint).into() }
+    }
     /// Returns the name of this value, or `None` if it lacks a name
     pub fn get_name(&self) -> Option<&str> {
         unsafe {
@@ -79,15 +94,191 @@ impl Value {
         unsafe { core::LLVMTypeOf(self.into()) }.into()
     }
 }
-/// Comparative operations on values.
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub enum Predicate {
+/// The ways integers can be compared.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IntPredicate {
     Equal,
     NotEqual,
-    GreaterThan,
-    GreaterThanOrEqual,
-    LessThan,
-    LessThanOrEqual
+    UnsignedGreaterThan,
+    UnsignedGreaterThanOrEqual,
+    UnsignedLessThan,
+    UnsignedLessThanOrEqual,
+    SignedGreaterThan,
+    SignedGreaterThanOrEqual,
+    SignedLessThan,
+    SignedLessThanOrEqual
+}
+impl From<IntPredicate> for LLVMIntPredicate {
+    fn from(pred: IntPredicate) -> LLVMIntPredicate {
+        match pred {
+            IntPredicate::Equal => LLVMIntPredicate::LLVMIntEQ,
+            IntPredicate::NotEqual => LLVMIntPredicate::LLVMIntNE,
+            IntPredicate::UnsignedGreaterThan => LLVMIntPredicate::LLVMIntUGT,
+            IntPredicate::UnsignedGreaterThanOrEqual => LLVMIntPredicate::LLVMIntUGE,
+            IntPredicate::UnsignedLessThan => LLVMIntPredicate::LLVMIntULT,
+            IntPredicate::UnsignedLessThanOrEqual => LLVMIntPredicate::LLVMIntULE,
+            IntPredicate::SignedGreaterThan => LLVMIntPredicate::LLVMIntSGT,
+            IntPredicate::SignedGreaterThanOrEqual => LLVMIntPredicate::LLVMIntSGE,
+            IntPredicate::SignedLessThan => LLVMIntPredicate::LLVMIntSLT,
+            IntPredicate::SignedLessThanOrEqual => LLVMIntPredicate::LLVMIntSLE
+        }
+    }
+}
+impl From<LLVMIntPredicate> for IntPredicate {
+    fn from(pred: LLVMIntPredicate) -> IntPredicate {
+        match pred {
+            LLVMIntPredicate::LLVMIntEQ => IntPredicate::Equal,
+            LLVMIntPredicate::LLVMIntNE => IntPredicate::NotEqual,
+            LLVMIntPredicate::LLVMIntUGT => IntPredicate::UnsignedGreaterThan,
+            LLVMIntPredicate::LLVMIntUGE => IntPredicate::UnsignedGreaterThanOrEqual,
+            LLVMIntPredicate::LLVMIntULT => IntPredicate::UnsignedLessThan,
+            LLVMIntPredicate::LLVMIntULE => IntPredicate::UnsignedLessThanOrEqual,
+            LLVMIntPredicate::LLVMIntSGT => IntPredicate::SignedGreaterThan,
+            LLVMIntPredicate::LLVMIntSGE => IntPredicate::SignedGreaterThanOrEqual,
+            LLVMIntPredicate::LLVMIntSLT => IntPredicate::SignedLessThan,
+            LLVMIntPredicate::LLVMIntSLE => IntPredicate::SignedLessThanOrEqual
+        }
+    }
+}
+impl IntPredicate {
+    /// Returns the predicate that is true exactly when this one is false.
+    pub fn inverse(self) -> IntPredicate {
+        match self {
+            IntPredicate::Equal => IntPredicate::NotEqual,
+            IntPredicate::NotEqual => IntPredicate::Equal,
+            IntPredicate::UnsignedGreaterThan => IntPredicate::UnsignedLessThanOrEqual,
+            IntPredicate::UnsignedGreaterThanOrEqual => IntPredicate::UnsignedLessThan,
+            IntPredicate::UnsignedLessThan => IntPredicate::UnsignedGreaterThanOrEqual,
+            IntPredicate::UnsignedLessThanOrEqual => IntPredicate::UnsignedGreaterThan,
+            IntPredicate::SignedGreaterThan => IntPredicate::SignedLessThanOrEqual,
+            IntPredicate::SignedGreaterThanOrEqual => IntPredicate::SignedLessThan,
+            IntPredicate::SignedLessThan => IntPredicate::SignedGreaterThanOrEqual,
+            IntPredicate::SignedLessThanOrEqual => IntPredicate::SignedGreaterThan
+        }
+    }
+    /// Returns the predicate that gives the same result when the operands are swapped.
+    pub fn swap_operands(self) -> IntPredicate {
+        match self {
+            IntPredicate::UnsignedGreaterThan => IntPredicate::UnsignedLessThan,
+            IntPredicate::UnsignedGreaterThanOrEqual => IntPredicate::UnsignedLessThanOrEqual,
+            IntPredicate::UnsignedLessThan => IntPredicate::UnsignedGreaterThan,
+            IntPredicate::UnsignedLessThanOrEqual => IntPredicate::UnsignedGreaterThanOrEqual,
+            IntPredicate::SignedGreaterThan => IntPredicate::SignedLessThan,
+            IntPredicate::SignedGreaterThanOrEqual => IntPredicate::SignedLessThanOrEqual,
+            IntPredicate::SignedLessThan => IntPredicate::SignedGreaterThan,
+            IntPredicate::SignedLessThanOrEqual => IntPredicate::SignedGreaterThanOrEqual,
+            IntPredicate::Equal | IntPredicate::NotEqual => self
+        }
+    }
+}
+/// The ways floating-point numbers can be compared.
+///
+/// Ordered comparisons are false if either operand is NaN, and unordered comparisons are
+/// true if either operand is NaN.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RealPredicate {
+    /// Always false.
+    False,
+    OrderedEqual,
+    OrderedGreaterThan,
+    OrderedGreaterThanOrEqual,
+    OrderedLessThan,
+    OrderedLessThanOrEqual,
+    OrderedNotEqual,
+    /// True if neither operand is NaN.
+    Ordered,
+    /// True if either operand is NaN.
+    Unordered,
+    UnorderedEqual,
+    UnorderedGreaterThan,
+    UnorderedGreaterThanOrEqual,
+    UnorderedLessThan,
+    UnorderedLessThanOrEqual,
+    UnorderedNotEqual,
+    /// Always true.
+    True
+}
+impl From<RealPredicate> for LLVMRealPredicate {
+    fn from(pred: RealPredicate) -> LLVMRealPredicate {
+        match pred {
+            RealPredicate::False => LLVMRealPredicate::LLVMRealPredicateFalse,
+            RealPredicate::OrderedEqual => LLVMRealPredicate::LLVMRealOEQ,
+            RealPredicate::OrderedGreaterThan => LLVMRealPredicate::LLVMRealOGT,
+            RealPredicate::OrderedGreaterThanOrEqual => LLVMRealPredicate::LLVMRealOGE,
+            RealPredicate::OrderedLessThan => LLVMRealPredicate::LLVMRealOLT,
+            RealPredicate::OrderedLessThanOrEqual => LLVMRealPredicate::LLVMRealOLE,
+            RealPredicate::OrderedNotEqual => LLVMRealPredicate::LLVMRealONE,
+            RealPredicate::Ordered => LLVMRealPredicate::LLVMRealORD,
+            RealPredicate::Unordered => LLVMRealPredicate::LLVMRealUNO,
+            RealPredicate::UnorderedEqual => LLVMRealPredicate::LLVMRealUEQ,
+            RealPredicate::UnorderedGreaterThan => LLVMRealPredicate::LLVMRealUGT,
+            RealPredicate::UnorderedGreaterThanOrEqual => LLVMRealPredicate::LLVMRealUGE,
+            RealPredicate::UnorderedLessThan => LLVMRealPredicate::LLVMRealULT,
+            RealPredicate::UnorderedLessThanOrEqual => LLVMRealPredicate::LLVMRealULE,
+            RealPredicate::UnorderedNotEqual => LLVMRealPredicate::LLVMRealUNE,
+            RealPredicate::True => LLVMRealPredicate::LLVMRealPredicateTrue
+        }
+    }
+}
+impl From<LLVMRealPredicate> for RealPredicate {
+    fn from(pred: LLVMRealPredicate) -> RealPredicate {
+        match pred {
+            LLVMRealPredicate::LLVMRealPredicateFalse => RealPredicate::False,
+            LLVMRealPredicate::LLVMRealOEQ => RealPredicate::OrderedEqual,
+            LLVMRealPredicate::LLVMRealOGT => RealPredicate::OrderedGreaterThan,
+            LLVMRealPredicate::LLVMRealOGE => RealPredicate::OrderedGreaterThanOrEqual,
+            LLVMRealPredicate::LLVMRealOLT => RealPredicate::OrderedLessThan,
+            LLVMRealPredicate::LLVMRealOLE => RealPredicate::OrderedLessThanOrEqual,
+            LLVMRealPredicate::LLVMRealONE => RealPredicate::OrderedNotEqual,
+            LLVMRealPredicate::LLVMRealORD => RealPredicate::Ordered,
+            LLVMRealPredicate::LLVMRealUNO => RealPredicate::Unordered,
+            LLVMRealPredicate::LLVMRealUEQ => RealPredicate::UnorderedEqual,
+            LLVMRealPredicate::LLVMRealUGT => RealPredicate::UnorderedGreaterThan,
+            LLVMRealPredicate::LLVMRealUGE => RealPredicate::UnorderedGreaterThanOrEqual,
+            LLVMRealPredicate::LLVMRealULT => RealPredicate::UnorderedLessThan,
+            LLVMRealPredicate::LLVMRealULE => RealPredicate::UnorderedLessThanOrEqual,
+            LLVMRealPredicate::LLVMRealUNE => RealPredicate::UnorderedNotEqual,
+            LLVMRealPredicate::LLVMRealPredicateTrue => RealPredicate::True
+        }
+    }
+}
+impl RealPredicate {
+    /// Returns the predicate that is true exactly when this one is false, which turns
+    /// ordered comparisons into unordered ones and back since NaN makes both false.
+    pub fn inverse(self) -> RealPredicate {
+        match self {
+            RealPredicate::False => RealPredicate::True,
+            RealPredicate::OrderedEqual => RealPredicate::UnorderedNotEqual,
+            RealPredicate::OrderedGreaterThan => RealPredicate::UnorderedLessThanOrEqual,
+            RealPredicate::OrderedGreaterThanOrEqual => RealPredicate::UnorderedLessThan,
+            RealPredicate::OrderedLessThan => RealPredicate::UnorderedGreaterThanOrEqual,
+            RealPredicate::OrderedLessThanOrEqual => RealPredicate::UnorderedGreaterThan,
+            RealPredicate::OrderedNotEqual => RealPredicate::UnorderedEqual,
+            RealPredicate::Ordered => RealPredicate::Unordered,
+            RealPredicate::Unordered => RealPredicate::Ordered,
+            RealPredicate::UnorderedEqual => RealPredicate::OrderedNotEqual,
+            RealPredicate::UnorderedGreaterThan => RealPredicate::OrderedLessThanOrEqual,
+            RealPredicate::UnorderedGreaterThanOrEqual => RealPredicate::OrderedLessThan,
+            RealPredicate::UnorderedLessThan => RealPredicate::OrderedGreaterThanOrEqual,
+            RealPredicate::UnorderedLessThanOrEqual => RealPredicate::OrderedGreaterThan,
+            RealPredicate::UnorderedNotEqual => RealPredicate::OrderedEqual,
+            RealPredicate::True => RealPredicate::False
+        }
+    }
+    /// Returns the predicate that gives the same result when the operands are swapped.
+    pub fn swap_operands(self) -> RealPredicate {
+        match self {
+            RealPredicate::OrderedGreaterThan => RealPredicate::OrderedLessThan,
+            RealPredicate::OrderedGreaterThanOrEqual => RealPredicate::OrderedLessThanOrEqual,
+            RealPredicate::OrderedLessThan => RealPredicate::OrderedGreaterThan,
+            RealPredicate::OrderedLessThanOrEqual => RealPredicate::OrderedGreaterThanOrEqual,
+            RealPredicate::UnorderedGreaterThan => RealPredicate::UnorderedLessThan,
+            RealPredicate::UnorderedGreaterThanOrEqual => RealPredicate::UnorderedLessThanOrEqual,
+            RealPredicate::UnorderedLessThan => RealPredicate::UnorderedGreaterThan,
+            RealPredicate::UnorderedLessThanOrEqual => RealPredicate::UnorderedGreaterThanOrEqual,
+            _ => self
+        }
+    }
 }
 /// An argument that is passed to a function.
 pub struct Arg(PhantomData<[u8]>);
@@ -95,37 +286,26 @@ native_ref!(&Arg = LLVMValueRef);
 sub!{Arg, LLVMIsAArgument}
 to_str!{Arg, LLVMPrintValueToString}
 impl Arg {
+    /// Returns the attributes of this argument.
+    pub fn get_attributes(&self) -> Attributes {
+        unsafe { core::LLVMGetAttribute(self.into()).into() }
+    }
     /// Add the attribute given to this argument.
     pub fn add_attribute(&self, attr: Attribute) {
         unsafe { core::LLVMAddAttribute(self.into(), attr.into()) }
     }
     /// Add all the attributes given to this argument.
     pub fn add_attributes(&self, attrs: &[Attribute]) {
-        let mut sum = LLVMAttribute::empty();
-        for attr in attrs {
-            let attr:LLVMAttribute = (*attr).into();
-            sum = sum | attr;
-        }
-        unsafe { core::LLVMAddAttribute(self.into(), sum.into()) }
+        unsafe { core::LLVMAddAttribute(self.into(), Attributes::from_slice(attrs).into()) }
     }
     /// Returns true if this argument has the attribute given.
     pub fn has_attribute(&self, attr: Attribute) -> bool {
-        unsafe {
-            let other = core::LLVMGetAttribute(self.into());
-            other.contains(attr.into())
-        }
+        self.get_attributes().contains(attr)
     }
     /// Returns true if this argument has all the attributes given.
     pub fn has_attributes(&self, attrs: &[Attribute]) -> bool {
-        unsafe {
-            let other = core::LLVMGetAttribute(self.into());
-            for &attr in attrs {
-                if !other.contains(attr.into()) {
-                    return false;
-                }
-            }
-            return true;
-        }
+        let other = self.get_attributes();
+        attrs.iter().all(|&attr| other.contains(attr))
     }
     /// Remove an attribute from this argument.
     pub fn remove_attribute(&self, attr: Attribute) {
@@ -151,6 +331,68 @@ impl GlobalValue {
             core::LLVMGetLinkage(self.into()).into()
         }
     }
+    /// Returns the module that this global is contained in.
+    pub fn get_parent(&self) -> &Module {
+        unsafe { core::LLVMGetGlobalParent(self.into()) }.into()
+    }
+    /// Set the section this global will be emitted to
+    pub fn set_section(&self, section: &str) {
+        util::with_cstr(section, |ptr| unsafe {
+            core::LLVMSetSection(self.into(), ptr)
+        })
+    }
+    /// Returns the section this global will be emitted to, or `None` if it uses the default section
+    pub fn get_section(&self) -> Option<&str> {
+        unsafe {
+            let section = core::LLVMGetSection(self.into());
+            util::to_null_str(section as *mut c_char).and_then(|section| if section.is_empty() { None } else { Some(section) })
+        }
+    }
+    /// Set the alignment of this global in bytes
+    pub fn set_alignment(&self, align: usize) {
+        unsafe { core::LLVMSetAlignment(self.into(), align as c_uint) }
+    }
+    /// Returns the alignment of this global in bytes, or zero if it uses the default alignment
+    pub fn get_alignment(&self) -> usize {
+        unsafe { core::LLVMGetAlignment(self.into()) as usize }
+    }
+    /// Set the visibility of this global
+    pub fn set_visibility(&self, visibility: Visibility) {
+        unsafe { core::LLVMSetVisibility(self.into(), visibility.into()) }
+    }
+    /// Returns the visibility of this global
+    pub fn get_visibility(&self) -> Visibility {
+        unsafe { core::LLVMGetVisibility(self.into()) }.into()
+    }
+    /// Set whether this global is imported from or exported to a DLL on Windows.
+    pub fn set_dll_storage_class(&self, class: DLLStorageClass) {
+        unsafe { core::LLVMSetDLLStorageClass(self.into(), class.into()) }
+    }
+    /// Returns whether this global is imported from or exported to a DLL on Windows.
+    pub fn get_dll_storage_class(&self) -> DLLStorageClass {
+        unsafe { core::LLVMGetDLLStorageClass(self.into()) }.into()
+    }
+    /// Set whether the address of this global is significant, or only its contents are, which
+    /// lets the linker merge it with identical constants and strings.
+    ///
+    /// LLVM 3.8 only has `unnamed_addr` for the whole program, so `UnnamedAddr::Local` is kept
+    /// as `UnnamedAddr::None`, which is always correct but never merges the global.
+    pub fn set_unnamed_addr(&self, unnamed: UnnamedAddr) {
+        let global = match unnamed {
+            UnnamedAddr::Global => 1,
+            UnnamedAddr::Local | UnnamedAddr::None => 0
+        };
+        unsafe { core::LLVMSetUnnamedAddr(self.into(), global) }
+    }
+    /// Returns whether the address of this global is significant, which is never
+    /// `UnnamedAddr::Local` in LLVM 3.8.
+    pub fn get_unnamed_addr(&self) -> UnnamedAddr {
+        if unsafe { core::LLVMHasUnnamedAddr(self.into()) } != 0 {
+            UnnamedAddr::Global
+        } else {
+            UnnamedAddr::None
+        }
+    }
     /// Returns true if this global is a declaration (as opposed to a definition).
     pub fn is_declaration(&self) -> bool {
         unsafe {
@@ -245,41 +487,142 @@ impl Function {
             core::LLVMGetElementType(ty).into()
         }
     }
+    /// Returns the attributes of this function.
+    pub fn get_attributes(&self) -> Attributes {
+        unsafe { core::LLVMGetFunctionAttr(self.into()).into() }
+    }
     /// Add the attribute given to this function.
     pub fn add_attribute(&self, attr: Attribute) {
         unsafe { core::LLVMAddFunctionAttr(self.into(), attr.into()) }
     }
     /// Add all the attributes given to this function.
     pub fn add_attributes(&self, attrs: &[Attribute]) {
-        let mut sum = LLVMAttribute::empty();
-        for attr in attrs {
-            let attr:LLVMAttribute = (*attr).into();
-            sum = sum | attr;
-        }
-        unsafe { core::LLVMAddFunctionAttr(self.into(), sum.into()) }
+        unsafe { core::LLVMAddFunctionAttr(self.into(), Attributes::from_slice(attrs).into()) }
     }
     /// Returns true if the attribute given is set in this function.
     pub fn has_attribute(&self, attr: Attribute) -> bool {
-        unsafe {
-            let other = core::LLVMGetFunctionAttr(self.into());
-            other.contains(attr.into())
-        }
+        self.get_attributes().contains(attr)
     }
     /// Returns true if all the attributes given is set in this function.
     pub fn has_attributes(&self, attrs: &[Attribute]) -> bool {
+        let other = self.get_attributes();
+        attrs.iter().all(|&attr| other.contains(attr))
+    }
+    /// Remove the attribute given from this function.
+    pub fn remove_attribute(&self, attr: Attribute) {
+        unsafe { core::LLVMRemoveFunctionAttr(self.into(), attr.into()) }
+    }
+    /// Add the target-dependent string attribute `name` with the value given to this function.
+    ///
+    /// An empty value adds the attribute without a value.
+    pub fn add_target_attribute(&self, name: &str, value: &str) {
+        let c_name = CString::new(name).unwrap();
+        let c_value = CString::new(value).unwrap();
+        unsafe { core::LLVMAddTargetDependentFunctionAttr(self.into(), c_name.as_ptr(), c_value.as_ptr()) }
+    }
+    /// Make this function probe its stack frame by calling the function named `probe` whenever
+    /// the frame is bigger than the probe size, so guard pages can't be skipped.
+    ///
+    /// This is how Rust catches stack overflows on targets with guard pages.
+    pub fn set_probe_stack(&self, probe: &str) {
+        self.add_target_attribute("probe-stack", probe)
+    }
+    /// Set the frame size, in bytes, above which this function probes its stack.
+    ///
+    /// This defaults to the page size of the target, which is 4096 on most targets.
+    pub fn set_stack_probe_size(&self, size: usize) {
+        self.add_target_attribute("stack-probe-size", &size.to_string())
+    }
+    /// Stop this function from probing the stack when passing arguments on the stack, which
+    /// Windows targets do by default.
+    pub fn set_no_stack_arg_probe(&self) {
+        self.add_target_attribute("no-stack-arg-probe", "")
+    }
+    /// Make this function compute floats with library calls if `abi` is `FloatAbi::Soft`, or
+    /// with FPU instructions otherwise.
+    ///
+    /// This doesn't change the calling convention, which is picked by the target triple, so
+    /// the triple should be adjusted with `FloatAbi::adjust_triple` too.
+    pub fn set_float_abi(&self, abi: FloatAbi) {
+        self.add_target_attribute("use-soft-float", if abi == FloatAbi::Soft { "true" } else { "false" })
+    }
+    /// Make this function be compiled for the CPU `cpu`, like `skylake-avx512`, instead of the
+    /// CPU of the target machine the module is compiled with.
+    ///
+    /// LLVM 3.8 can't tell whether a target knows a CPU, and only warns about one it doesn't
+    /// when the function is compiled, so the name isn't checked.
+    pub fn set_target_cpu(&self, cpu: &str) {
+        self.add_target_attribute("target-cpu", cpu)
+    }
+    /// Make this function be compiled with the target features given, instead of the features
+    /// of the target machine the module is compiled with, so only the functions that need
+    /// something like AVX-512 require a CPU that has it.
+    pub fn set_target_features(&self, features: &TargetFeatures) {
+        self.add_target_attribute("target-features", &features.to_string())
+    }
+    /// Set how this function treats denormal floating-point values, which lets the backend
+    /// use faster instructions that flush them to zero.
+    pub fn set_denormal_mode(&self, mode: DenormalMode) {
+        self.add_target_attribute("denormal-fp-math", mode.as_str())
+    }
+    /// Set the calling convention this function uses.
+    ///
+    /// Calls to it should be given the same convention with `CallSite::set_call_conv`.
+    pub fn set_call_conv(&self, conv: CallingConv) {
+        unsafe { core::LLVMSetFunctionCallConv(self.into(), conv.into()) }
+    }
+    /// Returns the calling convention this function uses.
+    pub fn get_call_conv(&self) -> CallingConv {
+        unsafe { core::LLVMGetFunctionCallConv(self.into()).into() }
+    }
+    /// Returns static estimates of how often each block of this function runs.
+    pub fn block_frequencies(&self) -> BlockFrequencies {
+        BlockFrequencies::new(self)
+    }
+    /// Returns the header of every simple counted loop in this function, with how many times
+    /// the loop goes around, as worked out by `BasicBlock::get_trip_count`.
+    pub fn get_trip_counts(&self) -> Vec<(&BasicBlock, u64)> {
+        let mut counts = Vec::new();
         unsafe {
-            let other = core::LLVMGetFunctionAttr(self.into());
-            for &attr in attrs {
-                if !other.contains(attr.into()) {
-                    return false;
+            let mut block = core::LLVMGetFirstBasicBlock(self.into());
+            while !block.is_null() {
+                let header: &BasicBlock = block.into();
+                if let Some(count) = header.get_trip_count() {
+                    counts.push((header, count));
                 }
+                block = core::LLVMGetNextBasicBlock(block);
             }
-            return true;
         }
+        counts
     }
-    /// Remove the attribute given from this function.
-    pub fn remove_attribute(&self, attr: Attribute) {
-        unsafe { core::LLVMRemoveFunctionAttr(self.into(), attr.into()) }
+    /// Run the function passes of the optimization pipeline for `opt_level` on this function
+    /// alone, leaving the rest of its module as it is.
+    ///
+    /// This is for lazy JITs that optimize each function when it is first needed. Passes over
+    /// the whole module, like inlining, aren't run.
+    pub fn optimize(&self, opt_level: usize) {
+        unsafe {
+            let builder = pass_builder::LLVMPassManagerBuilderCreate();
+            pass_builder::LLVMPassManagerBuilderSetOptLevel(builder, opt_level as c_uint);
+            let pass_manager = core::LLVMCreateFunctionPassManagerForModule(self.get_parent().into());
+            pass_builder::LLVMPassManagerBuilderPopulateFunctionPassManager(builder, pass_manager);
+            pass_builder::LLVMPassManagerBuilderDispose(builder);
+            core::LLVMInitializeFunctionPassManager(pass_manager);
+            core::LLVMRunFunctionPassManager(pass_manager, self.into());
+            core::LLVMFinalizeFunctionPassManager(pass_manager);
+            core::LLVMDisposePassManager(pass_manager);
+        }
+    }
+    /// Let the backend assume what `flags` allows about the floating-point values in this function.
+    ///
+    /// Reciprocals, contraction and reassociation are only allowed together, as `unsafe-fp-math`,
+    /// so they are only enabled when all three of them are set.
+    pub fn set_fast_math(&self, flags: FastMathFlags) {
+        let as_str = |enabled: bool| if enabled { "true" } else { "false" };
+        self.add_target_attribute("no-nans-fp-math", as_str(flags.no_nans));
+        self.add_target_attribute("no-infs-fp-math", as_str(flags.no_infs));
+        self.add_target_attribute("no-signed-zeros-fp-math", as_str(flags.no_signed_zeros));
+        self.add_target_attribute("unsafe-fp-math", as_str(flags.allow_reciprocal && flags.allow_contract && flags.allow_reassoc));
     }
 }
 impl GetContext for Function {
@@ -287,6 +630,294 @@ impl GetContext for Function {
         self.get_type().get_context()
     }
 }
+/// A piece of inline assembly, which can be called like a function with `Builder::build_call`.
+pub struct InlineAsm(PhantomData<[u8]>);
+native_ref!(&InlineAsm = LLVMValueRef);
+sub!{InlineAsm, LLVMIsAInlineAsm}
+to_str!{InlineAsm, LLVMPrintValueToString}
+impl InlineAsm {
+    /// Create inline assembly that is called with the signature `sig`, where `asm` is the
+    /// assembly with `$0`, `$1` and so on for its operands, and `constraints` is LLVM's
+    /// comma-separated constraint string for them, like `=r,r,~{memory}`.
+    ///
+    /// If `side_effects` is true, the assembly isn't removed or moved even if its result is
+    /// unused. If `align_stack` is true, the stack is aligned before it runs. x86 assembly is
+    /// always parsed in AT&T syntax, since the C API can't select the Intel dialect.
+    pub fn new<'a>(sig: &'a FunctionType, asm: &str, constraints: &str, side_effects: bool, align_stack: bool) -> &'a InlineAsm {
+        let c_asm = CString::new(asm).unwrap();
+        let c_constraints = CString::new(constraints).unwrap();
+        unsafe {
+            core::LLVMConstInlineAsm(sig.into(), c_asm.as_ptr(), c_constraints.as_ptr(), side_effects as c_int, align_stack as c_int).into()
+        }
+    }
+}
+/// An instruction in a basic block.
+pub struct Instruction(PhantomData<[u8]>);
+native_ref!(&Instruction = LLVMValueRef);
+sub!{Instruction, LLVMIsAInstruction}
+to_str!{Instruction, LLVMPrintValueToString}
+impl Instruction {
+    /// Returns the basic block this instruction is in, or `None` if it isn't in one.
+    pub fn get_parent(&self) -> Option<&BasicBlock> {
+        unsafe { util::ptr_to_null(core::LLVMGetInstructionParent(self.into())) }
+    }
+    /// Returns the predicate this compares with if it is an `icmp`, or `None` otherwise.
+    pub fn get_int_predicate(&self) -> Option<IntPredicate> {
+        unsafe {
+            if core::LLVMIsAICmpInst(self.into()).is_null() {
+                None
+            } else {
+                Some(core::LLVMGetICmpPredicate(self.into()).into())
+            }
+        }
+    }
+    /// Returns the instruction after this one in its block, or `None` if this is the last.
+    pub fn get_next(&self) -> Option<&Instruction> {
+        unsafe { util::ptr_to_null(core::LLVMGetNextInstruction(self.into())) }
+    }
+    /// Returns the instruction before this one in its block, or `None` if this is the first.
+    pub fn get_previous(&self) -> Option<&Instruction> {
+        unsafe { util::ptr_to_null(core::LLVMGetPreviousInstruction(self.into())) }
+    }
+    /// Returns the debug location attached to this instruction as `!dbg`, or `None` if it
+    /// doesn't have one.
+    pub fn get_debug_location(&self) -> Option<&Value> {
+        let dbg = self.get_context().get_metadata_kind(MetadataKind::Dbg);
+        unsafe { util::ptr_to_null(core::LLVMGetMetadata(self.into(), dbg as c_uint)) }
+    }
+    /// Set whether this load or store is volatile, so the optimizer never removes, merges or
+    /// reorders it with other volatile accesses, as memory-mapped I/O needs.
+    pub fn set_volatile(&self, volatile: bool) {
+        self.check_memory_access();
+        unsafe { core::LLVMSetVolatile(self.into(), volatile as c_int) }
+    }
+    /// Returns true if this load or store is volatile.
+    pub fn is_volatile(&self) -> bool {
+        self.check_memory_access();
+        unsafe { core::LLVMGetVolatile(self.into()) != 0 }
+    }
+    /// Set the alignment in bytes that this load or store can assume its address has.
+    ///
+    /// Zero means the ABI alignment of the type being accessed.
+    pub fn set_alignment(&self, align: usize) {
+        self.check_memory_access();
+        unsafe { core::LLVMSetAlignment(self.into(), align as c_uint) }
+    }
+    /// Returns the alignment in bytes that this load or store assumes its address has.
+    pub fn get_alignment(&self) -> usize {
+        self.check_memory_access();
+        unsafe { core::LLVMGetAlignment(self.into()) as usize }
+    }
+    fn check_memory_access(&self) {
+        unsafe {
+            if core::LLVMIsALoadInst(self.into()).is_null() && core::LLVMIsAStoreInst(self.into()).is_null() {
+                panic!("expected a load or store, got {:?}", self)
+            }
+        }
+    }
+    /// Move the instruction `new` from wherever it is to just before this one.
+    pub fn insert_before(&self, new: &Instruction) {
+        let builder = Builder::new(self.get_context());
+        builder.position_before(self);
+        new.insert_into(&builder);
+    }
+    /// Move the instruction `new` from wherever it is to just after this one.
+    pub fn insert_after(&self, new: &Instruction) {
+        match self.get_next() {
+            Some(next) => next.insert_before(new),
+            None => {
+                let builder = Builder::new(self.get_context());
+                builder.position_at_end(self.get_parent().expect("instruction is not in a block"));
+                new.insert_into(&builder);
+            }
+        }
+    }
+    /// Remove this instruction from its block if it is in one, then insert it where `builder`
+    /// is positioned.
+    fn insert_into(&self, builder: &Builder) {
+        unsafe {
+            if self.get_parent().is_some() {
+                core::LLVMInstructionRemoveFromParent(self.into());
+            }
+            core::LLVMInsertIntoBuilder(builder.into(), self.into());
+        }
+    }
+}
+/// A `switch` instruction, which jumps to the block of the case matching its value.
+pub struct Switch(PhantomData<[u8]>);
+native_ref!(&Switch = LLVMValueRef);
+sub!{Switch, LLVMIsASwitchInst, Instruction}
+to_str!{Switch, LLVMPrintValueToString}
+impl Switch {
+    /// Add a case that jumps to `block` when the value is the constant `value`.
+    pub fn add_case(&self, value: &Value, block: &BasicBlock) {
+        unsafe { core::LLVMAddCase(self.into(), value.into(), block.into()) }
+    }
+    /// Returns the block this jumps to when none of the cases match.
+    pub fn get_default(&self) -> &BasicBlock {
+        unsafe { core::LLVMGetSwitchDefaultDest(self.into()) }.into()
+    }
+}
+/// A `phi` instruction, which yields the value paired with whichever block ran before the
+/// one it is in.
+pub struct PhiNode(PhantomData<[u8]>);
+native_ref!(&PhiNode = LLVMValueRef);
+sub!{PhiNode, LLVMIsAPHINode, Instruction}
+to_str!{PhiNode, LLVMPrintValueToString}
+impl PhiNode {
+    /// Add `value` as the value this yields when coming from `block`.
+    pub fn add_incoming(&self, value: &Value, block: &BasicBlock) {
+        self.add_incomings(&[(value, block)])
+    }
+    /// Add each value with the block this yields it when coming from.
+    pub fn add_incomings(&self, incoming: &[(&Value, &BasicBlock)]) {
+        let mut values: Vec<LLVMValueRef> = incoming.iter().map(|&(value, _)| value.into()).collect();
+        let mut blocks: Vec<LLVMBasicBlockRef> = incoming.iter().map(|&(_, block)| block.into()).collect();
+        unsafe { core::LLVMAddIncoming(self.into(), values.as_mut_ptr(), blocks.as_mut_ptr(), incoming.len() as c_uint) }
+    }
+    /// Returns the number of blocks this has a value for.
+    pub fn count_incoming(&self) -> usize {
+        unsafe { core::LLVMCountIncoming(self.into()) as usize }
+    }
+    /// Returns the value of the incoming edge at `index`.
+    pub fn get_incoming_value(&self, index: usize) -> &Value {
+        assert!(index < self.count_incoming(), "no such incoming edge {} on {:?}", index, self);
+        unsafe { core::LLVMGetIncomingValue(self.into(), index as c_uint).into() }
+    }
+    /// Returns the block of the incoming edge at `index`.
+    pub fn get_incoming_block(&self, index: usize) -> &BasicBlock {
+        assert!(index < self.count_incoming(), "no such incoming edge {} on {:?}", index, self);
+        unsafe { core::LLVMGetIncomingBlock(self.into(), index as c_uint).into() }
+    }
+    /// Returns every incoming value with its block, in the order they were added.
+    pub fn get_incoming(&self) -> Vec<(&Value, &BasicBlock)> {
+        (0..self.count_incoming()).map(|index| (self.get_incoming_value(index), self.get_incoming_block(index))).collect()
+    }
+}
+/// An `indirectbr` instruction, which jumps to the block whose address is its operand.
+pub struct IndirectBr(PhantomData<[u8]>);
+native_ref!(&IndirectBr = LLVMValueRef);
+sub!{IndirectBr, LLVMIsAIndirectBrInst, Instruction}
+to_str!{IndirectBr, LLVMPrintValueToString}
+impl IndirectBr {
+    /// Add `block` to the blocks this may jump to, which must include every block whose
+    /// address it is given.
+    pub fn add_destination(&self, block: &BasicBlock) {
+        unsafe { core::LLVMAddDestination(self.into(), block.into()) }
+    }
+}
+/// A `call` instruction.
+///
+/// This isn't exported from the crate root, where `Call` is a call logged by a `Recorder`.
+pub struct Call(PhantomData<[u8]>);
+native_ref!(&Call = LLVMValueRef);
+sub!{Call, LLVMIsACallInst, Instruction}
+to_str!{Call, LLVMPrintValueToString}
+impl Call {
+    /// Mark this call as a tail call, which the code generator may turn into a jump when the
+    /// caller returns what it returns, or remove that mark.
+    ///
+    /// The callee must not use the caller's allocas or varargs when this is marked.
+    pub fn set_tail_call(&self, tail_call: bool) {
+        unsafe { core::LLVMSetTailCall(self.into(), tail_call as c_int) }
+    }
+    /// Returns true if this call is marked as a tail call.
+    pub fn is_tail_call(&self) -> bool {
+        unsafe { core::LLVMIsTailCall(self.into()) != 0 }
+    }
+}
+/// A `call` or `invoke` instruction, which both call a function with some arguments.
+pub struct CallSite(PhantomData<[u8]>);
+native_ref!(&CallSite = LLVMValueRef);
+to_str!{CallSite, LLVMPrintValueToString}
+unsafe impl Sub<Value> for CallSite {
+    fn is(value: &Value) -> bool {
+        unsafe { !core::LLVMIsACallInst(value.into()).is_null() || !core::LLVMIsAInvokeInst(value.into()).is_null() }
+    }
+}
+impl Deref for CallSite {
+    type Target = Instruction;
+    fn deref(&self) -> &Instruction {
+        unsafe { mem::transmute(self) }
+    }
+}
+impl CallSite {
+    /// Returns the value this calls, which is a function or a pointer to one.
+    pub fn get_called_value(&self) -> &Value {
+        // the callee comes after the arguments, before an invoke's normal and unwind blocks
+        unsafe { core::LLVMGetOperand(self.into(), self.num_args() as c_uint).into() }
+    }
+    /// Returns the function this calls, or `None` if it calls through a pointer.
+    pub fn get_called_function(&self) -> Option<&Function> {
+        let callee = self.get_called_value();
+        unsafe { util::ptr_to_null(core::LLVMIsAFunction(callee.into())) }
+    }
+    /// Returns the number of arguments this passes.
+    pub fn num_args(&self) -> usize {
+        // an invoke also has its normal and unwind blocks as operands
+        let extra = if unsafe { core::LLVMIsAInvokeInst(self.into()).is_null() } { 1 } else { 3 };
+        unsafe { core::LLVMGetNumOperands(self.into()) as usize - extra }
+    }
+    /// Returns the argument at `index`.
+    pub fn get_arg(&self, index: usize) -> &Value {
+        if index < self.num_args() {
+            unsafe { core::LLVMGetOperand(self.into(), index as c_uint).into() }
+        } else {
+            panic!("no such argument {} on {:?}", index, self)
+        }
+    }
+    /// Returns the arguments this passes.
+    pub fn get_args(&self) -> Vec<&Value> {
+        (0..self.num_args()).map(|index| self.get_arg(index)).collect()
+    }
+    /// Add the attribute given to the argument at `index` of this call.
+    ///
+    /// This only applies to this call, not to the function being called.
+    pub fn add_arg_attribute(&self, index: usize, attr: Attribute) {
+        assert!(index < self.num_args(), "no such argument {} on {:?}", index, self);
+        unsafe { core::LLVMAddInstrAttribute(self.into(), index as c_uint + 1, attr.into()) }
+    }
+    /// Remove the attribute given from the argument at `index` of this call.
+    pub fn remove_arg_attribute(&self, index: usize, attr: Attribute) {
+        assert!(index < self.num_args(), "no such argument {} on {:?}", index, self);
+        unsafe { core::LLVMRemoveInstrAttribute(self.into(), index as c_uint + 1, attr.into()) }
+    }
+    /// Set the alignment of the argument at `index` of this call, which must be a `byval` pointer.
+    pub fn set_arg_alignment(&self, index: usize, align: u32) {
+        assert!(index < self.num_args(), "no such argument {} on {:?}", index, self);
+        unsafe { core::LLVMSetInstrParamAlignment(self.into(), index as c_uint + 1, align as c_uint) }
+    }
+    /// Set the calling convention this call uses, which should match the function called.
+    pub fn set_call_conv(&self, conv: CallingConv) {
+        unsafe { core::LLVMSetInstructionCallConv(self.into(), conv.into()) }
+    }
+    /// Returns the calling convention this call uses.
+    pub fn get_call_conv(&self) -> CallingConv {
+        unsafe { core::LLVMGetInstructionCallConv(self.into()).into() }
+    }
+}
+/// A `landingpad` instruction, which starts the block an `invoke` unwinds to and yields the
+/// exception being thrown.
+pub struct LandingPad(PhantomData<[u8]>);
+native_ref!(&LandingPad = LLVMValueRef);
+sub!{LandingPad, LLVMIsALandingPadInst, Instruction}
+to_str!{LandingPad, LLVMPrintValueToString}
+impl LandingPad {
+    /// Add a clause, which is either a `catch` of the type info constant `clause`, or a
+    /// `filter` if it is a constant array of type infos.
+    pub fn add_clause(&self, clause: &Value) {
+        unsafe { core::LLVMAddClause(self.into(), clause.into()) }
+    }
+    /// Set whether this landing pad runs for exceptions that none of its clauses match, so
+    /// it can clean up before resuming unwinding.
+    pub fn set_cleanup(&self, cleanup: bool) {
+        unsafe { core::LLVMSetCleanup(self.into(), cleanup as c_int) }
+    }
+    /// Returns true if this landing pad runs for exceptions that none of its clauses match.
+    pub fn is_cleanup(&self) -> bool {
+        unsafe { core::LLVMIsCleanup(self.into()) != 0 }
+    }
+}
 /// These indicate how you want arguments / functions to be handled.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[repr(C)]
@@ -344,14 +975,101 @@ pub enum Attribute {
     /// Function is called early/often, so lazy binding isn't effective.
     NonLazyBind =       0b10000000000000000000000000000000
 }
-impl From<LLVMAttribute> for Attribute {
-    fn from(attr: LLVMAttribute) -> Attribute {
-        unsafe { mem::transmute(attr) }
+impl Attribute {
+    /// Returns the bits of an attribute set that this attribute is stored in, which is more
+    /// than one bit for the alignment fields.
+    fn mask(self) -> u64 {
+        match self {
+            Attribute::Alignment => 0b11111 << 16,
+            Attribute::StackAlignment => 0b111 << 26,
+            attr => attr as u64
+        }
     }
 }
 impl From<Attribute> for LLVMAttribute {
     fn from(attr: Attribute) -> LLVMAttribute {
-        unsafe { mem::transmute(attr) }
+        LLVMAttribute::from_bits_truncate(attr as u64 as _)
+    }
+}
+
+/// Every attribute, in the order of their bits.
+const ATTRIBUTES: [Attribute; 26] = [
+    Attribute::ZExt, Attribute::SExt, Attribute::NoReturn, Attribute::InReg, Attribute::StructRet,
+    Attribute::NoUnwind, Attribute::NoAlias, Attribute::ByVal, Attribute::Nest, Attribute::ReadNone,
+    Attribute::ReadOnly, Attribute::NoInline, Attribute::AlwaysInline, Attribute::OptimizeForSize,
+    Attribute::StackProtect, Attribute::StackProtectReq, Attribute::Alignment, Attribute::NoCapture,
+    Attribute::NoRedZone, Attribute::NoImplicitFloat, Attribute::Naked, Attribute::InlineHint,
+    Attribute::StackAlignment, Attribute::ReturnsTwice, Attribute::UWTable, Attribute::NonLazyBind
+];
+
+/// A set of attributes, the way LLVM stores them on a function or argument.
+///
+/// The alignment attributes are fields of several bits, so an argument with any alignment
+/// contains `Attribute::Alignment`, and the alignment itself is given by `get_alignment`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Attributes(u64);
+impl Attributes {
+    /// Returns a set with no attributes.
+    pub fn empty() -> Attributes {
+        Attributes(0)
+    }
+    /// Returns a set of the attributes given.
+    pub fn from_slice(attrs: &[Attribute]) -> Attributes {
+        attrs.iter().fold(Attributes::empty(), |set, &attr| set | attr)
+    }
+    /// Returns true if there are no attributes in this set.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    /// Returns true if this set contains the attribute given.
+    pub fn contains(&self, attr: Attribute) -> bool {
+        self.0 & attr.mask() != 0
+    }
+    /// Add the attribute given to this set.
+    pub fn insert(&mut self, attr: Attribute) {
+        self.0 |= attr as u64
+    }
+    /// Remove the attribute given from this set.
+    pub fn remove(&mut self, attr: Attribute) {
+        self.0 &= !attr.mask()
+    }
+    /// Returns the alignment in bytes given by the alignment field, or `None` if it isn't set.
+    pub fn get_alignment(&self) -> Option<u64> {
+        match (self.0 & Attribute::Alignment.mask()) >> 16 {
+            0 => None,
+            log => Some(1 << (log - 1))
+        }
+    }
+    /// Returns the attributes in this set, in the order of their bits.
+    pub fn to_vec(&self) -> Vec<Attribute> {
+        ATTRIBUTES.iter().cloned().filter(|&attr| self.contains(attr)).collect()
+    }
+}
+impl From<Attribute> for Attributes {
+    fn from(attr: Attribute) -> Attributes {
+        Attributes(attr as u64)
+    }
+}
+impl BitOr<Attribute> for Attributes {
+    type Output = Attributes;
+    fn bitor(self, attr: Attribute) -> Attributes {
+        Attributes(self.0 | attr as u64)
+    }
+}
+impl BitOr for Attributes {
+    type Output = Attributes;
+    fn bitor(self, other: Attributes) -> Attributes {
+        Attributes(self.0 | other.0)
+    }
+}
+impl From<LLVMAttribute> for Attributes {
+    fn from(attrs: LLVMAttribute) -> Attributes {
+        Attributes(attrs.bits() as u64)
+    }
+}
+impl From<Attributes> for LLVMAttribute {
+    fn from(attrs: Attributes) -> LLVMAttribute {
+        LLVMAttribute::from_bits_truncate(attrs.0 as _)
     }
 }
 
@@ -383,13 +1101,182 @@ pub enum Linkage {
     Common              = 14,
 }
 impl From<LLVMLinkage> for Linkage {
-    fn from(attr: LLVMLinkage) -> Linkage {
-        unsafe { mem::transmute(attr) }
+    /// Convert LLVM's linkage, mapping the obsolete linkages that LLVM no longer uses to the
+    /// ones that replaced them, the way LLVM itself does when they are set.
+    fn from(linkage: LLVMLinkage) -> Linkage {
+        match linkage {
+            LLVMLinkage::LLVMExternalLinkage => Linkage::External,
+            LLVMLinkage::LLVMAvailableExternallyLinkage => Linkage::AvailableExternally,
+            LLVMLinkage::LLVMLinkOnceAnyLinkage => Linkage::LinkOnceAny,
+            LLVMLinkage::LLVMLinkOnceODRLinkage => Linkage::LinkOnceODR,
+            LLVMLinkage::LLVMLinkOnceODRAutoHideLinkage => Linkage::LinkOnceODR,
+            LLVMLinkage::LLVMWeakAnyLinkage => Linkage::WeakAny,
+            LLVMLinkage::LLVMWeakODRLinkage => Linkage::WeakODR,
+            LLVMLinkage::LLVMAppendingLinkage => Linkage::Appending,
+            LLVMLinkage::LLVMInternalLinkage => Linkage::Internal,
+            LLVMLinkage::LLVMPrivateLinkage => Linkage::Private,
+            LLVMLinkage::LLVMDLLImportLinkage => Linkage::External,
+            LLVMLinkage::LLVMDLLExportLinkage => Linkage::External,
+            LLVMLinkage::LLVMExternalWeakLinkage => Linkage::ExternalWeak,
+            LLVMLinkage::LLVMGhostLinkage => Linkage::External,
+            LLVMLinkage::LLVMCommonLinkage => Linkage::Common,
+            LLVMLinkage::LLVMLinkerPrivateLinkage => Linkage::Private,
+            LLVMLinkage::LLVMLinkerPrivateWeakLinkage => Linkage::Private
+        }
     }
 }
 impl From<Linkage> for LLVMLinkage {
-    fn from(attr: Linkage) -> LLVMLinkage {
-        unsafe { mem::transmute(attr) }
+    fn from(linkage: Linkage) -> LLVMLinkage {
+        match linkage {
+            Linkage::External => LLVMLinkage::LLVMExternalLinkage,
+            Linkage::AvailableExternally => LLVMLinkage::LLVMAvailableExternallyLinkage,
+            Linkage::LinkOnceAny => LLVMLinkage::LLVMLinkOnceAnyLinkage,
+            Linkage::LinkOnceODR => LLVMLinkage::LLVMLinkOnceODRLinkage,
+            Linkage::WeakAny => LLVMLinkage::LLVMWeakAnyLinkage,
+            Linkage::WeakODR => LLVMLinkage::LLVMWeakODRLinkage,
+            Linkage::Appending => LLVMLinkage::LLVMAppendingLinkage,
+            Linkage::Internal => LLVMLinkage::LLVMInternalLinkage,
+            Linkage::Private => LLVMLinkage::LLVMPrivateLinkage,
+            Linkage::ExternalWeak => LLVMLinkage::LLVMExternalWeakLinkage,
+            Linkage::Common => LLVMLinkage::LLVMCommonLinkage
+        }
+    }
+}
+
+/// How a global is visible to other modules once it has been linked.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Visibility {
+    /// The global is visible to other modules, and can be overridden by them.
+    Default,
+    /// The global is not visible outside of the linked object, such as a shared library.
+    Hidden,
+    /// The global is visible to other modules, but can't be overridden by them.
+    Protected
+}
+impl From<LLVMVisibility> for Visibility {
+    fn from(visibility: LLVMVisibility) -> Visibility {
+        match visibility {
+            LLVMVisibility::LLVMDefaultVisibility => Visibility::Default,
+            LLVMVisibility::LLVMHiddenVisibility => Visibility::Hidden,
+            LLVMVisibility::LLVMProtectedVisibility => Visibility::Protected
+        }
+    }
+}
+impl From<Visibility> for LLVMVisibility {
+    fn from(visibility: Visibility) -> LLVMVisibility {
+        match visibility {
+            Visibility::Default => LLVMVisibility::LLVMDefaultVisibility,
+            Visibility::Hidden => LLVMVisibility::LLVMHiddenVisibility,
+            Visibility::Protected => LLVMVisibility::LLVMProtectedVisibility
+        }
+    }
+}
+
+/// Whether a global is imported from or exported to a DLL, which only matters on Windows.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DLLStorageClass {
+    /// The global isn't imported or exported.
+    Default,
+    /// The global is defined in a DLL, and is accessed through the import table.
+    DLLImport,
+    /// The global is exported from the DLL it is defined in.
+    DLLExport
+}
+impl From<LLVMDLLStorageClass> for DLLStorageClass {
+    fn from(class: LLVMDLLStorageClass) -> DLLStorageClass {
+        match class {
+            LLVMDLLStorageClass::LLVMDefaultStorageClass => DLLStorageClass::Default,
+            LLVMDLLStorageClass::LLVMDLLImportStorageClass => DLLStorageClass::DLLImport,
+            LLVMDLLStorageClass::LLVMDLLExportStorageClass => DLLStorageClass::DLLExport
+        }
+    }
+}
+impl From<DLLStorageClass> for LLVMDLLStorageClass {
+    fn from(class: DLLStorageClass) -> LLVMDLLStorageClass {
+        match class {
+            DLLStorageClass::Default => LLVMDLLStorageClass::LLVMDefaultStorageClass,
+            DLLStorageClass::DLLImport => LLVMDLLStorageClass::LLVMDLLImportStorageClass,
+            DLLStorageClass::DLLExport => LLVMDLLStorageClass::LLVMDLLExportStorageClass
+        }
+    }
+}
+
+/// Whether the address of a global is significant, or it can be merged with other globals
+/// that have the same contents.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum UnnamedAddr {
+    /// The address is significant, so the global must stay distinct from every other.
+    None,
+    /// The address isn't significant within the module, but may be compared outside it.
+    Local,
+    /// The address isn't significant anywhere, so the global can be merged with any other.
+    Global
+}
+
+/// How a function receives its arguments and returns its result.
+///
+/// A call and the function it calls must use the same convention, or the call is undefined.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CallingConv {
+    /// The C calling convention of the target, which is the default.
+    C,
+    /// A convention that makes calls as fast as possible, which may pass arguments in registers.
+    Fast,
+    /// A convention that makes the code calling rarely called functions as fast as possible.
+    Cold,
+    /// The convention used by the Glasgow Haskell Compiler, which passes everything in registers.
+    GHC,
+    /// The convention used by the High-Performance Erlang compiler.
+    HiPE,
+    /// The convention used by the WebKit JavaScript JIT.
+    WebKitJS,
+    /// A convention that can pass arguments in any register, for patchpoints.
+    AnyReg,
+    /// A convention that makes the callee save most registers.
+    PreserveMost,
+    /// A convention that makes the callee save all registers.
+    PreserveAll,
+    /// The `stdcall` convention of 32-bit x86 Windows.
+    X86Stdcall,
+    /// The `fastcall` convention of 32-bit x86 Windows.
+    X86Fastcall,
+    /// Another target-specific convention, by its number.
+    Other(u32)
+}
+impl From<c_uint> for CallingConv {
+    fn from(conv: c_uint) -> CallingConv {
+        match conv {
+            0 => CallingConv::C,
+            8 => CallingConv::Fast,
+            9 => CallingConv::Cold,
+            10 => CallingConv::GHC,
+            11 => CallingConv::HiPE,
+            12 => CallingConv::WebKitJS,
+            13 => CallingConv::AnyReg,
+            14 => CallingConv::PreserveMost,
+            15 => CallingConv::PreserveAll,
+            64 => CallingConv::X86Stdcall,
+            65 => CallingConv::X86Fastcall,
+            other => CallingConv::Other(other as u32)
+        }
+    }
+}
+impl From<CallingConv> for c_uint {
+    fn from(conv: CallingConv) -> c_uint {
+        match conv {
+            CallingConv::C => 0,
+            CallingConv::Fast => 8,
+            CallingConv::Cold => 9,
+            CallingConv::GHC => 10,
+            CallingConv::HiPE => 11,
+            CallingConv::WebKitJS => 12,
+            CallingConv::AnyReg => 13,
+            CallingConv::PreserveMost => 14,
+            CallingConv::PreserveAll => 15,
+            CallingConv::X86Stdcall => 64,
+            CallingConv::X86Fastcall => 65,
+            CallingConv::Other(other) => other as c_uint
+        }
     }
 }
 
@@ -0,0 +1,132 @@
+extern crate llvm;
+extern crate libc;
+use llvm::*;
+use std::mem;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+extern "C" fn compare_exchange_4(ptr: &AtomicU32, expected: &mut u32, desired: u32, success: i32, failure: i32) -> bool {
+    assert_eq!((success, failure), (5, 2));
+    match ptr.compare_exchange(*expected, desired, Ordering::SeqCst, Ordering::Acquire) {
+        Ok(_) => true,
+        Err(actual) => {
+            *expected = actual;
+            false
+        }
+    }
+}
+
+#[test]
+fn test_cmpxchg() {
+    let ctx = Context::new();
+    let module = Module::new("cmpxchg", &ctx);
+    let func = module.add_function("cas", Type::get::<fn(Option<&u32>, u32, u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let result = builder.build_cmpxchg(&func[0], &func[1], &func[2], AtomicOrdering::SequentiallyConsistent, AtomicOrdering::Acquire, false);
+    let swapped = builder.build_zext(builder.build_extract_value(result, &[1]), Type::get::<u32>(&ctx));
+    let old = builder.build_extract_value(result, &[0]);
+    builder.build_ret(builder.build_add(builder.build_mul(old, 10u32.compile(&ctx)), swapped));
+    module.verify().unwrap();
+    let libcall = module.get_function("__atomic_compare_exchange_4").unwrap();
+    assert_eq!(libcall.get_signature().num_params(), 5);
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    unsafe { ee.add_global_mapping(libcall, compare_exchange_4 as *const libc::c_void) };
+    let value = AtomicU32::new(3);
+    ee.with_function(func, |cas: extern fn(Option<&u32>, u32, u32) -> u32| {
+        let cas: extern fn(&AtomicU32, u32, u32) -> u32 = unsafe { mem::transmute(cas) };
+        assert_eq!(cas(&value, 4, 7), 30);
+        assert_eq!(value.load(Ordering::SeqCst), 3);
+        assert_eq!(cas(&value, 3, 7), 31);
+        assert_eq!(value.load(Ordering::SeqCst), 7);
+    });
+}
+
+#[test]
+#[should_panic(expected = "failure ordering SequentiallyConsistent is stronger than success ordering Acquire")]
+fn test_cmpxchg_stronger_failure() {
+    let ctx = Context::new();
+    let module = Module::new("cmpxchg", &ctx);
+    let func = module.add_function("cas", Type::get::<fn(Option<&u32>, u32, u32)>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_cmpxchg(&func[0], &func[1], &func[2], AtomicOrdering::Acquire, AtomicOrdering::SequentiallyConsistent, true);
+}
+
+#[test]
+#[should_panic(expected = "expected pointer to i64")]
+fn test_cmpxchg_mismatched_type() {
+    let ctx = Context::new();
+    let module = Module::new("cmpxchg", &ctx);
+    let func = module.add_function("cas", Type::get::<fn(Option<&u32>, u64, u64)>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_cmpxchg(&func[0], &func[1], &func[2], AtomicOrdering::SequentiallyConsistent, AtomicOrdering::Monotonic, false);
+}
+
+#[test]
+fn test_atomic_rmw() {
+    let ctx = Context::new();
+    let module = Module::new("rmw", &ctx);
+    let func = module.add_function("fetch_max", Type::get::<fn(Option<&u32>, u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let old = builder.build_atomic_rmw(AtomicOp::UMax, &func[0], &func[1], AtomicOrdering::AcquireRelease);
+    builder.build_ret(old);
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let value = AtomicU32::new(5);
+    ee.with_function(func, |fetch_max: extern fn(Option<&u32>, u32) -> u32| {
+        let fetch_max: extern fn(&AtomicU32, u32) -> u32 = unsafe { mem::transmute(fetch_max) };
+        assert_eq!(fetch_max(&value, 9), 5);
+        assert_eq!(fetch_max(&value, 7), 9);
+        assert_eq!(value.load(Ordering::SeqCst), 9);
+    });
+}
+
+#[test]
+fn test_lowered_atomics() {
+    let ctx = Context::new();
+    let module = Module::new("lowered", &ctx);
+    let func = module.add_function("update", Type::get::<fn(Option<&u32>, Option<&u64>, u64) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let atomics = builder.with_atomics(AtomicOptions {max_width: 32, lowering: AtomicLowering::Libcall});
+    atomics.build_atomic_rmw(AtomicOp::UMax, &func[1], &func[2]);
+    let value = atomics.build_atomic_load(&func[0]);
+    atomics.build_ret(value);
+    module.verify().unwrap();
+    assert_eq!(module.get_function("__atomic_compare_exchange_8").unwrap().get_signature().num_params(), 5);
+    assert!(module.get_function("__atomic_load_4").is_some());
+    assert!(!format!("{:?}", module).contains("atomicrmw"));
+}
+
+#[test]
+fn test_fence() {
+    let ctx = Context::new();
+    let module = Module::new("fence", &ctx);
+    let func = module.add_function("publish", Type::get::<fn(Option<&u32>)>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_store(1u32.compile(&ctx), &func[0]);
+    builder.build_fence(AtomicOrdering::Release, false);
+    builder.build_fence(AtomicOrdering::SequentiallyConsistent, true);
+    builder.build_ret_void();
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_volatile_aligned_access() {
+    let ctx = Context::new();
+    let module = Module::new("mmio", &ctx);
+    let func = module.add_function("poke", Type::get::<fn(Option<&u32>)>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let store = Instruction::from_super(builder.build_store(1u32.compile(&ctx), &func[0])).unwrap();
+    assert!(!store.is_volatile());
+    store.set_volatile(true);
+    store.set_alignment(16);
+    assert!(store.is_volatile());
+    assert_eq!(store.get_alignment(), 16);
+    builder.build_ret_void();
+    module.verify().unwrap();
+}
@@ -27,3 +27,131 @@ fn test_argument_attributes() {
     assert!(!x.has_attribute(ByVal));
     assert!(x.has_attribute(InReg));
 }
+
+#[test]
+fn test_stack_probe_attributes() {
+    let ctx = Context::new();
+    let module = Module::new("simple", &ctx);
+    let func = module.add_function("main", Type::get::<fn() -> ()>(&ctx));
+    func.set_probe_stack("__rust_probestack");
+    func.set_stack_probe_size(8192);
+    let ir = format!("{:?}", module);
+    assert!(ir.contains("\"probe-stack\"=\"__rust_probestack\""));
+    assert!(ir.contains("\"stack-probe-size\"=\"8192\""));
+}
+
+#[test]
+fn test_fast_math_attributes() {
+    let ctx = Context::new();
+    let module = Module::new("simple", &ctx);
+    let func = module.add_function("main", Type::get::<fn() -> ()>(&ctx));
+    func.set_fast_math(FastMathFlags { no_nans: true, ..FastMathFlags::default() });
+    let ir = format!("{:?}", module);
+    assert!(ir.contains("\"no-nans-fp-math\"=\"true\""));
+    assert!(ir.contains("\"unsafe-fp-math\"=\"false\""));
+    assert!(FastMathFlags::fast().is_fast());
+}
+
+#[test]
+fn test_call_site() {
+    let ctx = Context::new();
+    let module = Module::new("simple", &ctx);
+    let callee = module.add_function("callee", Type::get::<fn(u32, u64) -> ()>(&ctx));
+    callee.set_call_conv(CallingConv::Fast);
+    assert_eq!(callee.get_call_conv(), CallingConv::Fast);
+    let func = module.add_function("main", Type::get::<fn(u32, u64) -> ()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let call = CallSite::from_super(builder.build_call(callee, &[&func[0], &func[1]])).unwrap();
+    assert_eq!(call.get_called_function(), Some(callee));
+    assert_eq!(call.num_args(), 2);
+    assert_eq!(call.get_arg(1), &*func[1]);
+    assert_eq!(call.get_call_conv(), CallingConv::C);
+    call.set_call_conv(CallingConv::Fast);
+    assert_eq!(call.get_call_conv(), CallingConv::Fast);
+    call.add_arg_attribute(0, ZExt);
+    assert!(format!("{:?}", call).contains("i32 zeroext"));
+    call.remove_arg_attribute(0, ZExt);
+    assert!(!format!("{:?}", call).contains("zeroext"));
+    assert!(CallSite::from_super(&func[0]).is_none());
+    let personality = module.add_function("__gxx_personality_v0", Type::get::<fn() -> i32>(&ctx));
+    let (ok, catch) = (func.append("ok"), func.append("catch"));
+    let invoke = CallSite::from_super(builder.build_invoke(callee, &[&func[0], &func[1]], ok, catch)).unwrap();
+    assert_eq!(invoke.get_called_value(), &***callee);
+    assert_eq!(invoke.get_called_function(), Some(callee));
+    assert_eq!(invoke.num_args(), 2);
+    assert_eq!(invoke.get_arg(0), &*func[0]);
+    builder.position_at_end(ok);
+    builder.build_ret_void();
+    builder.position_at_end(catch);
+    let exn_ty = StructType::new(&ctx, &[Type::get::<Option<&u8>>(&ctx), Type::get::<i32>(&ctx)], false);
+    let pad = builder.build_landing_pad(exn_ty, personality, 0);
+    pad.set_cleanup(true);
+    builder.build_resume(pad);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_attribute_sets() {
+    let mut attrs = Attributes::from_slice(&[NoUnwind, ReadOnly]);
+    assert!(attrs.contains(NoUnwind) && attrs.contains(ReadOnly) && !attrs.contains(NoInline));
+    attrs.insert(NoInline);
+    attrs.remove(ReadOnly);
+    assert_eq!(attrs.to_vec(), vec![NoUnwind, NoInline]);
+    assert!(Attributes::empty().is_empty());
+    let ctx = Context::new();
+    let module = Module::new("simple", &ctx);
+    let func = module.add_function("main", Type::get::<fn(&u64) -> ()>(&ctx));
+    func.add_attributes(&[NoUnwind, ReadNone, NoInline]);
+    assert_eq!(func.get_attributes(), Attributes::from_slice(&[NoUnwind, NoInline, ReadNone]));
+    func[0].add_attributes(&[NoCapture, Alignment]);
+    let arg_attrs = func[0].get_attributes();
+    assert_eq!(arg_attrs.to_vec(), vec![Alignment, NoCapture]);
+    assert_eq!(arg_attrs.get_alignment(), Some(1));
+}
+
+#[test]
+fn test_target_attributes() {
+    let ctx = Context::new();
+    let module = Module::new("simple", &ctx);
+    let func = module.add_function("kernel", Type::get::<fn() -> ()>(&ctx));
+    func.set_target_cpu("skylake-avx512");
+    func.set_target_features(&TargetFeatures::new().add("avx512f").remove("avx512er"));
+    let ir = format!("{:?}", module);
+    assert!(ir.contains("\"target-cpu\"=\"skylake-avx512\""));
+    assert!(ir.contains("\"target-features\"=\"+avx512f,-avx512er\""));
+}
+
+#[test]
+fn test_declare_spec() {
+    let ctx = Context::new();
+    let module = Module::new("libc", &ctx);
+    let (ptr, size) = (PointerType::new(Type::get::<i8>(&ctx)), Type::get::<u64>(&ctx));
+    let spec = FunctionSpec {
+        name: "memcpy",
+        ret: ptr,
+        params: &[(ptr, &[NoAlias, NoCapture]), (ptr, &[NoAlias, NoCapture, ReadOnly]), (size, &[])],
+        variadic: false,
+        call_conv: CallingConv::C,
+        attributes: &[NoUnwind]
+    };
+    let memcpy = module.declare(&spec).unwrap();
+    assert!(memcpy.has_attribute(NoUnwind));
+    assert!(memcpy[0].has_attributes(&[NoAlias, NoCapture]));
+    assert!(!memcpy[0].has_attribute(ReadOnly));
+    assert!(memcpy[1].has_attributes(&[NoAlias, NoCapture, ReadOnly]));
+    assert!(memcpy[2].get_attributes().is_empty());
+    module.declare(&spec).unwrap();
+    assert_eq!((&*module).into_iter().count(), 1);
+    let printf = module.declare(&FunctionSpec {
+        name: "printf",
+        ret: Type::get::<i32>(&ctx),
+        params: &[(ptr, &[NoCapture, ReadOnly])],
+        variadic: true,
+        call_conv: CallingConv::C,
+        attributes: &[]
+    }).unwrap();
+    assert!(printf.get_signature().is_variadic());
+    assert!(module.declare(&FunctionSpec { name: "memcpy", variadic: true, ..spec }).is_err());
+    module.verify().unwrap();
+}
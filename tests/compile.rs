@@ -0,0 +1,548 @@
+extern crate llvm;
+extern crate libc;
+use llvm::*;
+
+#[test]
+fn test_compile_types() {
+    let ctx = Context::new();
+    let array = ArrayType::from_super(Type::get::<[u8; 4]>(&ctx)).unwrap();
+    assert_eq!(array.get_length(), 4);
+    assert_eq!(array.get_element(), Type::get::<u8>(&ctx));
+    let tuple = StructType::from_super(Type::get::<(u8, f64)>(&ctx)).unwrap();
+    assert_eq!(tuple.get_elements(), vec![Type::get::<u8>(&ctx), Type::get::<f64>(&ctx)]);
+    let pointer = PointerType::from_super(Type::get::<Option<&u32>>(&ctx)).unwrap();
+    assert_eq!(pointer.get_element(), Type::get::<u32>(&ctx));
+    assert!(Type::get::<()>(&ctx).is_void());
+}
+
+#[test]
+fn test_array_round_trip() {
+    let ctx = Context::new();
+    let module = Module::new("array", &ctx);
+    let func = module.add_function("second", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let array = [10u32, 20, 30].compile(&ctx);
+    let second = builder.build_extract_value(array, &[1]);
+    builder.build_ret(builder.build_add(second, &func[0]));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |second: extern fn(u32) -> u32| {
+        assert_eq!(second(1), 21);
+    });
+}
+
+#[test]
+fn test_nested_aggregate() {
+    let ctx = Context::new();
+    let module = Module::new("nested", &ctx);
+    let func = module.add_function("swap", Type::get::<fn(u32, u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let pair = Type::get::<(u32, u32)>(&ctx);
+    let outer = StructType::new(&ctx, &[Type::get::<u32>(&ctx), pair], false);
+    let agg = builder.build_insert_value(Value::new_undef(outer), &func[0], &[1, 0]);
+    let agg = builder.build_insert_value(agg, &func[1], &[1, 1]);
+    let high = builder.build_mul(builder.build_extract_value(agg, &[1, 1]), 10u32.compile(&ctx));
+    builder.build_ret(builder.build_add(high, builder.build_extract_value(agg, &[1, 0])));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |swap: extern fn(u32, u32) -> u32| {
+        assert_eq!(swap(1, 2), 21);
+    });
+}
+
+#[test]
+fn test_vector_elements() {
+    let ctx = Context::new();
+    let module = Module::new("vectors", &ctx);
+    let func = module.add_function("interleave", Type::get::<fn(u32, u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let zero = 0u32.compile(&ctx);
+    let left = Value::new_vector(&[zero, 1u32.compile(&ctx)]);
+    let right = builder.build_insert_element(Value::new_undef(VectorType::new(Type::get::<u32>(&ctx), 2)), &func[0], zero);
+    let right = builder.build_insert_element(right, &func[1], 1u32.compile(&ctx));
+    let shuffled = builder.build_shuffle_vector(left, right, &[3, 2, 1, 0]);
+    let last = builder.build_extract_element(shuffled, 3u32.compile(&ctx));
+    let first = builder.build_extract_element(shuffled, zero);
+    builder.build_ret(builder.build_add(builder.build_mul(first, 10u32.compile(&ctx)), last));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |interleave: extern fn(u32, u32) -> u32| {
+        assert_eq!(interleave(5, 7), 70);
+    });
+}
+
+#[test]
+fn test_vector_select() {
+    let ctx = Context::new();
+    let module = Module::new("select", &ctx);
+    let func = module.add_function("clamp", Type::get::<fn(u32, u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let zero = 0u32.compile(&ctx);
+    let one = 1u32.compile(&ctx);
+    let undef = Value::new_undef(VectorType::new(Type::get::<u32>(&ctx), 2));
+    let values = builder.build_insert_element(builder.build_insert_element(undef, &func[0], zero), &func[1], one);
+    let limit = Value::new_vector(&[10u32.compile(&ctx), 10u32.compile(&ctx)]);
+    let small = builder.build_icmp(values, limit, IntPredicate::UnsignedLessThan);
+    let clamped = builder.build_select(small, values, limit);
+    let first = builder.build_extract_element(clamped, zero);
+    let second = builder.build_extract_element(clamped, one);
+    builder.build_ret(builder.build_add(builder.build_mul(first, 100u32.compile(&ctx)), second));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |clamp: extern fn(u32, u32) -> u32| {
+        assert_eq!(clamp(3, 42), 310);
+    });
+}
+
+#[test]
+fn test_variadic() {
+    let ctx = Context::new();
+    let module = Module::new("variadic", &ctx);
+    let i64_ty = Type::get::<i64>(&ctx);
+    let sum = module.add_function("sum", FunctionType::new_variadic(i64_ty, &[Type::get::<i32>(&ctx)]));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(sum.append("entry"));
+    // big enough for the va_list of every target this runs on
+    let list = builder.build_alloca(ArrayType::new(i64_ty, 4));
+    builder.build_va_start(list);
+    let first = builder.build_va_arg(list, i64_ty);
+    let second = builder.build_va_arg(list, i64_ty);
+    builder.build_va_end(list);
+    builder.build_ret(builder.build_sub(first, second));
+    let func = module.add_function("call_sum", Type::get::<fn(i64, i64) -> i64>(&ctx));
+    builder.position_at_end(func.append("entry"));
+    builder.build_ret(builder.build_call(sum, &[2i32.compile(&ctx), &func[0], &func[1]]));
+    module.verify().unwrap();
+    assert!(sum.get_signature().is_variadic());
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |call_sum: extern fn(i64, i64) -> i64| {
+        assert_eq!(call_sum(50, 8), 42);
+    });
+}
+
+#[test]
+fn test_build_cast() {
+    let ctx = Context::new();
+    let module = Module::new("casts", &ctx);
+    let func = module.add_function("convert", Type::get::<fn(i8) -> u16>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let wide = builder.build_cast(&func[0], Type::get::<i64>(&ctx), true);
+    let real = builder.build_cast(wide, Type::get::<f64>(&ctx), true);
+    let narrow = builder.build_cast(real, Type::get::<f32>(&ctx), true);
+    let real = builder.build_mul(builder.build_cast(narrow, Type::get::<f64>(&ctx), true), (-2.0f64).compile(&ctx));
+    let result = builder.build_cast(real, Type::get::<u16>(&ctx), false);
+    assert!(builder.build_cast(result, Type::get::<u16>(&ctx), false) == result);
+    builder.build_ret(result);
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |convert: extern fn(i8) -> u16| {
+        assert_eq!(convert(-21), 42);
+    });
+}
+
+#[test]
+fn test_option_round_trip() {
+    let ctx = Context::new();
+    let module = Module::new("option", &ctx);
+    let value = 42u32;
+    let func = module.add_function("load", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let loaded = builder.build_load(Some(&value).compile(&ctx));
+    builder.build_ret(builder.build_add(loaded, &func[0]));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |load: extern fn(u32) -> u32| {
+        assert_eq!(load(1), 43);
+    });
+}
+
+#[test]
+fn test_ptr_diff() {
+    let ctx = Context::new();
+    let module = Module::new("diff", &ctx);
+    let func = module.add_function("diff", Type::get::<fn(Option<&u32>, Option<&u32>) -> i64>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let diff = builder.build_ptr_diff(&func[0], &func[1]);
+    let zero = 0i64.compile(&ctx);
+    let diff = builder.build_select(builder.build_is_null(&func[1]), zero, diff);
+    builder.build_ret(diff);
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let array = [1u32, 2, 3, 4];
+    ee.with_function(func, |diff: extern fn(Option<&u32>, Option<&u32>) -> i64| {
+        assert_eq!(diff(Some(&array[3]), Some(&array[1])), 2);
+        assert_eq!(diff(Some(&array[3]), None), 0);
+    });
+}
+
+#[test]
+fn test_struct_gep() {
+    let ctx = Context::new();
+    let module = Module::new("fields", &ctx);
+    let init = Value::new_struct(&ctx, &[7u64.compile(&ctx), 9u64.compile(&ctx)], false);
+    let global = module.add_global_variable("pair", init);
+    let func = module.add_function("sum", Type::get::<fn() -> u64>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let zero = 0u32.compile(&ctx);
+    let first = builder.build_load(Value::new_inbounds_gep(global, &[zero, zero]));
+    let second = builder.build_load(builder.build_struct_gep(global, 1));
+    builder.build_ret(builder.build_add(first, second));
+    module.verify().unwrap();
+    let one = 1u32.compile(&ctx);
+    assert!(format!("{:?}", builder.build_gep(global, &[zero, one])).contains("getelementptr inbounds"));
+    let plain = format!("{:?}", builder.build_plain_gep(global, &[zero, one]));
+    assert!(plain.contains("getelementptr") && !plain.contains("inbounds"));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |sum: extern fn() -> u64| {
+        assert_eq!(sum(), 16);
+    });
+}
+
+#[test]
+fn test_wrap_flags() {
+    let ctx = Context::new();
+    let module = Module::new("flags", &ctx);
+    let func = module.add_function("average", Type::get::<fn(i32, i32) -> i32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let sum = builder.build_nsw_add(&func[0], &func[1]);
+    let half = builder.build_exact_sdiv(builder.build_nsw_mul(sum, 2i32.compile(&ctx)), 4i32.compile(&ctx));
+    builder.build_ret(half);
+    module.verify().unwrap();
+    assert!(format!("{:?}", sum).contains("add nsw"));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |average: extern fn(i32, i32) -> i32| {
+        assert_eq!(average(4, 6), 5);
+    });
+}
+
+#[test]
+fn test_tail_call() {
+    let ctx = Context::new();
+    let module = Module::new("tail", &ctx);
+    let func = module.add_function("identity", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_ret(&func[0]);
+    let caller = module.add_function("caller", Type::get::<fn(u32) -> u32>(&ctx));
+    builder.position_at_end(caller.append("entry"));
+    fn as_call(inst: &Value) -> &value::Call {
+        value::Call::from_super(Instruction::from_super(inst).unwrap()).unwrap()
+    }
+    let call = as_call(builder.build_call(func, &[&caller[0]]));
+    assert!(!call.is_tail_call());
+    call.set_tail_call(true);
+    assert!(call.is_tail_call());
+    assert!(as_call(builder.build_tail_call(func, &[&caller[0]])).is_tail_call());
+    builder.build_ret(call);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_inline_asm() {
+    let ctx = Context::new();
+    let module = Module::new("asm", &ctx);
+    let sig = FunctionType::new(Type::get::<u32>(&ctx), &[Type::get::<u32>(&ctx)]);
+    let asm = InlineAsm::new(sig, "mov $1, $0", "=r,r", true, false);
+    assert!(InlineAsm::from_super(asm).is_some());
+    let func = module.add_function("copy", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let result = builder.build_call(asm, &[&func[0]]);
+    builder.build_ret(result);
+    module.verify().unwrap();
+    assert!(format!("{:?}", module).contains("asm sideeffect \"mov $1, $0\", \"=r,r\""));
+}
+
+#[test]
+fn test_predicates() {
+    use IntPredicate::*;
+    use RealPredicate::*;
+    let ctx = Context::new();
+    let module = Module::new("predicates", &ctx);
+    let func = module.add_function("compare", Type::get::<fn(i32, i32) -> ()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let int_preds = [Equal, NotEqual, UnsignedGreaterThan, UnsignedGreaterThanOrEqual, UnsignedLessThan, UnsignedLessThanOrEqual,
+                     SignedGreaterThan, SignedGreaterThanOrEqual, SignedLessThan, SignedLessThanOrEqual];
+    for &pred in &int_preds {
+        let inst = builder.build_icmp(&func[0], &func[1], pred);
+        assert_eq!(Instruction::from_super(inst).unwrap().get_int_predicate(), Some(pred));
+        assert_eq!(pred.inverse().inverse(), pred);
+        assert_eq!(pred.swap_operands().swap_operands(), pred);
+        for &(a, b) in &[(-1i32, 1i32), (1, 1), (3, -2)] {
+            let (a, b) = (a.compile(&ctx), b.compile(&ctx));
+            let result = builder.build_icmp(a, b, pred);
+            assert!(builder.build_icmp(a, b, pred.inverse()) != result);
+            assert_eq!(builder.build_icmp(b, a, pred.swap_operands()), result);
+        }
+    }
+    assert_eq!(builder.build_icmp((-1i32).compile(&ctx), 1i32.compile(&ctx), SignedLessThan), true.compile(&ctx));
+    assert_eq!(builder.build_icmp((-1i32).compile(&ctx), 1i32.compile(&ctx), UnsignedLessThan), false.compile(&ctx));
+    let real_preds = [False, OrderedEqual, OrderedGreaterThan, OrderedGreaterThanOrEqual, OrderedLessThan, OrderedLessThanOrEqual,
+                      OrderedNotEqual, Ordered, Unordered, UnorderedEqual, UnorderedGreaterThan, UnorderedGreaterThanOrEqual,
+                      UnorderedLessThan, UnorderedLessThanOrEqual, UnorderedNotEqual, True];
+    for &pred in &real_preds {
+        assert_eq!(pred.inverse().inverse(), pred);
+        assert_eq!(pred.swap_operands().swap_operands(), pred);
+        for &(a, b) in &[(-1.5f64, 2.0f64), (2.0, 2.0), (::std::f64::NAN, 1.0)] {
+            let (a, b) = (a.compile(&ctx), b.compile(&ctx));
+            let result = builder.build_fcmp(a, b, pred);
+            assert!(builder.build_fcmp(a, b, pred.inverse()) != result);
+            assert_eq!(builder.build_fcmp(b, a, pred.swap_operands()), result);
+        }
+    }
+    builder.build_ret_void();
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_mem_intrinsics() {
+    let ctx = Context::new();
+    let module = Module::new("mem", &ctx);
+    let func = module.add_function("copy", Type::get::<fn(u64) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let int = Type::get::<u32>(&ctx);
+    let (src, dst) = (builder.build_array_alloca(int, 4u32.compile(&ctx)), builder.build_array_alloca(int, 4u32.compile(&ctx)));
+    let element = |array, index: u32| builder.build_gep(array, &[index.compile(&ctx)]);
+    for index in 0..4 {
+        builder.build_store((index + 1).compile(&ctx), element(src, index));
+    }
+    builder.build_memset(dst, 4, 0u8.compile(&ctx), 16u64.compile(&ctx));
+    builder.build_memcpy(dst, 4, src, 4, &func[0]);
+    builder.build_memmove(dst, 4, dst, 4, 0u32.compile(&ctx));
+    let (second, third) = (builder.build_load(element(dst, 1)), builder.build_load(element(dst, 2)));
+    builder.build_ret(builder.build_add(second, third));
+    module.verify().unwrap();
+    assert!(module.get_function("llvm.memset.p0i8.i64").is_some());
+    assert!(module.get_function("llvm.memcpy.p0i8.p0i8.i64").is_some());
+    assert!(module.get_function("llvm.memmove.p0i8.p0i8.i32").is_some());
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |copy: extern fn(u64) -> u32| {
+        assert_eq!(copy(0), 0);
+        assert_eq!(copy(8), 2);
+        assert_eq!(copy(12), 5);
+    });
+}
+
+#[test]
+fn test_mem_intrinsics_address_space() {
+    let ctx = Context::new();
+    let module = Module::new("mem", &ctx);
+    let int = Type::get::<u32>(&ctx);
+    let global = module.add_global_in_address_space("shared", ArrayType::new(int, 4), AddressSpace::Global);
+    let func = module.add_function("fill", Type::get::<fn()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let local = builder.build_array_alloca(int, 4u32.compile(&ctx));
+    builder.build_memset(global, 4, 0u8.compile(&ctx), 16u64.compile(&ctx));
+    builder.build_memcpy(global, 4, local, 4, 16u64.compile(&ctx));
+    builder.build_memmove(local, 4, global, 4, 16u32.compile(&ctx));
+    builder.build_ret_void();
+    module.verify().unwrap();
+    assert!(module.get_function("llvm.memset.p1i8.i64").is_some());
+    assert!(module.get_function("llvm.memcpy.p1i8.p0i8.i64").is_some());
+    assert!(module.get_function("llvm.memmove.p0i8.p1i8.i32").is_some());
+}
+
+#[test]
+fn test_with_overflow() {
+    let ctx = Context::new();
+    let module = Module::new("overflow", &ctx);
+    let builder = Builder::new(&ctx);
+    let build = |name| {
+        let func = module.add_function(name, Type::get::<fn(i32, i32) -> bool>(&ctx));
+        builder.position_at_end(func.append("entry"));
+        let (_, overflowed) = match name {
+            "sadd" => builder.build_sadd_with_overflow(&func[0], &func[1]),
+            "umul" => builder.build_umul_with_overflow(&func[0], &func[1]),
+            _ => builder.build_usub_with_overflow(&func[0], &func[1])
+        };
+        builder.build_ret(overflowed);
+        func
+    };
+    let (sadd, umul, usub) = (build("sadd"), build("umul"), build("usub"));
+    module.verify().unwrap();
+    assert!(module.get_function("llvm.sadd.with.overflow.i32").is_some());
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(sadd, |sadd: extern fn(i32, i32) -> bool| {
+        assert!(!sadd(1, 2));
+        assert!(sadd(i32::max_value(), 1));
+    });
+    ee.with_function(umul, |umul: extern fn(i32, i32) -> bool| {
+        assert!(!umul(1 << 15, 1 << 15));
+        assert!(umul(1 << 16, 1 << 16));
+    });
+    ee.with_function(usub, |usub: extern fn(i32, i32) -> bool| {
+        assert!(!usub(2, 1));
+        assert!(usub(1, 2));
+    });
+}
+
+#[test]
+#[should_panic(expected = "expected integer operands")]
+fn test_with_overflow_float() {
+    let ctx = Context::new();
+    let module = Module::new("overflow", &ctx);
+    let func = module.add_function("sadd", Type::get::<fn(f64, f64)>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_sadd_with_overflow(&func[0], &func[1]);
+}
+
+#[test]
+fn test_global_string_ptr() {
+    let ctx = Context::new();
+    let module = Module::new("strings", &ctx);
+    let func = module.add_function("third", Type::get::<fn() -> u8>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let text = builder.build_global_string_ptr("a\0b");
+    assert_eq!(text.get_type(), PointerType::new(Type::get::<i8>(&ctx)));
+    builder.build_ret(builder.build_load(builder.build_gep(text, &[2u32.compile(&ctx)])));
+    module.verify().unwrap();
+    assert!(format!("{:?}", module).contains("private unnamed_addr constant [4 x i8] c\"a\\00b\\00\""));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |third: extern fn() -> u8| {
+        assert_eq!(third(), b'b');
+    });
+}
+
+#[test]
+fn test_code_sizes() {
+    let ctx = Context::new();
+    let module = Module::new("sizes", &ctx);
+    let builder = Builder::new(&ctx);
+    for name in &["first", "second"] {
+        let func = module.add_function(name, Type::get::<fn(u32) -> u32>(&ctx));
+        builder.position_at_end(func.append("entry"));
+        builder.build_ret(builder.build_add(&func[0], 1u32.compile(&ctx)));
+    }
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let table = FrameTable::new(&*ee, &[&module]);
+    let sizes = table.code_sizes();
+    assert_eq!(sizes.len(), 2);
+    if cfg!(target_os = "linux") {
+        for size in &sizes {
+            let bytes = unsafe { size.as_bytes() }.unwrap();
+            assert!(!bytes.is_empty() && bytes.len() < 64, "{}", size);
+        }
+    }
+}
+
+#[test]
+fn test_jit_debug_info() {
+    let ctx = Context::new();
+    let module = Module::parse_ir(&ctx, r#"
+define i32 @next(i32 %x) {
+  %y = add i32 %x, 1, !dbg !7
+  ret i32 %y, !dbg !7
+}
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!8}
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: "test", isOptimized: false, runtimeVersion: 0, emissionKind: 1, subprograms: !2)
+!1 = !DIFile(filename: "next.c", directory: "/")
+!2 = !{!3}
+!3 = distinct !DISubprogram(name: "next", scope: !1, file: !1, line: 1, type: !4, isLocal: false, isDefinition: true, scopeLine: 1, isOptimized: false, function: i32 (i32)* @next)
+!4 = !DISubroutineType(types: !5)
+!5 = !{!6, !6}
+!6 = !DIBasicType(name: "int", size: 32, align: 32, encoding: DW_ATE_signed)
+!7 = !DILocation(line: 2, column: 3, scope: !3)
+!8 = !{i32 2, !"Debug Info Version", i32 3}
+"#).unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let next = module.get_function("next").unwrap();
+    ee.with_function(next, |next: extern fn(i32) -> i32| {
+        assert_eq!(next(41), 42);
+    });
+    let table = FrameTable::new(&*ee, &[&module]);
+    if cfg!(target_os = "linux") {
+        assert!(table.has_debug_info(next));
+    }
+    let plain = Module::new("plain", &ctx);
+    let func = plain.add_function("twice", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_ret(builder.build_add(&func[0], &func[0]));
+    let plain_ee = JitEngine::new(&plain, JitOptions {opt_level: 0}).unwrap();
+    assert!(!FrameTable::new(&*plain_ee, &[&plain]).has_debug_info(func));
+}
+
+#[test]
+fn test_symbolize() {
+    let ctx = Context::new();
+    let module = Module::new("trace", &ctx);
+    let builder = Builder::new(&ctx);
+    let func = module.add_function("twice", Type::get::<fn(u32) -> u32>(&ctx));
+    builder.position_at_end(func.append("entry"));
+    builder.build_ret(builder.build_add(&func[0], &func[0]));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let table = FrameTable::new(&*ee, &[&module]);
+    let start = table.get_address(func).unwrap();
+    match table.symbolize(start + 1) {
+        Frame::Jit { function, offset } => assert!(function == func && offset == 1),
+        Frame::Host(_) => panic!("expected a JIT frame")
+    }
+    assert!(match table.symbolize(start - 1) { Frame::Host(address) => address == start - 1, _ => false });
+    if let Some(size) = table.code_sizes()[0].size {
+        assert!(match table.symbolize(start + size) { Frame::Host(_) => true, _ => false });
+    }
+    assert_eq!(table.symbolize_trace(&[start, 16]).len(), 2);
+    if cfg!(target_os = "linux") {
+        // an empty section is just its zero terminator
+        let eh_frame = [0u32; 1];
+        unsafe {
+            register_frame(eh_frame.as_ptr() as *const _);
+            deregister_frame(eh_frame.as_ptr() as *const _);
+        }
+    }
+}
+
+extern "C" fn scale(value: u32, factor: f64) -> u32 {
+    (value as f64 * factor) as u32
+}
+
+#[test]
+fn test_recorder() {
+    let ctx = Context::new();
+    let module = Module::new("record", &ctx);
+    let host = module.add_function("scale", Type::get::<fn(u32, f64) -> u32>(&ctx));
+    let func = module.add_function("run", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let once = builder.build_call(host, &[&func[0], 2.0f64.compile(&ctx)]);
+    builder.build_ret(builder.build_call(host, &[once, 0.5f64.compile(&ctx)]));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let recorder = Recorder::new(&*ee);
+    unsafe { recorder.add_host_function(host, scale as *const libc::c_void) };
+    module.verify().unwrap();
+    recorder.with_function(func, |run: extern fn(u32) -> u32| {
+        assert_eq!(run(3), 3);
+    });
+    assert_eq!(recorder.calls(), vec![
+        Call { function: "scale".to_owned(), args: vec![3, 2.0f64.to_bits()] },
+        Call { function: "scale".to_owned(), args: vec![6, 0.5f64.to_bits()] }
+    ]);
+    recorder.clear();
+    assert!(recorder.calls().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "can't log arguments of type { i32, i32 } to pair")]
+fn test_recorder_aggregate() {
+    let ctx = Context::new();
+    let module = Module::new("record", &ctx);
+    let host = module.add_function("pair", Type::get::<fn((u32, u32))>(&ctx));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    let recorder = Recorder::new(&*ee);
+    unsafe { recorder.add_host_function(host, 0 as *const libc::c_void) };
+}
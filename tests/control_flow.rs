@@ -0,0 +1,312 @@
+extern crate llvm;
+use llvm::*;
+use std::panic;
+
+#[test]
+fn test_loop_while() {
+    let ctx = Context::new();
+    let module = Module::new("sum", &ctx);
+    let func = module.add_function("sum", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let (i, total) = (builder.build_alloca(Type::get::<u32>(&ctx)), builder.build_alloca(Type::get::<u32>(&ctx)));
+    builder.build_store(0u32.compile(&ctx), i);
+    builder.build_store(0u32.compile(&ctx), total);
+    builder.loop_while(|b| b.build_icmp(b.build_load(i), &func[0], IntPredicate::UnsignedLessThan), |b| {
+        let current = b.build_load(i);
+        let odd = b.build_and(current, 1u32.compile(&ctx));
+        let step = b.if_then_else(b.build_icmp(odd, 0u32.compile(&ctx), IntPredicate::Equal), |_| current, |_| 0u32.compile(&ctx));
+        b.build_store(b.build_add(b.build_load(total), step), total);
+        b.build_store(b.build_add(current, 1u32.compile(&ctx)), i);
+    });
+    builder.build_ret(builder.build_load(total));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |sum: extern fn(u32) -> u32| {
+        assert_eq!(sum(7), 0 + 2 + 4 + 6);
+    });
+}
+
+#[test]
+fn test_finalize() {
+    let ctx = Context::new();
+    let module = Module::new("finalize", &ctx);
+    let func = module.add_function("abort", Type::get::<fn() -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    assert!(builder.current_function().is_none());
+    builder.position_at_end(func.append("entry"));
+    assert_eq!(builder.current_function(), Some(func));
+    builder.ret(Some(1u32.compile(&ctx)));
+    let dead = func.append("dead");
+    assert_eq!(builder.finalize(func, Unterminated::Fail), Err(UnterminatedBlock {
+        function: "abort".to_owned(),
+        block: "dead".to_owned()
+    }));
+    builder.finalize(func, Unterminated::Unreachable).unwrap();
+    assert!(dead.get_terminator().is_some());
+    module.verify().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "after the terminator of block entry")]
+fn test_build_after_terminator() {
+    let ctx = Context::new();
+    let module = Module::new("terminated", &ctx);
+    let func = module.add_function("f", Type::get::<fn()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_ret_void();
+    builder.build_ret_void();
+}
+
+#[test]
+fn test_caught_build_after_terminator() {
+    let ctx = Context::new();
+    let module = Module::new("terminated", &ctx);
+    let func = module.add_function("f", Type::get::<fn()>(&ctx));
+    let builder = Builder::new(&ctx);
+    let entry = func.append("entry");
+    builder.position_at_end(entry);
+    builder.build_ret_void();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        builder.build_ret_void();
+    }));
+    assert!(result.is_err());
+    assert_eq!(entry.get_first(), entry.get_terminator());
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_insert_before_terminator() {
+    let ctx = Context::new();
+    let module = Module::new("instrument", &ctx);
+    let counter = module.add_global("counter", Type::get::<u32>(&ctx));
+    let func = module.add_function("f", Type::get::<fn()>(&ctx));
+    let builder = Builder::new(&ctx);
+    let entry = func.append("entry");
+    builder.position_at_end(entry);
+    let ret = Instruction::from_super(builder.build_ret_void()).unwrap();
+    builder.position_before(ret);
+    let load = Instruction::from_super(builder.build_load(counter)).unwrap();
+    let add = builder.build_add(load, 1u32.compile(&ctx));
+    let store = Instruction::from_super(builder.build_store(add, counter)).unwrap();
+    assert_eq!(store.get_next(), Some(ret));
+    let early = Instruction::from_super(builder.build_load(counter)).unwrap();
+    load.insert_before(early);
+    assert_eq!(load.get_previous(), Some(early));
+    assert_eq!(ret.get_previous(), Some(store));
+    assert!(ret.get_parent() == Some(entry));
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_switch_cases() {
+    let ctx = Context::new();
+    let module = Module::new("switch", &ctx);
+    let func = module.add_function("classify", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let other = func.append("other");
+    let switch = builder.build_empty_switch(&func[0], other, 2);
+    assert!(switch.get_default() == other);
+    for &(case, result) in &[(1u32, 10u32), (2, 20)] {
+        let block = func.append("case");
+        switch.add_case(case.compile(&ctx), block);
+        builder.position_at_end(block);
+        builder.build_ret(result.compile(&ctx));
+    }
+    builder.position_at_end(other);
+    builder.build_ret(0u32.compile(&ctx));
+    module.verify().unwrap();
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |classify: extern fn(u32) -> u32| {
+        assert_eq!((classify(1), classify(2), classify(3)), (10, 20, 0));
+    });
+}
+
+#[test]
+fn test_invoke_landing_pad() {
+    let ctx = Context::new();
+    let module = Module::new("eh", &ctx);
+    let may_throw = module.add_function("may_throw", Type::get::<fn()>(&ctx));
+    let personality = module.add_function("__gxx_personality_v0", Type::get::<fn() -> i32>(&ctx));
+    let func = module.add_function("f", Type::get::<fn()>(&ctx));
+    let builder = Builder::new(&ctx);
+    let (entry, ok, catch) = (func.append("entry"), func.append("ok"), func.append("catch"));
+    builder.position_at_end(entry);
+    builder.build_invoke(may_throw, &[], ok, catch);
+    builder.position_at_end(ok);
+    builder.build_ret_void();
+    builder.position_at_end(catch);
+    let exn_ty = StructType::new(&ctx, &[Type::get::<Option<&u8>>(&ctx), Type::get::<i32>(&ctx)], false);
+    let pad = builder.build_landing_pad(exn_ty, personality, 0);
+    pad.set_cleanup(true);
+    assert!(pad.is_cleanup());
+    builder.build_resume(pad);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_assumptions() {
+    let ctx = Context::new();
+    let module = Module::new("assume", &ctx);
+    let func = module.add_function("load", Type::get::<fn(Option<&u64>) -> u64>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    builder.build_assume_nonnull(&func[0]);
+    builder.build_assume_aligned(&func[0], 8);
+    builder.build_ret(builder.build_load(&func[0]));
+    module.verify().unwrap();
+    assert!(module.get_function("llvm.assume").is_some());
+}
+
+#[test]
+fn test_trip_count() {
+    let ctx = Context::new();
+    let module = Module::new("trips", &ctx);
+    let builder = Builder::new(&ctx);
+    let build = |name, start: i32, step: i32, predicate, bound: i32| {
+        let func = module.add_function(name, Type::get::<fn() -> ()>(&ctx));
+        let entry = func.append("entry");
+        let header = ParamBlock::new(func, "header", &[Type::get::<i32>(&ctx)]);
+        let body = func.append("body");
+        let exit = func.append("exit");
+        builder.position_at_end(entry);
+        header.build_jump(&builder, &[start.compile(&ctx)]);
+        builder.position_at_end(header.get_block());
+        let counter = header.get_param(0);
+        builder.build_cond_br(builder.build_icmp(counter, bound.compile(&ctx), predicate), body, Some(exit));
+        builder.position_at_end(body);
+        header.build_jump(&builder, &[builder.build_add(counter, step.compile(&ctx))]);
+        builder.position_at_end(exit);
+        builder.build_ret_void();
+        module.verify().unwrap();
+        (func, header.get_block())
+    };
+    let (func, header) = build("up", 0, 1, IntPredicate::SignedLessThan, 10);
+    assert_eq!(header.get_trip_count(), Some(10));
+    assert!(func.get_trip_counts() == vec![(header, 10)]);
+    let (_, header) = build("down", 10, -3, IntPredicate::SignedGreaterThan, -2);
+    assert_eq!(header.get_trip_count(), Some(4));
+    let (_, header) = build("exact", 0, 4, IntPredicate::NotEqual, 12);
+    assert_eq!(header.get_trip_count(), Some(3));
+    let (_, header) = build("skip", 0, 4, IntPredicate::NotEqual, 10);
+    assert_eq!(header.get_trip_count(), None);
+    let (_, header) = build("never", 5, 1, IntPredicate::SignedLessThan, 5);
+    assert_eq!(header.get_trip_count(), Some(0));
+    let (_, header) = build("overflow", 0, 1, IntPredicate::SignedGreaterThanOrEqual, 0);
+    assert_eq!(header.get_trip_count(), None);
+}
+
+#[test]
+fn test_block_frequencies() {
+    let ctx = Context::new();
+    let module = Module::new("hot", &ctx);
+    let func = module.add_function("count", Type::get::<fn(u32) -> ()>(&ctx));
+    let builder = Builder::new(&ctx);
+    let entry = func.append("entry");
+    let header = ParamBlock::new(func, "header", &[Type::get::<u32>(&ctx)]);
+    let body = func.append("body");
+    let odd = func.append("odd");
+    let latch = func.append("latch");
+    let exit = func.append("exit");
+    builder.position_at_end(entry);
+    header.build_jump(&builder, &[0u32.compile(&ctx)]);
+    builder.position_at_end(header.get_block());
+    let counter = header.get_param(0);
+    builder.build_cond_br(builder.build_icmp(counter, 10u32.compile(&ctx), IntPredicate::UnsignedLessThan), body, Some(exit));
+    builder.position_at_end(body);
+    builder.build_cond_br(builder.build_icmp(counter, &func[0], IntPredicate::Equal), odd, Some(latch));
+    builder.position_at_end(odd);
+    builder.build_br(latch);
+    builder.position_at_end(latch);
+    header.build_jump(&builder, &[builder.build_add(counter, 1u32.compile(&ctx))]);
+    builder.position_at_end(exit);
+    builder.build_ret_void();
+    module.verify().unwrap();
+    let frequencies = func.block_frequencies();
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    assert!(close(frequencies.get_frequency(entry), 1.0));
+    assert!(close(frequencies.get_frequency(header.get_block()), 11.0));
+    assert!(close(frequencies.get_frequency(body), 10.0));
+    assert!(close(frequencies.get_frequency(odd), 5.0));
+    assert!(close(frequencies.get_frequency(exit), 1.0));
+    assert!(close(frequencies.get_probability(header.get_block(), exit), 1.0 / 11.0));
+    assert!(close(frequencies.get_probability(entry, exit), 0.0));
+    assert!(frequencies.get_hottest()[0].0 == header.get_block());
+}
+
+#[test]
+fn test_indirect_br() {
+    let ctx = Context::new();
+    let module = Module::new("computed_goto", &ctx);
+    let func = module.add_function("pick", Type::get::<fn(bool) -> u32>(&ctx));
+    let entry = func.append("entry");
+    let (one, two) = (func.append("one"), func.append("two"));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(entry);
+    let address = builder.build_select(&func[0], one.get_address(), two.get_address());
+    let branch = builder.build_indirect_br(address, &[one]);
+    branch.add_destination(two);
+    assert!(entry.get_successors() == vec![one, two]);
+    builder.position_at_end(one);
+    builder.build_ret(1u32.compile(&ctx));
+    builder.position_at_end(two);
+    builder.build_ret(2u32.compile(&ctx));
+    module.verify().unwrap();
+    assert!(format!("{:?}", module).contains("blockaddress(@pick, %one)"));
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    ee.with_function(func, |pick: extern fn(bool) -> u32| {
+        assert_eq!(pick(true), 1);
+        assert_eq!(pick(false), 2);
+    });
+}
+
+#[test]
+fn test_phi_incoming() {
+    let ctx = Context::new();
+    let module = Module::new("phi", &ctx);
+    let func = module.add_function("pick", Type::get::<fn(u32) -> u32>(&ctx));
+    let (entry, one, two, merge) = (func.append("entry"), func.append("one"), func.append("two"), func.append("merge"));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(entry);
+    let cases = [(0u32.compile(&ctx), one), (1u32.compile(&ctx), two)];
+    builder.build_switch(&func[0], merge, &cases);
+    for &block in &[one, two] {
+        builder.position_at_end(block);
+        builder.build_br(merge);
+    }
+    builder.position_at_end(merge);
+    let phi = builder.build_phi(Type::get::<u32>(&ctx), &[(10u32.compile(&ctx), one)]);
+    phi.add_incomings(&[(20u32.compile(&ctx), two), (&*func[0], entry)]);
+    assert_eq!(phi.count_incoming(), 3);
+    assert_eq!(phi.get_incoming_value(1), 20u32.compile(&ctx));
+    assert!(phi.get_incoming_block(2) == entry);
+    assert_eq!(phi.get_incoming().into_iter().map(|(value, _)| value).collect::<Vec<_>>(), vec![10u32.compile(&ctx), 20u32.compile(&ctx), &*func[0]]);
+    builder.build_ret(phi);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_positioning() {
+    let ctx = Context::new();
+    let module = Module::new("positioning", &ctx);
+    let func = module.add_function("add", Type::get::<fn(u32) -> u32>(&ctx));
+    let entry = func.append("entry");
+    let builder = Builder::new(&ctx);
+    assert!(builder.get_insert_block().is_none());
+    builder.position_at_end(entry);
+    assert!(builder.get_insert_block() == Some(entry));
+    let ret = Instruction::from_super(builder.build_ret(&func[0])).unwrap();
+    builder.position_at_start(entry);
+    let second = builder.build_add(&func[0], 2u32.compile(&ctx));
+    builder.position_at_start(entry);
+    let first = builder.build_add(&func[0], 1u32.compile(&ctx));
+    builder.position_before(ret);
+    let third = Instruction::from_super(builder.build_add(first, second)).unwrap();
+    assert_eq!(entry.get_first(), Some(first));
+    assert_eq!(entry.get_last(), Some(&**ret));
+    assert_eq!(ret.get_previous(), Some(third));
+    assert!(builder.get_insert_block() == Some(entry));
+    module.verify().unwrap();
+}
@@ -0,0 +1,624 @@
+extern crate llvm;
+use llvm::*;
+
+#[test]
+fn test_filter_globals() {
+    let ctx = Context::new();
+    let module = Module::new("registry", &ctx);
+    let first = module.add_global_variable("first_test", 1u32.compile(&ctx));
+    first.set_section("tests");
+    let second = module.add_global_variable("second_test", 2u32.compile(&ctx));
+    second.set_section("tests");
+    second.set_linkage(Linkage::Internal);
+    module.add_global_variable("other", 3u32.compile(&ctx));
+    assert_eq!(module.globals_in_section("tests"), vec![first, second]);
+    assert_eq!(module.globals_with_linkage(Linkage::Internal), vec![second]);
+}
+
+#[test]
+fn test_append_to_global_array() {
+    let ctx = Context::new();
+    let module = Module::new("registry", &ctx);
+    let ty = Type::get::<u32>(&ctx);
+    module.append_to_global_array("registry", ty, &[1u32.compile(&ctx)]);
+    let registry = module.append_to_global_array("registry", ty, &[2u32.compile(&ctx), 3u32.compile(&ctx)]);
+    assert_eq!(registry.get_name(), Some("registry"));
+    assert_eq!(registry.get_initializer(), Some([1u32, 2, 3].compile(&ctx)));
+    assert_eq!(module.globals().count(), 1);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_binary_blob() {
+    let ctx = Context::new();
+    let module = Module::new("blobs", &ctx);
+    module.embed_binary_blob("shader", b"\x03\x02\x23\x07", ".rodata.shaders");
+    module.add_global_variable("zeros", [0u8, 0, 0, 0].compile(&ctx));
+    module.add_global_variable("words", [1u32, 2].compile(&ctx));
+    assert_eq!(module.get_binary_blob("shader"), Some(&b"\x03\x02\x23\x07"[..]));
+    assert_eq!(module.get_binary_blob("zeros"), None);
+    assert_eq!(module.get_binary_blob("words"), None);
+    assert_eq!(module.get_binary_blob("missing"), None);
+}
+
+#[test]
+fn test_add_notes() {
+    let ctx = Context::new();
+    let module = Module::new("notes", &ctx);
+    module.add_note(".note.first", "rust", 1, b"one");
+    module.add_note(".note.second", "rust", 2, b"two");
+    let used = GlobalVariable::from_super(module.get_global("llvm.used").unwrap()).unwrap();
+    assert_eq!(used.get_section(), Some("llvm.metadata"));
+    let used = format!("{:?}", used.get_initializer().unwrap());
+    assert!(used.contains("@.note.first") && used.contains("@.note.second"), "{}", used);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_rename_symbols() {
+    let ctx = Context::new();
+    let module = Module::new("program", &ctx);
+    let helper = module.add_function("helper", Type::get::<fn() -> u32>(&ctx));
+    helper.set_linkage(Linkage::Internal);
+    let main = module.add_function("main", Type::get::<fn() -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(helper.append("entry"));
+    builder.build_ret(1u32.compile(&ctx));
+    builder.position_at_end(main.append("entry"));
+    builder.build_ret(builder.build_call(helper, &[]));
+    module.add_prefix_to_internal_symbols("p1.");
+    assert_eq!(helper.get_name(), Some("p1.helper"));
+    assert_eq!(main.get_name(), Some("main"));
+    module.rename_symbols(&|name| if name == "main" { "p1.main".to_owned() } else { name.to_owned() });
+    assert_eq!(main.get_name(), Some("p1.main"));
+    assert!(module.get_function("p1.main").is_some());
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_internalize_and_prune() {
+    let ctx = Context::new();
+    let module = Module::new("runtime", &ctx);
+    let builder = Builder::new(&ctx);
+    for name in &["used", "unused", "entry"] {
+        let func = module.add_function(name, Type::get::<fn() -> u32>(&ctx));
+        builder.position_at_end(func.append("entry"));
+        if *name == "entry" {
+            let used = module.get_function("used").unwrap();
+            builder.build_ret(builder.build_call(used, &[]));
+        } else {
+            builder.build_ret(0u32.compile(&ctx));
+        }
+    }
+    module.internalize_and_prune(&["entry"]);
+    assert!(module.get_function("unused").is_none());
+    assert_eq!(module.get_function("used").unwrap().get_linkage(), Linkage::Internal);
+    assert_eq!(module.get_function("entry").unwrap().get_linkage(), Linkage::External);
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_run_always_inliner() {
+    let ctx = Context::new();
+    let module = Module::new("inline", &ctx);
+    let builder = Builder::new(&ctx);
+    let helper = module.add_function("helper", Type::get::<fn() -> u32>(&ctx));
+    helper.add_attribute(Attribute::AlwaysInline);
+    builder.position_at_end(helper.append("entry"));
+    builder.build_ret(1u32.compile(&ctx));
+    let main = module.add_function("main", Type::get::<fn() -> u32>(&ctx));
+    builder.position_at_end(main.append("entry"));
+    builder.build_ret(builder.build_call(helper, &[]));
+    module.run_always_inliner();
+    module.verify().unwrap();
+    assert_eq!(module.num_instructions(), 2);
+}
+
+#[test]
+fn test_strip_value_names() {
+    let ctx = Context::new();
+    let module = Module::new("names", &ctx);
+    let func = module.add_function("double", Type::get::<fn(u32) -> u32>(&ctx));
+    func[0].set_name("x");
+    let builder = Builder::new(&ctx);
+    let entry = func.append("entry");
+    builder.position_at_end(entry);
+    let doubled = builder.build_add(&func[0], &func[0]);
+    doubled.set_name("doubled");
+    builder.build_ret(doubled);
+    module.strip_debug_info();
+    module.strip_value_names();
+    assert_eq!(func[0].get_name(), Some(""));
+    assert_eq!(doubled.get_name(), Some(""));
+    assert_eq!(entry.get_name(), Some(""));
+    assert_eq!(func.get_name(), Some("double"));
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_optimize_function() {
+    let ctx = Context::new();
+    let module = Module::new("lazy", &ctx);
+    let builder = Builder::new(&ctx);
+    let build = |name| {
+        let func = module.add_function(name, Type::get::<fn(u32) -> u32>(&ctx));
+        builder.position_at_end(func.append("entry"));
+        let slot = builder.build_alloca(Type::get::<u32>(&ctx));
+        builder.build_store(&func[0], slot);
+        builder.build_ret(builder.build_load(slot));
+        func
+    };
+    let optimized = build("optimized");
+    build("untouched");
+    optimized.optimize(2);
+    module.verify().unwrap();
+    assert_eq!(module.num_instructions(), 5);
+}
+
+#[test]
+fn test_optimize_snapshots() {
+    let ctx = Context::new();
+    let module = Module::new("snapshots", &ctx);
+    let builder = Builder::new(&ctx);
+    let func = module.add_function("id", Type::get::<fn(u32) -> u32>(&ctx));
+    builder.position_at_end(func.append("entry"));
+    let slot = builder.build_alloca(Type::get::<u32>(&ctx));
+    builder.build_store(&func[0], slot);
+    builder.build_ret(builder.build_load(slot));
+    let mut phases = Vec::new();
+    module.run_passes_with_snapshots(&["mem2reg", "instcombine"], &mut |phase, module| {
+        module.verify().unwrap();
+        phases.push((phase.to_string(), module.num_instructions()));
+    }).unwrap();
+    assert_eq!(phases, vec![("mem2reg".to_string(), 1), ("instcombine".to_string(), 1)]);
+    assert!(module.run_passes_with_snapshots(&["mem2reg", "frobnicate"], &mut |_, _| panic!()).is_err());
+    phases.clear();
+    module.optimize_with_snapshots(2, 0, &mut |phase, _| phases.push((phase.to_string(), 0)));
+    assert_eq!(phases, vec![("function".to_string(), 0), ("module".to_string(), 0)]);
+}
+
+#[test]
+fn test_alias_analysis() {
+    let ctx = Context::new();
+    let module = Module::new("alias", &ctx);
+    let func = module.add_function("f", Type::get::<fn(Option<&u64>) -> ()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let pair = builder.build_alloca(Type::get::<(u32, u32)>(&ctx));
+    let other = builder.build_alloca(Type::get::<u64>(&ctx));
+    let first = builder.build_struct_gep(pair, 0);
+    let second = builder.build_struct_gep(pair, 1);
+    let whole = builder.build_bit_cast(pair, PointerType::new(Type::get::<u64>(&ctx)));
+    builder.build_ret_void();
+    let aa = module.alias_analysis();
+    assert_eq!(aa.alias(first, 4, second, 4), AliasResult::NoAlias);
+    assert_eq!(aa.alias(first, 4, pair, 4), AliasResult::MustAlias);
+    assert_eq!(aa.alias(whole, 8, second, 4), AliasResult::PartialAlias);
+    assert_eq!(aa.alias(pair, 8, other, 8), AliasResult::NoAlias);
+    assert_eq!(aa.alias(&func[0], 8, other, 8), AliasResult::MayAlias);
+    func[0].add_attribute(Attribute::NoAlias);
+    assert_eq!(aa.alias(&func[0], 8, other, 8), AliasResult::NoAlias);
+}
+
+#[test]
+fn test_clobbered_between() {
+    let ctx = Context::new();
+    let module = Module::new("clobber", &ctx);
+    let func = module.add_function("f", Type::get::<fn(u32) -> u32>(&ctx));
+    let opaque = module.add_function("opaque", Type::get::<fn() -> ()>(&ctx));
+    let pure = module.add_function("pure", Type::get::<fn() -> ()>(&ctx));
+    pure.add_attribute(Attribute::ReadNone);
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    let a = builder.build_alloca(Type::get::<u32>(&ctx));
+    let b = builder.build_alloca(Type::get::<u32>(&ctx));
+    let first = Instruction::from_super(builder.build_load(a)).unwrap();
+    builder.build_store(&func[0], b);
+    builder.build_call(pure, &[]);
+    let second = Instruction::from_super(builder.build_load(a)).unwrap();
+    builder.build_store(&func[0], a);
+    let third = Instruction::from_super(builder.build_load(a)).unwrap();
+    builder.build_call(opaque, &[]);
+    let fourth = Instruction::from_super(builder.build_load(b)).unwrap();
+    builder.build_ret(fourth);
+    let aa = module.alias_analysis();
+    assert!(!aa.is_clobbered_between(a, 4, first, second));
+    assert!(aa.is_clobbered_between(a, 4, second, third));
+    assert!(aa.is_clobbered_between(b, 4, third, fourth));
+    assert!(aa.is_clobbered_between(a, 4, second, first));
+}
+
+#[test]
+fn test_call_graph() {
+    let ctx = Context::new();
+    let module = Module::new("calls", &ctx);
+    let builder = Builder::new(&ctx);
+    let sig = Type::get::<fn() -> ()>(&ctx);
+    let leaf = module.add_function("leaf", sig);
+    let even = module.add_function("even", sig);
+    let odd = module.add_function("odd", sig);
+    let main = module.add_function("main", sig);
+    builder.position_at_end(leaf.append("entry"));
+    builder.build_ret_void();
+    builder.position_at_end(even.append("entry"));
+    builder.build_call(odd, &[]);
+    builder.build_call(leaf, &[]);
+    builder.build_ret_void();
+    builder.position_at_end(odd.append("entry"));
+    builder.build_call(even, &[]);
+    builder.build_ret_void();
+    builder.position_at_end(main.append("entry"));
+    builder.build_call(even, &[]);
+    builder.build_ret_void();
+    module.verify().unwrap();
+    let graph = module.call_graph();
+    assert_eq!(graph.get_functions().len(), 4);
+    assert_eq!(graph.get_callees(even), vec![odd, leaf]);
+    assert_eq!(graph.get_callers(even), vec![odd, main]);
+    assert!(!graph.calls_unknown(main));
+    let sccs = graph.get_sccs();
+    assert_eq!(sccs.len(), 3);
+    assert_eq!(sccs[0], vec![leaf]);
+    assert!(sccs[1].len() == 2 && sccs[1].contains(&even) && sccs[1].contains(&odd));
+    assert_eq!(sccs[2], vec![main]);
+}
+
+#[test]
+fn test_inline_cost() {
+    let ctx = Context::new();
+    let module = Module::new("inline", &ctx);
+    let builder = Builder::new(&ctx);
+    let sig = Type::get::<fn(u32) -> u32>(&ctx);
+    let small = module.add_function("small", sig);
+    builder.position_at_end(small.append("entry"));
+    builder.build_ret(builder.build_add(&small[0], 1u32.compile(&ctx)));
+    let big = module.add_function("big", sig);
+    builder.position_at_end(big.append("entry"));
+    let mut value: &Value = &big[0];
+    for _ in 0..100 {
+        value = builder.build_mul(value, &big[0]);
+    }
+    builder.build_ret(value);
+    let external = module.add_function("external", sig);
+    let main = module.add_function("main", sig);
+    builder.position_at_end(main.append("entry"));
+    let calls: Vec<&Value> = [small, big, external].iter().map(|&callee| builder.build_call(callee, &[&main[0]])).collect();
+    builder.build_ret(calls[0]);
+    module.verify().unwrap();
+    let cost = |index: usize, callee| InlineCost::new(Instruction::from_super(calls[index]).unwrap(), callee);
+    assert!(cost(0, small).should_inline());
+    assert!(!cost(1, big).should_inline());
+    assert_eq!(cost(2, external), InlineCost::Never);
+    big.add_attribute(Attribute::AlwaysInline);
+    assert_eq!(cost(1, big), InlineCost::Always);
+}
+
+#[test]
+fn test_reduce() {
+    let ctx = Context::new();
+    let module = Module::new("bug", &ctx);
+    module.add_global_variable("table", 7u32.compile(&ctx));
+    let helper = module.add_function("helper", Type::get::<fn(u32) -> u32>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(helper.append("entry"));
+    builder.build_ret(builder.build_add(&helper[0], 1u32.compile(&ctx)));
+    let spin = module.add_function("spin", Type::get::<fn(u32) -> u32>(&ctx));
+    let (entry, header, exit) = (spin.append("entry"), spin.append("header"), spin.append("exit"));
+    builder.position_at_end(entry);
+    builder.build_br(header);
+    builder.position_at_end(header);
+    let count = builder.build_phi(Type::get::<u32>(&ctx), &[(&spin[0], entry)]);
+    let next = builder.build_sub(count, 1u32.compile(&ctx));
+    count.add_incoming(next, header);
+    builder.build_cond_br(builder.build_icmp(next, 0u32.compile(&ctx), IntPredicate::Equal), exit, Some(header));
+    builder.position_at_end(exit);
+    builder.build_ret(next);
+    let func = module.add_function("compute", Type::get::<fn(u32, u32) -> u32>(&ctx));
+    builder.position_at_end(func.append("entry"));
+    let sum = builder.build_add(&func[0], &func[1]);
+    let product = builder.build_mul(sum, &func[1]);
+    let result = builder.build_call(helper, &[product]);
+    builder.build_ret(result);
+    module.verify().unwrap();
+    let reduced = module.reduce(&|module| {
+        let ir = format!("{:?}", module);
+        ir.contains(" mul ") && ir.contains("@spin(")
+    });
+    reduced.verify().unwrap();
+    let ir = format!("{:?}", reduced);
+    assert!(ir.contains(" mul "));
+    assert!(ir.contains("declare i32 @spin(i32)"));
+    assert!(!ir.contains("helper"));
+    assert!(!ir.contains("table"));
+    assert!(reduced.num_instructions() < module.num_instructions());
+}
+
+#[test]
+fn test_random_module() {
+    let ctx = Context::new();
+    let config = testing::RandomConfig::default();
+    for seed in 0..20 {
+        let module = testing::random_module(&ctx, seed, &config);
+        module.verify().unwrap();
+        assert_eq!(module.into_iter().count(), config.num_functions);
+        module.optimize(2, 0);
+        module.verify().unwrap();
+    }
+    let first = format!("{:?}", testing::random_module(&ctx, 7, &config));
+    assert_eq!(first, format!("{:?}", testing::random_module(&ctx, 7, &config)));
+    assert!(first != format!("{:?}", testing::random_module(&ctx, 8, &config)));
+}
+
+#[test]
+fn test_round_trip() {
+    let ctx = Context::new();
+    for seed in 0..10 {
+        testing::assert_round_trip(&testing::random_module(&ctx, seed, &testing::RandomConfig::default()));
+    }
+    let module = Module::new("predicates", &ctx);
+    let func = module.add_function("compare", Type::get::<fn(u32, f64) -> ()>(&ctx));
+    let builder = Builder::new(&ctx);
+    builder.position_at_end(func.append("entry"));
+    for &pred in &[IntPredicate::Equal, IntPredicate::UnsignedLessThan, IntPredicate::SignedGreaterThanOrEqual] {
+        builder.build_icmp(&func[0], 5u32.compile(&ctx), pred);
+    }
+    for &pred in &[RealPredicate::OrderedEqual, RealPredicate::UnorderedLessThan, RealPredicate::Ordered] {
+        builder.build_fcmp(&func[1], 1.5f64.compile(&ctx), pred);
+    }
+    builder.build_ret_void();
+    testing::assert_round_trip(&module);
+    testing::assert_ir(&module, "
+        define void @compare(i32, double) {
+        entry:
+          %2 = icmp eq i32 %0, 5
+          %3 = icmp ult i32 %0, 5
+          %4 = icmp sge i32 %0, 5
+          %5 = fcmp oeq double %1, 1.5
+          %6 = fcmp ult double %1, 1.5
+          %7 = fcmp ord double %1, 1.5
+          ret void
+        }
+    ");
+    assert!(testing::check_ir(&module, "define void @compare(i32, double) {\nentry:\n  ret void\n}").is_err());
+    let structs = Module::new("structs", &ctx);
+    let pair = StructType::new_named(&ctx, "Pair", &[Type::get::<u32>(&ctx), Type::get::<u32>(&ctx)], false);
+    structs.add_global("pair", pair);
+    testing::assert_round_trip(&structs);
+    assert!(Module::parse_ir(&ctx, "define i32 @broken() {").is_err());
+}
+
+#[test]
+fn test_linkages() {
+    let ctx = Context::new();
+    let module = Module::new("linkages", &ctx);
+    let global = module.add_global_variable("global", 1u32.compile(&ctx));
+    for &linkage in &[Linkage::External, Linkage::AvailableExternally, Linkage::LinkOnceAny, Linkage::LinkOnceODR,
+                      Linkage::WeakAny, Linkage::WeakODR, Linkage::Appending, Linkage::Internal, Linkage::Private,
+                      Linkage::ExternalWeak, Linkage::Common] {
+        global.set_linkage(linkage);
+        assert_eq!(global.get_linkage(), linkage);
+    }
+}
+
+#[test]
+fn test_global_storage() {
+    let ctx = Context::new();
+    let module = Module::new("storage", &ctx);
+    let global = module.add_global_variable("message", "hello".compile(&ctx));
+    assert_eq!(global.get_unnamed_addr(), UnnamedAddr::None);
+    global.set_unnamed_addr(UnnamedAddr::Global);
+    assert_eq!(global.get_unnamed_addr(), UnnamedAddr::Global);
+    assert!(format!("{:?}", module).contains("unnamed_addr"));
+    global.set_unnamed_addr(UnnamedAddr::Local);
+    assert_eq!(global.get_unnamed_addr(), UnnamedAddr::None);
+    for &class in &[DLLStorageClass::DLLImport, DLLStorageClass::DLLExport, DLLStorageClass::Default] {
+        global.set_dll_storage_class(class);
+        assert_eq!(global.get_dll_storage_class(), class);
+    }
+}
+
+#[test]
+fn test_strip_debug_info() {
+    let ctx = Context::new();
+    let module = Module::parse_ir(&ctx, r#"
+define i32 @next(i32 %x) {
+  call void @llvm.dbg.value(metadata i32 %x, i64 0, metadata !8, metadata !9), !dbg !10
+  %y = add i32 %x, 1, !dbg !10
+  ret i32 %y, !dbg !10
+}
+declare void @llvm.dbg.value(metadata, i64, metadata, metadata)
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!7}
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: "test", isOptimized: false, runtimeVersion: 0, emissionKind: 1, subprograms: !2)
+!1 = !DIFile(filename: "test.c", directory: "/")
+!2 = !{!3}
+!3 = distinct !DISubprogram(name: "next", scope: !1, file: !1, line: 1, type: !4, isLocal: false, isDefinition: true, scopeLine: 1, isOptimized: false, function: i32 (i32)* @next)
+!4 = !DISubroutineType(types: !5)
+!5 = !{!6, !6}
+!6 = !DIBasicType(name: "int", size: 32, align: 32, encoding: DW_ATE_signed)
+!7 = !{i32 2, !"Debug Info Version", i32 3}
+!8 = !DILocalVariable(name: "x", arg: 1, scope: !3, file: !1, line: 1, type: !6)
+!9 = !DIExpression()
+!10 = !DILocation(line: 2, column: 3, scope: !3)
+"#).unwrap();
+    module.strip_debug_info();
+    module.verify().unwrap();
+    assert_eq!(module.num_instructions(), 2);
+    let ir = format!("{:?}", module);
+    assert!(!ir.contains("!dbg"));
+    assert!(!ir.contains("call void @llvm.dbg.value"));
+    assert!(ir.contains("!llvm.dbg.cu"));
+    assert!(ir.contains("Debug Info Version"));
+    assert!(ir.contains("DICompileUnit"));
+}
+
+#[test]
+fn test_debug_location() {
+    let ctx = Context::new();
+    let module = Module::parse_ir(&ctx, r#"
+define void @f() {
+  ret void, !dbg !5
+}
+!llvm.dbg.cu = !{!0}
+!llvm.module.flags = !{!7}
+!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: "test", isOptimized: false, runtimeVersion: 0, emissionKind: 1, subprograms: !2)
+!1 = !DIFile(filename: "test.c", directory: "/")
+!2 = !{!3}
+!3 = distinct !DISubprogram(name: "f", scope: !1, file: !1, line: 1, type: !4, isLocal: false, isDefinition: true, scopeLine: 1, isOptimized: false)
+!4 = !DISubroutineType(types: !6)
+!5 = !DILocation(line: 2, column: 3, scope: !3)
+!6 = !{null}
+!7 = !{i32 2, !"Debug Info Version", i32 3}
+"#).unwrap();
+    let func = module.get_function("f").unwrap();
+    let ret = Instruction::from_super(func.get_entry().unwrap().get_first().unwrap()).unwrap();
+    let loc = ret.get_debug_location().unwrap();
+    let builder = Builder::new(&ctx);
+    assert!(builder.get_current_debug_location().is_none());
+    builder.position_before(ret);
+    builder.set_current_debug_location(loc);
+    assert_eq!(builder.get_current_debug_location(), Some(loc));
+    let located = Instruction::from_super(builder.build_alloca(Type::get::<u32>(&ctx))).unwrap();
+    assert_eq!(located.get_debug_location(), Some(loc));
+    builder.clear_debug_location();
+    assert!(builder.get_current_debug_location().is_none());
+    let unlocated = Instruction::from_super(builder.build_alloca(Type::get::<u32>(&ctx))).unwrap();
+    assert!(unlocated.get_debug_location().is_none());
+}
+
+#[test]
+fn test_overloaded_intrinsic() {
+    let ctx = Context::new();
+    let module = Module::new("intrinsics", &ctx);
+    let (ptr, size) = (PointerType::new(Type::get::<i8>(&ctx)), Type::get::<i64>(&ctx));
+    let sig = FunctionType::new(Type::get::<()>(&ctx), &[ptr, ptr, size, Type::get::<u32>(&ctx), Type::get::<bool>(&ctx)]);
+    let memcpy = module.get_overloaded_intrinsic("llvm.memcpy", &[ptr, ptr, size], sig).unwrap();
+    assert_eq!(memcpy.get_name(), Some("llvm.memcpy.p0i8.p0i8.i64"));
+    let vector = VectorType::new(Type::get::<f32>(&ctx), 4);
+    let sqrt = module.get_overloaded_intrinsic("llvm.sqrt", &[vector], FunctionType::new(vector, &[vector])).unwrap();
+    assert_eq!(sqrt.get_name(), Some("llvm.sqrt.v4f32"));
+    let double = Type::get::<f64>(&ctx);
+    assert!(module.get_overloaded_intrinsic("llvm.sqrt", &[double], FunctionType::new(vector, &[vector])).is_err());
+    module.verify().unwrap();
+}
+
+#[test]
+fn test_split() {
+    let ctx = Context::new();
+    let module = testing::random_module(&ctx, 7, &testing::RandomConfig { num_functions: 6, ..testing::RandomConfig::default() });
+    let counter = module.add_global_variable("counter", 0u32.compile(&ctx));
+    counter.set_linkage(Linkage::Internal);
+    module.get_function("f0").unwrap().set_linkage(Linkage::Internal);
+    let parts = module.split(3);
+    assert_eq!(parts.len(), 3);
+    for name in &["f0", "f1", "f2", "f3", "f4", "f5"] {
+        let defined = parts.iter().filter(|part| !part.get_function(name).unwrap().is_declaration()).count();
+        assert_eq!(defined, 1);
+    }
+    assert_eq!(parts.iter().filter(|part| !part.get_global("counter").unwrap().is_declaration()).count(), 1);
+    let mut parts = parts.into_iter();
+    let linked = parts.next().unwrap();
+    linked.verify().unwrap();
+    for part in parts {
+        part.verify().unwrap();
+        linked.link_destroy(part).unwrap();
+    }
+    linked.verify().unwrap();
+    assert_eq!(linked.num_instructions(), module.num_instructions());
+}
+
+#[test]
+fn test_split_invoke() {
+    let ctx = Context::new();
+    let module = Module::new("eh", &ctx);
+    let may_throw = module.add_function("may_throw", Type::get::<fn()>(&ctx));
+    let personality = module.add_function("__gxx_personality_v0", Type::get::<fn() -> i32>(&ctx));
+    let builder = Builder::new(&ctx);
+    for name in &["f", "g"] {
+        let func = module.add_function(name, Type::get::<fn()>(&ctx));
+        let (entry, ok, catch) = (func.append("entry"), func.append("ok"), func.append("catch"));
+        builder.position_at_end(entry);
+        builder.build_invoke(may_throw, &[], ok, catch);
+        builder.position_at_end(ok);
+        builder.build_ret_void();
+        builder.position_at_end(catch);
+        let exn_ty = StructType::new(&ctx, &[Type::get::<Option<&u8>>(&ctx), Type::get::<i32>(&ctx)], false);
+        let pad = builder.build_landing_pad(exn_ty, personality, 0);
+        pad.set_cleanup(true);
+        builder.build_resume(pad);
+    }
+    module.verify().unwrap();
+    let parts = module.split(2);
+    for part in &parts {
+        part.verify().unwrap();
+    }
+    for name in &["f", "g"] {
+        let defined = parts.iter().filter(|part| !part.get_function(name).unwrap().is_declaration()).count();
+        assert_eq!(defined, 1);
+    }
+}
+
+#[test]
+fn test_catch_fatal_errors() {
+    let result = catch_fatal_errors(|| {
+        let ctx = Context::new();
+        let module = Module::new("worker", &ctx);
+        module.add_function("f", Type::get::<fn(u32) -> u32>(&ctx));
+        module.verify().is_ok()
+    });
+    assert_eq!(result, Ok(true));
+    assert_eq!(get_fatal_error(), None);
+}
+
+#[test]
+fn test_export_manifest() {
+    let ctx = Context::new();
+    let module = Module::parse_ir(&ctx, r#"
+%"struct.odd name" = type { i32 }
+@counter = global i32 0
+@"weird.name" = global i32 1
+@int = global i32 2
+@hidden = internal global i32 3
+@table = appending global [1 x i32] [i32 1]
+@llvm.used = appending global [1 x i8*] [i8* bitcast (i32* @counter to i8*)], section "llvm.metadata"
+define i32 @add(i32 %a, i32 %b) {
+  %sum = add i32 %a, %b
+  ret i32 %sum
+}
+define void @touch(%"struct.odd name"* %p) {
+  ret void
+}
+"#).unwrap();
+    let names: Vec<_> = module.export_manifest().iter().map(|symbol| symbol.name).collect();
+    assert_eq!(names, vec!["add", "touch", "counter", "weird.name", "int"]);
+}
+
+#[test]
+fn test_c_header() {
+    let ctx = Context::new();
+    let module = Module::parse_ir(&ctx, r#"
+%"struct.odd name" = type { i32 }
+@counter = global i32 0
+@"weird.name" = global i32 1
+@int = global i32 2
+@hidden = internal global i32 3
+@table = appending global [1 x i32] [i32 1]
+@llvm.used = appending global [1 x i8*] [i8* bitcast (i32* @counter to i8*)], section "llvm.metadata"
+define i32 @add(i32 %a, i32 %b) {
+  %sum = add i32 %a, %b
+  ret i32 %sum
+}
+define void @touch(%"struct.odd name"* %p) {
+  ret void
+}
+"#).unwrap();
+    let header = module.to_c_header("ODD_H");
+    assert!(header.contains("int32_t add(int32_t, int32_t);"));
+    assert!(header.contains("struct struct_odd_name;"));
+    assert!(header.contains("void touch(struct struct_odd_name*);"));
+    assert!(header.contains("extern int32_t counter;"));
+    assert!(!header.contains("weird"));
+    assert!(!header.contains(" int;"));
+    assert!(!header.contains("table"));
+    assert!(!header.contains("llvm"));
+}
@@ -0,0 +1,84 @@
+extern crate llvm;
+use llvm::*;
+
+#[test]
+fn test_target_features() {
+    let features = TargetFeatures::new().add("+avx2").remove("sse4.1").add("fma").remove("avx2");
+    assert_eq!(features.to_string(), "-sse4.1,+fma,-avx2");
+    assert_eq!(features.is_enabled("fma"), Some(true));
+    assert_eq!(features.is_enabled("avx"), None);
+    assert_eq!(TargetFeatures::parse("+avx2,-sse4.1").unwrap(), TargetFeatures::new().add("avx2").remove("sse4.1"));
+    assert_eq!(TargetFeatures::parse("avx2"), Err(InvalidFeature("avx2".to_owned())));
+    assert!(TargetFeatures::parse("+avx 2").is_err());
+}
+
+#[test]
+fn test_float_abi_triple() {
+    assert_eq!(FloatAbi::Hard.adjust_triple("thumbv7em-none-eabi"), "thumbv7em-none-eabihf");
+    assert_eq!(FloatAbi::Soft.adjust_triple("thumbv7em-none-eabihf"), "thumbv7em-none-eabi");
+    assert_eq!(FloatAbi::SoftFP.adjust_triple("thumbv7em-none-eabi"), "thumbv7em-none-eabi");
+    assert_eq!(FloatAbi::Hard.adjust_triple("x86_64-unknown-linux-gnu"), "x86_64-unknown-linux-gnu");
+}
+
+#[test]
+fn test_aot_compiler() {
+    let target = Target::native().unwrap();
+    let triple = TargetMachine::new_native(0).unwrap().get_triple();
+    assert!(target.has_target_machine());
+    let ctx = Context::new();
+    let module = testing::random_module(&ctx, 3, &testing::RandomConfig::default());
+    let output = std::env::temp_dir().join("llvm-rs-aot-test");
+    let compiler = aot::Compiler::new(&triple).add_module(&module).threads(2).linker("llvm-rs-missing-linker");
+    match compiler.compile(&output, aot::Artifact::Object) {
+        Err(aot::CompileError::Io(_)) => (),
+        other => panic!("expected the missing linker to fail, got {:?}", other)
+    }
+    for index in 0..2 {
+        assert!(!output.with_extension(format!("{}.o", index)).exists());
+    }
+}
+
+#[test]
+fn test_aot_object() {
+    Target::native().unwrap();
+    let triple = TargetMachine::new_native(0).unwrap().get_triple();
+    let ctx = Context::new();
+    let module = testing::random_module(&ctx, 5, &testing::RandomConfig::default());
+    let instructions = module.num_instructions();
+    let output = std::env::temp_dir().join("llvm-rs-aot-object.o");
+    let compiler = aot::Compiler::new(&triple).add_module(&module).threads(2).linker("ld");
+    compiler.compile(&output, aot::Artifact::Object).unwrap();
+    assert_eq!(module.num_instructions(), instructions);
+    module.verify().unwrap();
+    let size = std::fs::metadata(&output).unwrap().len();
+    std::fs::remove_file(&output).unwrap();
+    assert!(size > 0);
+    for index in 0..2 {
+        assert!(!output.with_extension(format!("{}.o", index)).exists());
+    }
+}
+
+#[test]
+fn test_multiversion() {
+    let ctx = Context::new();
+    let module = Module::new("versions", &ctx);
+    let baseline = TargetFeatures::new();
+    let fast = TargetFeatures::parse("+sse2,-avx").unwrap();
+    let builder = Builder::new(&ctx);
+    let first = |func: &Function| {
+        builder.position_at_end(func.append("entry"));
+        builder.build_ret(0u32.compile(&ctx));
+    };
+    let second = |func: &Function| {
+        builder.position_at_end(func.append("entry"));
+        builder.build_ret(1u32.compile(&ctx));
+    };
+    let versions = Multiversion::new(&module, "pick", Type::get::<fn() -> u32>(&ctx), &[(&baseline, &first), (&fast, &second)]);
+    module.verify().unwrap();
+    assert_eq!(versions.get_features(1), &fast);
+    let ee = JitEngine::new(&module, JitOptions {opt_level: 0}).unwrap();
+    assert_eq!(versions.select(&*ee, &|_| false), 0);
+    ee.with_function(versions.get_dispatcher(), |pick: extern fn() -> u32| assert_eq!(pick(), 0));
+    assert_eq!(versions.select(&*ee, &|feature| feature == "sse2"), 1);
+    ee.with_function(versions.get_dispatcher(), |pick: extern fn() -> u32| assert_eq!(pick(), 1));
+}
@@ -0,0 +1,27 @@
+extern crate llvm;
+use llvm::*;
+
+#[test]
+fn test_typed_constants() {
+    let ctx = Context::new();
+    let int = IntegerType::from_super(Type::get::<u8>(&ctx)).unwrap();
+    assert!(IntegerType::from_super(Type::get::<Option<&u8>>(&ctx)).is_none());
+    assert_eq!(int.const_int(300), 44u8.compile(&ctx));
+    assert_eq!(int.const_signed(-1), int.const_all_ones());
+    assert_eq!(int.const_all_ones(), 255u8.compile(&ctx));
+    let real = RealType::from_super(Type::get::<f64>(&ctx)).unwrap();
+    assert_eq!(real.const_float(1.5), 1.5f64.compile(&ctx));
+    let ptr = PointerType::from_super(Type::get::<Option<&u8>>(&ctx)).unwrap();
+    assert_eq!(ptr.const_null().get_type(), Type::get::<Option<&u8>>(&ctx));
+}
+
+#[test]
+fn test_type_kind() {
+    let ctx = Context::new();
+    assert_eq!(Type::get::<()>(&ctx).kind(), TypeKind::Void);
+    assert_eq!(Type::get::<u32>(&ctx).kind(), TypeKind::Integer);
+    assert_eq!(Type::get::<f32>(&ctx).kind(), TypeKind::Float);
+    assert_eq!(Type::get::<[u8; 4]>(&ctx).kind(), TypeKind::Array);
+    assert_eq!(Type::get::<fn(u8) -> u8>(&ctx).kind(), TypeKind::Function);
+    assert_eq!(Type::get::<u32>(&ctx).to_string(), "i32");
+}